@@ -0,0 +1,108 @@
+/// Arguments of a subcommand.
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct Args {
+    pub positional: Vec<String>,
+    flags: Vec<String>,
+    options: Vec<(String, String)>
+}
+
+/// Flags and options accepted by a subcommand, options take a value (`--filter tcp` or `--filter=tcp`).
+pub struct Spec {
+    pub flags: &'static [&'static str],
+    pub options: &'static [&'static str]
+}
+
+impl Args {
+
+    /// Parse the arguments following the subcommand, `--` ends the flags and options.
+    pub fn parse<I: IntoIterator<Item = String>>(spec: &Spec, arguments: I) -> Result<Args, String> {
+
+        let mut args = Args::default();
+        let mut arguments = arguments.into_iter();
+
+        while let Some(argument) = arguments.next() {
+            if argument == "--" {
+                args.positional.extend(arguments);
+                break;
+            }
+
+            if !argument.starts_with('-') || argument == "-" {
+                args.positional.push(argument);
+                continue;
+            }
+
+            let (name, inline_value) = match argument.split_once('=') {
+                Some((name, value)) => (name.to_string(), Some(value.to_string())),
+                None => (argument, None)
+            };
+
+            if spec.flags.contains(&name.as_str()) {
+                if inline_value.is_some() {
+                    return Err(format!("{} doesn't take a value", name));
+                }
+                args.flags.push(name);
+            }
+            else if spec.options.contains(&name.as_str()) {
+                let value = match inline_value {
+                    Some(value) => value,
+                    None => arguments.next().ok_or_else(|| format!("{} expects a value", name))?
+                };
+                args.options.push((name, value));
+            }
+            else {
+                return Err(format!("unknown option {}", name));
+            }
+        }
+
+        Ok(args)
+    }
+
+    pub fn flag(&self, name: &str) -> bool {
+        self.flags.iter().any(|flag| flag == name)
+    }
+
+    /// Last value of the option.
+    pub fn option(&self, name: &str) -> Option<&str> {
+        self.options.iter().rev().find(|(option, _)| option == name).map(|(_, value)| value.as_str())
+    }
+
+    /// Values of a repeatable option, in order.
+    pub fn options(&self, name: &str) -> Vec<&str> {
+        self.options.iter().filter(|(option, _)| option == name).map(|(_, value)| value.as_str()).collect()
+    }
+
+    /// Positional arguments, checking their count.
+    pub fn positional(&self, min: usize, max: usize, usage: &str) -> Result<&[String], String> {
+
+        if self.positional.len() < min || self.positional.len() > max {
+            return Err(format!("usage: pcap-assistant {}", usage));
+        }
+
+        Ok(&self.positional)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(arguments: &[&str]) -> Vec<String> {
+        arguments.iter().map(|argument| argument.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_args_test() {
+        let spec = Spec { flags: &["--vpp", "-q"], options: &["--filter", "--set"] };
+
+        let args = Args::parse(&spec, strings(&["in.pcap", "--vpp", "--set", "ip.ttl=1", "--set=eth.src=02:00:00:00:00:01", "out.pcap", "--", "--filter"])).unwrap();
+        assert_eq!(args.positional, strings(&["in.pcap", "out.pcap", "--filter"]));
+        assert!(args.flag("--vpp") && !args.flag("-q"));
+        assert_eq!(args.options("--set"), vec!["ip.ttl=1", "eth.src=02:00:00:00:00:01"]);
+        assert_eq!(args.option("--filter"), None);
+        assert!(args.positional(1, 2, "").is_err());
+
+        assert_eq!(Args::parse(&spec, strings(&["--filter"])), Err("--filter expects a value".to_string()));
+        assert_eq!(Args::parse(&spec, strings(&["--vpp=1"])), Err("--vpp doesn't take a value".to_string()));
+        assert_eq!(Args::parse(&spec, strings(&["--unknown"])), Err("unknown option --unknown".to_string()));
+    }
+}
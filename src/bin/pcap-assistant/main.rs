@@ -0,0 +1,545 @@
+//! Command-line interface of `PcapTester`, for shell-based test suites.
+//!
+//! Exits with 0 on success, 1 when the compared captures differ and 2 on errors.
+
+mod args;
+
+use args::{Args, Spec};
+
+use pcap_assistant::{
+    AssistantError,
+    CaptureReader,
+    DataLink,
+    filter::{BpfFilter, DisplayFilter, PacketFilter},
+    pcap::{Packet, VppPacket},
+    pcap_assistant::assistant::*
+};
+
+use std::{
+    env,
+    error::Error,
+    fs::File,
+    process::ExitCode
+};
+
+
+const USAGE: &str = "\
+Usage: pcap-assistant <command> [options] <files>
+
+Commands:
+  print [--vpp] <file>
+      Print the header and the hex data of every packet.
+
+  compare [--vpp | --lhs-vpp | --rhs-vpp] [--filter <bpf>] [--display-filter <expr>] [--ignore <field>]... [--match <strategy>]
+          [--check-headers] [--ts-tolerance <seconds>] [--relative-ts] [--map-interface <lhs>=<rhs>]... [-q] <lhs> <rhs>
+      Compare the packets of both captures, exits with 1 if they differ.
+
+  convert (--to-vpp | --from-vpp | --to-pcapng [--vpp]) <input> <output>
+      Convert between the classic, VPP and pcapng formats.
+
+  process [--vpp] [--filter <bpf>] [--display-filter <expr>] [--set <field>=<value>]... [--fix-checksums]
+          [--expect <expected>] [-q] <input> [<output>]
+      Keep the matching packets, rewrite their fields and save them to <output>,
+      or compare them with <expected> and exit with 1 if they differ.
+
+  merge [--vpp] [--tag-interfaces] -w <output> <input>...
+      Merge the packets of the inputs in timestamp order.
+
+  split [--vpp] (--packets <n> | --duration <seconds> | --bytes <n> | --flow | --interface) -w <template> <input>
+      Split the input in several files named after <template>, where {index} is the number
+      of the file and {key} the flow or the interface index. Prints the new files.
+
+  repair [--vpp] <input> <output>
+      Save the packets which can be recovered from a corrupted or truncated capture,
+      prints the skipped regions.
+
+  scenario [-q] <scenario | directory>...
+      Run the scenario files, and the .toml scenarios of the directories, printing PASS, FAIL or ERROR
+      per scenario and the comparison report of the failed ones. Exits with 1 if a scenario doesn't pass.
+
+Options:
+  --vpp                    Captures are in the VPP format, with an interface index per packet
+  --filter <bpf>           Keep the packets matching a pcap filter expression (tcpdump syntax)
+  --display-filter <expr>  Keep the packets matching a display filter (Wireshark syntax)
+  --set <field>=<value>    Rewrite a field (eth.src, ip.dst, tcp.dstport, ip.ttl, vlan.id...)
+  --ignore <field>         Leave a field (ip.ttl, ip.checksum, eth.src...) or the bits of a byte mask
+                           written <offset>:<hex mask> (22:ff) out of the comparison
+  --match <strategy>       Pair the compared packets by index (default), by alignment, reporting the dropped
+                           and inserted packets, or by content with multiset[:<seconds window>]
+  --check-headers          Fail on any header difference, otherwise they're only reported
+  --ts-tolerance <seconds> Fail on timestamps differing by more than the tolerance
+  --relative-ts            Fail on timestamps differing from the first packet of each file, to check the pacing
+  --map-interface <lhs>=<rhs>
+                           Fail on interface indexes differing, after mapping the left index to the right one
+  --fix-checksums          Recompute the IP and transport checksums
+  --tag-interfaces         Save VPP packets whose interface index is the position of their input
+  -w <output>              File to write to, or template of the files to write to
+  -q, --quiet              Don't print the comparison report
+  -h, --help               Print this help
+";
+
+const EXIT_SUCCESS: u8 = 0;
+const EXIT_MISMATCH: u8 = 1;
+const EXIT_FAILURE: u8 = 2;
+
+type CliResult = Result<bool, Box<dyn Error>>;
+
+fn main() -> ExitCode {
+    ExitCode::from(run(env::args().skip(1)))
+}
+
+// Run the command of the arguments, returns the exit code
+fn run<I: Iterator<Item = String>>(mut arguments: I) -> u8 {
+
+    let result = match arguments.next().as_deref() {
+        Some("print") => print(arguments),
+        Some("compare") => compare(arguments),
+        Some("convert") => convert(arguments),
+        Some("process") => process(arguments),
+        Some("merge") => merge(arguments),
+        Some("split") => split(arguments),
+        Some("repair") => repair(arguments),
+        Some("scenario") => scenario(arguments),
+        Some("help" | "-h" | "--help") => {
+            print!("{}", USAGE);
+            return EXIT_SUCCESS;
+        },
+        Some(command) => Err(format!("unknown command '{}'\n\n{}", command, USAGE).into()),
+        None => Err(USAGE.into())
+    };
+
+    match result {
+        Ok(true) => EXIT_SUCCESS,
+        Ok(false) => EXIT_MISMATCH,
+        Err(error) => {
+            eprintln!("pcap-assistant: {}", describe(error.as_ref()));
+            EXIT_FAILURE
+        }
+    }
+}
+
+// Error message followed by its sources
+fn describe(error: &dyn Error) -> String {
+
+    let mut message = error.to_string();
+    let mut source = error.source();
+
+    while let Some(error) = source {
+        message.push_str(&format!(": {}", error));
+        source = error.source();
+    }
+
+    message
+}
+
+fn print<I: IntoIterator<Item = String>>(arguments: I) -> CliResult {
+
+    let args = Args::parse(&Spec { flags: &["--vpp"], options: &[] }, arguments)?;
+    let file = &args.positional(1, 1, "print [--vpp] <file>")?[0];
+
+    if args.flag("--vpp") {
+        PcapTester::print_file::<VppPacket>(file)?;
+    }
+    else {
+        PcapTester::print_file::<Packet>(file)?;
+    }
+
+    Ok(true)
+}
+
+fn compare<I: IntoIterator<Item = String>>(arguments: I) -> CliResult {
+
+    let spec = Spec {
+        flags: &["--vpp", "--lhs-vpp", "--rhs-vpp", "--check-headers", "--relative-ts", "-q", "--quiet"],
+        options: &["--filter", "--display-filter", "--ignore", "--match", "--ts-tolerance", "--map-interface"]
+    };
+    let args = Args::parse(&spec, arguments)?;
+    let files = args.positional(2, 2, "compare [options] <lhs> <rhs>")?;
+    let (lhs, rhs) = (&files[0], &files[1]);
+
+    let datalink = datalink(lhs)?;
+    let filter = CliFilter::new(&args, datalink)?;
+    let comparator = comparator(&args, datalink)?;
+    let env = PcapTester::new(lhs);
+
+    let report = match (args.flag("--vpp") || args.flag("--lhs-vpp"), args.flag("--vpp") || args.flag("--rhs-vpp")) {
+        (false, false) => env.compare_files_filtered_with::<Packet, Packet, _>(rhs, &filter, &comparator)?,
+        (false, true) => env.compare_files_filtered_with::<Packet, VppPacket, _>(rhs, &filter, &comparator)?,
+        (true, false) => env.compare_files_filtered_with::<VppPacket, Packet, _>(rhs, &filter, &comparator)?,
+        (true, true) => env.compare_files_filtered_with::<VppPacket, VppPacket, _>(rhs, &filter, &comparator)?
+    };
+
+    if !quiet(&args) {
+        report.print();
+    }
+
+    Ok(report.is_equal())
+}
+
+// Comparator of the compare options: ignored fields, match strategy and header comparison
+fn comparator(args: &Args, datalink: DataLink) -> Result<PacketComparator, String> {
+
+    let mut options = ComparisonOptions {
+        check_headers: args.flag("--check-headers"),
+        relative_timestamps: args.flag("--relative-ts"),
+        ..Default::default()
+    };
+
+    for rule in args.options("--ignore") {
+        options.ignore.push(IgnoreRule::parse(rule).map_err(|error| format!("--ignore {}: {}", rule, error))?);
+    }
+    if let Some(strategy) = args.option("--match") {
+        options.strategy = strategy.parse().map_err(|error| format!("--match {}: {}", strategy, error))?;
+    }
+    if let Some(seconds) = args.option("--ts-tolerance") {
+        options.timestamp_tolerance = Some(parse_seconds(seconds).map_err(|_| format!("--ts-tolerance expects a number of seconds, found '{}'", seconds))?);
+    }
+    for mapping in args.options("--map-interface") {
+        options.interface_map.push(parse_interface_mapping(mapping).map_err(|_| format!("--map-interface expects <lhs>=<rhs> interface indexes, found '{}'", mapping))?);
+    }
+
+    Ok(options.comparator(datalink))
+}
+
+fn convert<I: IntoIterator<Item = String>>(arguments: I) -> CliResult {
+
+    let args = Args::parse(&Spec { flags: &["--to-vpp", "--from-vpp", "--to-pcapng", "--vpp"], options: &[] }, arguments)?;
+    let usage = "convert (--to-vpp | --from-vpp | --to-pcapng [--vpp]) <input> <output>";
+    let files = args.positional(2, 2, usage)?;
+    let env = PcapTester::new(&files[0]);
+
+    match (args.flag("--to-vpp"), args.flag("--from-vpp"), args.flag("--to-pcapng")) {
+        (true, false, false) => env.convert_to_vpp(&files[1])?,
+        (false, true, false) => env.convert_from_vpp(&files[1])?,
+        (false, false, true) if args.flag("--vpp") => env.convert_to_pcapng::<VppPacket>(&files[1])?,
+        (false, false, true) => env.convert_to_pcapng::<Packet>(&files[1])?,
+        _ => return Err(format!("usage: pcap-assistant {}", usage).into())
+    }
+
+    Ok(true)
+}
+
+fn process<I: IntoIterator<Item = String>>(arguments: I) -> CliResult {
+
+    let spec = Spec {
+        flags: &["--vpp", "--fix-checksums", "-q", "--quiet"],
+        options: &["--filter", "--display-filter", "--set", "--expect"]
+    };
+    let args = Args::parse(&spec, arguments)?;
+    let files = args.positional(1, 2, "process [options] <input> [<output>]")?;
+    let input = &files[0];
+    let expected = args.option("--expect");
+
+    if files.len() == 1 && expected.is_none() {
+        return Err("process needs an <output> file or --expect <expected>".into());
+    }
+
+    let datalink = datalink(input)?;
+    let vpp = args.flag("--vpp");
+
+    if let Some(output) = files.get(1) {
+        let env = PcapTester::new(input);
+        let mut processor = cli_processor(&args, datalink)?;
+
+        if vpp {
+            env.process_and_save::<VppPacket>(output, &mut processor)?;
+        }
+        else {
+            env.process_and_save::<Packet>(output, &mut processor)?;
+        }
+    }
+
+    let expected = match expected {
+        Some(expected) => expected,
+        None => return Ok(true)
+    };
+
+    let env = PcapTester::new(expected);
+    let mut processor = cli_processor(&args, datalink)?;
+
+    let report = if vpp {
+        env.process_and_compare_files::<_, VppPacket>(input, &mut processor)?
+    }
+    else {
+        env.process_and_compare_files::<_, Packet>(input, &mut processor)?
+    };
+
+    if !quiet(&args) {
+        report.print();
+    }
+
+    Ok(report.is_equal())
+}
+
+fn merge<I: IntoIterator<Item = String>>(arguments: I) -> CliResult {
+
+    let args = Args::parse(&Spec { flags: &["--vpp", "--tag-interfaces"], options: &["-w"] }, arguments)?;
+    let usage = "merge [--vpp] [--tag-interfaces] -w <output> <input>...";
+    let inputs: Vec<&str> = args.positional(1, usize::MAX, usage)?.iter().map(String::as_str).collect();
+    let output = args.option("-w").ok_or_else(|| format!("usage: pcap-assistant {}", usage))?;
+
+    if args.flag("--vpp") {
+        PcapTester::merge_files::<VppPacket>(&inputs, output, args.flag("--tag-interfaces"))?;
+    }
+    else {
+        PcapTester::merge_files::<Packet>(&inputs, output, args.flag("--tag-interfaces"))?;
+    }
+
+    Ok(true)
+}
+
+fn split<I: IntoIterator<Item = String>>(arguments: I) -> CliResult {
+
+    let spec = Spec { flags: &["--vpp", "--flow", "--interface"], options: &["--packets", "--duration", "--bytes", "-w"] };
+    let args = Args::parse(&spec, arguments)?;
+    let usage = "split [--vpp] (--packets <n> | --duration <seconds> | --bytes <n> | --flow | --interface) -w <template> <input>";
+    let input = &args.positional(1, 1, usage)?[0];
+    let template = args.option("-w").ok_or_else(|| format!("usage: pcap-assistant {}", usage))?;
+
+    let mut policies = Vec::new();
+    if let Some(count) = args.option("--packets") {
+        policies.push(SplitPolicy::Packets(count.parse().map_err(|_| format!("--packets expects a number, found '{}'", count))?));
+    }
+    if let Some(seconds) = args.option("--duration") {
+        let seconds = parse_seconds(seconds).map_err(|_| format!("--duration expects a number of seconds, found '{}'", seconds))?;
+        policies.push(SplitPolicy::Duration(seconds));
+    }
+    if let Some(bytes) = args.option("--bytes") {
+        policies.push(SplitPolicy::Bytes(bytes.parse().map_err(|_| format!("--bytes expects a number, found '{}'", bytes))?));
+    }
+    if args.flag("--flow") {
+        policies.push(SplitPolicy::Flow);
+    }
+    if args.flag("--interface") {
+        policies.push(SplitPolicy::Interface);
+    }
+
+    let policy = match policies.as_slice() {
+        [policy] => *policy,
+        _ => return Err(format!("usage: pcap-assistant {}", usage).into())
+    };
+
+    let env = PcapTester::new(input);
+    let paths = if args.flag("--vpp") {
+        env.split_file::<VppPacket>(policy, template)?
+    }
+    else {
+        env.split_file::<Packet>(policy, template)?
+    };
+
+    for path in paths {
+        println!("{}", path);
+    }
+
+    Ok(true)
+}
+
+fn repair<I: IntoIterator<Item = String>>(arguments: I) -> CliResult {
+
+    let args = Args::parse(&Spec { flags: &["--vpp"], options: &[] }, arguments)?;
+    let files = args.positional(2, 2, "repair [--vpp] <input> <output>")?;
+    let env = PcapTester::new(&files[0]);
+
+    let skipped = if args.flag("--vpp") {
+        env.repair_file::<VppPacket>(&files[1])?
+    }
+    else {
+        env.repair_file::<Packet>(&files[1])?
+    };
+
+    for region in skipped {
+        println!("Skipped {} bytes at offset {}: {:?}", region.len, region.offset, region.reason);
+    }
+
+    Ok(true)
+}
+
+fn scenario<I: IntoIterator<Item = String>>(arguments: I) -> CliResult {
+
+    let args = Args::parse(&Spec { flags: &["-q", "--quiet"], options: &[] }, arguments)?;
+    let paths = args.positional(1, usize::MAX, "scenario [-q] <scenario | directory>...")?;
+
+    let report = run_scenarios(paths)?;
+    report.print(!quiet(&args));
+
+    Ok(report.passed())
+}
+
+fn quiet(args: &Args) -> bool {
+    args.flag("-q") || args.flag("--quiet")
+}
+
+// DataLink of the capture, used to compile the filters and to rewrite the fields
+fn datalink(path: &str) -> Result<DataLink, AssistantError> {
+
+    let file = File::open(path).map_err(|source| AssistantError::FileOpen { path: path.to_string(), source })?;
+    let reader = CaptureReader::<_, Packet>::new(file).map_err(|source| AssistantError::HeaderParse { path: path.to_string(), source })?;
+
+    Ok(reader.datalink())
+}
+
+/// Filters of `--filter` and `--display-filter`, a packet must match both.
+struct CliFilter {
+    bpf: Option<BpfFilter>,
+    display: Option<DisplayFilter>
+}
+
+impl CliFilter {
+
+    fn new(args: &Args, datalink: DataLink) -> Result<CliFilter, Box<dyn Error>> {
+
+        let bpf = args.option("--filter").map(|expression| BpfFilter::new(expression, datalink)).transpose()?;
+        let display = args.option("--display-filter").map(DisplayFilter::new).transpose()?;
+
+        Ok(CliFilter { bpf, display })
+    }
+}
+
+impl PacketFilter for CliFilter {
+
+    fn matches(&self, context: &PacketContext, data: &[u8]) -> bool {
+
+        self.bpf.as_ref().is_none_or(|bpf| bpf.matches(context, data))
+            && self.display.as_ref().is_none_or(|display| display.matches(context, data))
+    }
+}
+
+/// Drops the packets which don't match the filters, to chain them before the other processors.
+impl ContextProcessor for CliFilter {
+
+    fn process_with_context(&mut self, context: &mut PacketContext, packet: &mut Vec<u8>) -> Result<bool, String> {
+        Ok(self.matches(context, packet))
+    }
+}
+
+// Processor of the `process` command: filters, then field rewrites, then checksums
+fn cli_processor(args: &Args, datalink: DataLink) -> Result<ProcessorChain, Box<dyn Error>> {
+
+    let mut chain = ProcessorChain::new().with(CliFilter::new(args, datalink)?);
+
+    let assignments = args.options("--set");
+    if !assignments.is_empty() {
+        let mut rewriter = FieldRewriter::new(datalink);
+        for assignment in assignments {
+            let (field, value) = assignment.split_once('=')
+                .ok_or_else(|| format!("--set expects <field>=<value>, found '{}'", assignment))?;
+            rewriter = rewriter.set(field, value).map_err(|error| format!("--set {}: {}", assignment, error))?;
+        }
+        chain = chain.with(rewriter);
+    }
+
+    if args.flag("--fix-checksums") {
+        chain = chain.with(ChecksumFixer::new(datalink));
+    }
+
+    Ok(chain)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pcap_assistant::pcap::{PcapHeader, PcapReader, SomePacket};
+    use std::fs;
+
+    fn arguments(arguments: &[&str]) -> std::vec::IntoIter<String> {
+        arguments.iter().map(|argument| argument.to_string()).collect::<Vec<_>>().into_iter()
+    }
+
+    // Ethernet, IPv4 and UDP headers followed by a byte of payload
+    fn udp(ttl: u8, payload: u8) -> Vec<u8> {
+        let mut frame = vec![0x02, 0, 0, 0, 0, 2, 0x02, 0, 0, 0, 0, 1, 0x08, 0x00];
+        frame.extend_from_slice(&[0x45, 0, 0, 29, 0, 0, 0, 0, ttl, 17, 0, 0, 10, 0, 0, 1, 10, 0, 0, 2]);
+        frame.extend_from_slice(&[0x30, 0x39, 0x00, 0x35, 0, 9, 0, 0, payload]);
+        frame
+    }
+
+    fn write(path: &str, frames: &[Vec<u8>]) {
+        let packets = frames.iter().enumerate().map(|(index, frame)| Packet::new_owned(index as u32, 0, frame.clone(), frame.len() as u32)).collect();
+        PcapTester::save_packets_to_new_pcap(path, PcapHeader::default(), packets).unwrap();
+    }
+
+    fn write_vpp(path: &str, interface_indexes: &[u32]) {
+        let packets = interface_indexes.iter().map(|interface_index| {
+            let mut packet = VppPacket::new_owned(0, 0, udp(64, 0), 43);
+            packet.header.interface_index = *interface_index;
+            packet
+        }).collect();
+        PcapTester::save_packets_to_new_pcap(path, PcapHeader::default(), packets).unwrap();
+    }
+
+    fn count(path: &str) -> usize {
+        PcapReader::<_, Packet>::new(File::open(path).unwrap()).unwrap().count()
+    }
+
+    #[test]
+    fn exit_code_test() {
+        let dir = "cli_exit_code_test";
+        fs::create_dir_all(dir).unwrap();
+        let (lhs, rhs, routed) = (format!("{}/lhs.pcap", dir), format!("{}/rhs.pcap", dir), format!("{}/routed.pcap", dir));
+        write(&lhs, &[udp(64, 1), udp(64, 2)]);
+        write(&rhs, &[udp(64, 1), udp(64, 2)]);
+        write(&routed, &[udp(63, 1), udp(63, 2)]);
+
+        assert_eq!(run(arguments(&["compare", "-q", &lhs, &rhs])), EXIT_SUCCESS);
+        assert_eq!(run(arguments(&["compare", "-q", &lhs, &routed])), EXIT_MISMATCH);
+        assert_eq!(run(arguments(&["compare", "-q", &lhs, &format!("{}/missing.pcap", dir)])), EXIT_FAILURE);
+        assert_eq!(run(arguments(&["compare", "--ignore", "ip.tll", &lhs, &routed])), EXIT_FAILURE);
+        assert_eq!(run(arguments(&["compare", &lhs])), EXIT_FAILURE);
+        assert_eq!(run(arguments(&["sort", &lhs])), EXIT_FAILURE);
+        assert_eq!(run(arguments(&[])), EXIT_FAILURE);
+        assert_eq!(run(arguments(&["help"])), EXIT_SUCCESS);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn compare_command_test() {
+        let dir = "cli_compare_test";
+        fs::create_dir_all(dir).unwrap();
+        let (lhs, routed, reordered) = (format!("{}/lhs.pcap", dir), format!("{}/routed.pcap", dir), format!("{}/reordered.pcap", dir));
+        write(&lhs, &[udp(64, 1), udp(64, 2)]);
+        write(&routed, &[udp(63, 1), udp(63, 2)]);
+        write(&reordered, &[udp(64, 2), udp(64, 1)]);
+
+        assert!(!compare(arguments(&["-q", &lhs, &routed])).unwrap());
+        assert!(compare(arguments(&["-q", "--ignore", "ip.ttl", &lhs, &routed])).unwrap());
+        assert!(!compare(arguments(&["-q", &lhs, &reordered])).unwrap());
+        assert!(compare(arguments(&["-q", "--match", "multiset", &lhs, &reordered])).unwrap());
+        assert!(compare(arguments(&["--match", "lcs", &lhs, &reordered])).is_err());
+
+        // Interface indexes are only checked when they're mapped
+        let (vpp_lhs, vpp_rhs) = (format!("{}/vpp_lhs.pcap", dir), format!("{}/vpp_rhs.pcap", dir));
+        write_vpp(&vpp_lhs, &[1, 1]);
+        write_vpp(&vpp_rhs, &[2, 2]);
+        assert!(compare(arguments(&["-q", "--vpp", &vpp_lhs, &vpp_rhs])).unwrap());
+        assert!(compare(arguments(&["-q", "--vpp", "--map-interface", "1=2", &vpp_lhs, &vpp_rhs])).unwrap());
+        assert!(!compare(arguments(&["-q", "--vpp", "--map-interface", "1=3", &vpp_lhs, &vpp_rhs])).unwrap());
+        assert!(compare(arguments(&["--vpp", "--map-interface", "1:2", &vpp_lhs, &vpp_rhs])).is_err());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn repair_and_split_commands_test() {
+        let dir = "cli_repair_split_test";
+        fs::create_dir_all(dir).unwrap();
+        let (input, repaired) = (format!("{}/input.pcap", dir), format!("{}/repaired.pcap", dir));
+        write(&input, &[udp(64, 1), udp(64, 2), udp(64, 3)]);
+
+        // Truncated last packet
+        let mut truncated = fs::read(&input).unwrap();
+        truncated.truncate(truncated.len() - 5);
+        fs::write(&input, truncated).unwrap();
+        assert!(repair(arguments(&[&input, &repaired])).unwrap());
+        assert_eq!(count(&repaired), 2);
+
+        let template = format!("{}/part.pcap", dir);
+        assert!(split(arguments(&["--packets", "1", "-w", &template, &repaired])).unwrap());
+        assert_eq!((count(&format!("{}/part_0.pcap", dir)), count(&format!("{}/part_1.pcap", dir))), (1, 1));
+        assert!(split(arguments(&["--flow", "-w", &format!("{}/{{key}}.pcap", dir), &repaired])).unwrap());
+        assert_eq!(count(&format!("{}/udp_10.0.0.1_12345_10.0.0.2_53.pcap", dir)), 2);
+        assert!(split(arguments(&["--flow", "--packets", "1", "-w", &template, &repaired])).is_err());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
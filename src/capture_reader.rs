@@ -0,0 +1,119 @@
+use crate::{
+    DataLink,
+    myerrors::*,
+    pcap::{LengthPolicy, PcapReader, SomePacket},
+    pcapng::{PcapNgReader, SECTION_HEADER_BLOCK}
+};
+
+use std::io::{Read, Seek, SeekFrom};
+
+
+/// Reader of either a pcap or a pcapng stream, detected from its magic number.
+///
+/// It implements the Iterator trait in order to read one packet at a time
+/// whatever the underlying format.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use std::fs::File;
+/// use pcap_assistant::CaptureReader;
+/// use pcap_assistant::pcap::Packet;
+///
+/// let file_in = File::open("test.pcapng").expect("Error opening file");
+/// let reader = CaptureReader::<_, Packet>::new(file_in).unwrap();
+///
+/// for packet in reader {
+///     let packet = packet.unwrap();
+/// }
+/// ```
+#[derive(Debug)]
+pub enum CaptureReader<T: Read, P: SomePacket<'static>> {
+    Pcap(PcapReader<T, P>),
+    PcapNg(PcapNgReader<T, P>)
+}
+
+impl<T: Read + Seek, P: SomePacket<'static>> CaptureReader<T, P> {
+
+    /// Create a new `CaptureReader` from an existing reader positioned at the start of a capture.
+    ///
+    /// # Errors
+    /// Return an error if the stream is neither a valid pcap nor a valid pcapng.
+    pub fn new(mut reader: T) -> ResultParsing<Self> {
+
+        let start = reader.stream_position()?;
+
+        let mut magic = [0_u8; 4];
+        reader.read_exact(&mut magic)?;
+        reader.seek(SeekFrom::Start(start))?;
+
+        if u32::from_be_bytes(magic) == SECTION_HEADER_BLOCK {
+            Ok(CaptureReader::PcapNg(PcapNgReader::new(reader)?))
+        }
+        else {
+            Ok(CaptureReader::Pcap(PcapReader::new(reader)?))
+        }
+    }
+}
+
+impl<T: Read, P: SomePacket<'static>> CaptureReader<T, P> {
+
+    /// DataLink of the capture, the one of the first interface for a pcapng.
+    pub fn datalink(&self) -> DataLink {
+
+        match self {
+            CaptureReader::Pcap(reader) => reader.header.datalink,
+            CaptureReader::PcapNg(reader) => reader.datalink()
+        }
+    }
+
+    /// DataLink of the last packet read, see `PcapNgReader::packet_datalink`.
+    pub fn packet_datalink(&self) -> DataLink {
+
+        match self {
+            CaptureReader::Pcap(reader) => reader.header.datalink,
+            CaptureReader::PcapNg(reader) => reader.packet_datalink()
+        }
+    }
+
+    /// Byte offset of the next packet (or block for a pcapng) from the start of the stream.
+    pub fn position(&self) -> u64 {
+
+        match self {
+            CaptureReader::Pcap(reader) => reader.position(),
+            CaptureReader::PcapNg(reader) => reader.position()
+        }
+    }
+
+    /// Set the validation of the packet lengths of a pcap, see `PcapReader::set_length_policy`.
+    ///
+    /// The lengths of the pcapng packets are checked by their blocks.
+    pub fn set_length_policy(&mut self, length_policy: LengthPolicy) {
+
+        if let CaptureReader::Pcap(reader) = self {
+            reader.set_length_policy(length_policy);
+        }
+    }
+
+    /// Consumes the `CaptureReader`, returning the wrapped reader.
+    pub fn into_reader(self) -> T {
+
+        match self {
+            CaptureReader::Pcap(reader) => reader.into_reader(),
+            CaptureReader::PcapNg(reader) => reader.into_reader()
+        }
+    }
+}
+
+impl<T: Read, P: SomePacket<'static>> Iterator for CaptureReader<T, P> {
+
+    type Item = ResultParsing<P::Item>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+
+        match self {
+            CaptureReader::Pcap(reader) => reader.next(),
+            CaptureReader::PcapNg(reader) => reader.next()
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use byteorder::{BigEndian, ByteOrder};
+
+use crate::{
+    DataLink,
+    dissect::*
+};
+
+
+/// One's complement sum of the 16 bits words of `data`, not folded nor complemented.
+fn sum_words(data: &[u8]) -> u32 {
+
+    let mut chunks = data.chunks_exact(2);
+    let mut sum: u32 = chunks.by_ref().map(|word| BigEndian::read_u16(word) as u32).sum();
+
+    if let [last] = chunks.remainder() {
+        sum += (*last as u32) << 8;
+    }
+
+    sum
+}
+
+fn fold(mut sum: u32) -> u16 {
+
+    while sum > 0xFFFF {
+        sum = (sum & 0xFFFF) + (sum >> 16);
+    }
+
+    !(sum as u16)
+}
+
+/// Internet checksum (RFC 1071) of `data`.
+///
+/// The checksum field of `data` must be zeroed beforehand.
+pub fn internet_checksum(data: &[u8]) -> u16 {
+    fold(sum_words(data))
+}
+
+// Addresses of the IP layer enclosing a transport layer
+#[derive(Copy, Clone)]
+enum PseudoHeader {
+    V4([u8; 4], [u8; 4]),
+    V6([u8; 16], [u8; 16])
+}
+
+impl PseudoHeader {
+
+    fn sum(&self, protocol: u8, len: usize) -> u32 {
+
+        match self {
+            PseudoHeader::V4(src, dst) => sum_words(src) + sum_words(dst) + protocol as u32 + len as u32,
+            PseudoHeader::V6(src, dst) => {
+                sum_words(src) + sum_words(dst) + (len as u32 >> 16) + (len as u32 & 0xFFFF) + protocol as u32
+            }
+        }
+    }
+}
+
+// Checksum to recompute, found while dissecting
+enum Fixup {
+    Ipv4Header { offset: usize, header_len: usize },
+    Transport { offset: usize, end: usize, checksum_at: usize, protocol: u8, pseudo: Option<PseudoHeader> }
+}
+
+/// Recompute the IPv4 header, TCP, UDP, ICMP and ICMPv6 checksums of a packet in place.
+///
+/// TCP, UDP and ICMPv6 checksums include the IPv4 or IPv6 pseudo-header.
+/// Transport layers truncated by the capture or fragmented are left untouched,
+/// as are IPv4 UDP datagrams without checksum (checksum 0).
+pub fn fix_checksums(datalink: DataLink, data: &mut [u8]) {
+
+    let fixups = find_fixups(datalink, data);
+
+    for fixup in fixups {
+        match fixup {
+            Fixup::Ipv4Header { offset, header_len } => {
+                data[offset + 10..offset + 12].copy_from_slice(&[0, 0]);
+                let checksum = internet_checksum(&data[offset..offset + header_len]);
+                BigEndian::write_u16(&mut data[offset + 10..offset + 12], checksum);
+            },
+            Fixup::Transport { offset, end, checksum_at, protocol, pseudo } => {
+                data[checksum_at..checksum_at + 2].copy_from_slice(&[0, 0]);
+
+                let len = end - offset;
+                let pseudo_sum = pseudo.map(|pseudo| pseudo.sum(protocol, len)).unwrap_or(0);
+                let mut checksum = fold(pseudo_sum + sum_words(&data[offset..end]));
+
+                // A computed UDP checksum of 0 is transmitted as all ones
+                if protocol == IP_PROTO_UDP && checksum == 0 {
+                    checksum = 0xFFFF;
+                }
+
+                BigEndian::write_u16(&mut data[checksum_at..checksum_at + 2], checksum);
+            }
+        }
+    }
+}
+
+fn find_fixups(datalink: DataLink, data: &[u8]) -> Vec<Fixup> {
+
+    let dissection = dissect(datalink, data);
+    let mut fixups = Vec::new();
+
+    // Pseudo-header and end of the innermost IP layer, None if the IP payload is incomplete
+    let mut ip: Option<(PseudoHeader, usize)> = None;
+
+    for (offset, layer) in &dissection.layers {
+        let offset = *offset;
+
+        match layer {
+            Layer::Ipv4(ipv4) => {
+                fixups.push(Fixup::Ipv4Header { offset, header_len: ipv4.header_len() });
+
+                // The transport checksum of a fragmented datagram covers the other fragments too
+                let complete = ipv4.header_len() + ipv4.payload().len() == ipv4.total_len() as usize
+                    && !ipv4.more_fragments();
+                ip = complete.then(|| (
+                    PseudoHeader::V4(ipv4.src().octets(), ipv4.dst().octets()),
+                    offset + ipv4.header_len() + ipv4.payload().len()
+                ));
+            },
+            Layer::Ipv6(ipv6) => {
+                let complete = ipv6.payload().len() == ipv6.payload_len() as usize;
+                ip = complete.then(|| (
+                    PseudoHeader::V6(ipv6.src().octets(), ipv6.dst().octets()),
+                    offset + Ipv6Packet::HEADER_LEN + ipv6.payload().len()
+                ));
+            },
+            Layer::Tcp(_) => if let Some((pseudo, end)) = ip {
+                fixups.push(Fixup::Transport { offset, end, checksum_at: offset + 16, protocol: IP_PROTO_TCP, pseudo: Some(pseudo) });
+            },
+            Layer::Udp(udp) => if let Some((pseudo, end)) = ip {
+                let no_checksum = udp.checksum() == 0 && matches!(pseudo, PseudoHeader::V4(..));
+                if !no_checksum {
+                    fixups.push(Fixup::Transport { offset, end, checksum_at: offset + 6, protocol: IP_PROTO_UDP, pseudo: Some(pseudo) });
+                }
+            },
+            Layer::Icmp(_) => if let Some((_, end)) = ip {
+                fixups.push(Fixup::Transport { offset, end, checksum_at: offset + 2, protocol: IP_PROTO_ICMP, pseudo: None });
+            },
+            Layer::Icmpv6(_) => if let Some((pseudo, end)) = ip {
+                fixups.push(Fixup::Transport { offset, end, checksum_at: offset + 2, protocol: IP_PROTO_ICMPV6, pseudo: Some(pseudo) });
+            },
+            _ => {}
+        }
+    }
+
+    fixups
+}
@@ -0,0 +1,239 @@
+use byteorder::{BigEndian, ByteOrder};
+
+use crate::{
+    myerrors::*,
+    dissect::check_len
+};
+
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+
+/// IPv4 header view.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Ipv4Packet<'a> {
+    data: &'a [u8]
+}
+
+impl<'a> Ipv4Packet<'a> {
+
+    /// Length of the header without options
+    pub const MIN_HEADER_LEN: usize = 20;
+
+    /// Create a view on an IPv4 packet.
+    ///
+    /// Returns an error if the version isn't 4 or if the slice is shorter than the header.
+    pub fn new(data: &'a [u8]) -> ResultParsing<Ipv4Packet<'a>> {
+
+        check_len(data, Self::MIN_HEADER_LEN)?;
+
+        let packet = Ipv4Packet { data };
+
+        if packet.version() != 4 {
+            return Err(PcapError::InvalidField("Ipv4Packet version != 4"));
+        }
+
+        if packet.header_len() < Self::MIN_HEADER_LEN {
+            return Err(PcapError::InvalidField("Ipv4Packet ihl < 5"));
+        }
+
+        check_len(data, packet.header_len())?;
+
+        Ok(packet)
+    }
+
+    pub fn version(&self) -> u8 {
+        self.data[0] >> 4
+    }
+
+    /// Header length in bytes
+    pub fn header_len(&self) -> usize {
+        ((self.data[0] & 0x0F) as usize) * 4
+    }
+
+    pub fn dscp(&self) -> u8 {
+        self.data[1] >> 2
+    }
+
+    pub fn ecn(&self) -> u8 {
+        self.data[1] & 0x03
+    }
+
+    pub fn total_len(&self) -> u16 {
+        BigEndian::read_u16(&self.data[2..4])
+    }
+
+    pub fn identification(&self) -> u16 {
+        BigEndian::read_u16(&self.data[4..6])
+    }
+
+    /// Flags (reserved, don't fragment, more fragments)
+    pub fn flags(&self) -> u8 {
+        self.data[6] >> 5
+    }
+
+    pub fn dont_fragment(&self) -> bool {
+        self.flags() & 0b010 != 0
+    }
+
+    pub fn more_fragments(&self) -> bool {
+        self.flags() & 0b001 != 0
+    }
+
+    /// Fragment offset in units of 8 bytes
+    pub fn fragment_offset(&self) -> u16 {
+        BigEndian::read_u16(&self.data[6..8]) & 0x1FFF
+    }
+
+    pub fn ttl(&self) -> u8 {
+        self.data[8]
+    }
+
+    pub fn protocol(&self) -> u8 {
+        self.data[9]
+    }
+
+    pub fn checksum(&self) -> u16 {
+        BigEndian::read_u16(&self.data[10..12])
+    }
+
+    pub fn src(&self) -> Ipv4Addr {
+        Ipv4Addr::new(self.data[12], self.data[13], self.data[14], self.data[15])
+    }
+
+    pub fn dst(&self) -> Ipv4Addr {
+        Ipv4Addr::new(self.data[16], self.data[17], self.data[18], self.data[19])
+    }
+
+    pub fn options(&self) -> &'a [u8] {
+        &self.data[Self::MIN_HEADER_LEN..self.header_len()]
+    }
+
+    pub fn header(&self) -> &'a [u8] {
+        &self.data[..self.header_len()]
+    }
+
+    /// Data following the header, bounded by the total length to leave out the link-layer padding
+    pub fn payload(&self) -> &'a [u8] {
+        let end = (self.total_len() as usize).clamp(self.header_len(), self.data.len());
+        &self.data[self.header_len()..end]
+    }
+
+    /// True for every fragment but the first one, which doesn't start with the upper layer header
+    pub fn is_non_first_fragment(&self) -> bool {
+        self.fragment_offset() != 0
+    }
+}
+
+/// IPv6 header view.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Ipv6Packet<'a> {
+    data: &'a [u8]
+}
+
+impl<'a> Ipv6Packet<'a> {
+
+    /// Length of the fixed header
+    pub const HEADER_LEN: usize = 40;
+
+    /// Create a view on an IPv6 packet.
+    ///
+    /// Returns an error if the version isn't 6 or if the slice is shorter than the header.
+    pub fn new(data: &'a [u8]) -> ResultParsing<Ipv6Packet<'a>> {
+
+        check_len(data, Self::HEADER_LEN)?;
+
+        let packet = Ipv6Packet { data };
+
+        if packet.version() != 6 {
+            return Err(PcapError::InvalidField("Ipv6Packet version != 6"));
+        }
+
+        Ok(packet)
+    }
+
+    pub fn version(&self) -> u8 {
+        self.data[0] >> 4
+    }
+
+    pub fn traffic_class(&self) -> u8 {
+        (BigEndian::read_u16(&self.data[0..2]) >> 4) as u8
+    }
+
+    pub fn dscp(&self) -> u8 {
+        self.traffic_class() >> 2
+    }
+
+    pub fn flow_label(&self) -> u32 {
+        BigEndian::read_u32(&self.data[0..4]) & 0x000F_FFFF
+    }
+
+    pub fn payload_len(&self) -> u16 {
+        BigEndian::read_u16(&self.data[4..6])
+    }
+
+    /// Next header of the fixed header, see `upper_layer` to skip the extension headers
+    pub fn next_header(&self) -> u8 {
+        self.data[6]
+    }
+
+    pub fn hop_limit(&self) -> u8 {
+        self.data[7]
+    }
+
+    pub fn src(&self) -> Ipv6Addr {
+        let mut raw = [0_u8; 16];
+        raw.copy_from_slice(&self.data[8..24]);
+        Ipv6Addr::from(raw)
+    }
+
+    pub fn dst(&self) -> Ipv6Addr {
+        let mut raw = [0_u8; 16];
+        raw.copy_from_slice(&self.data[24..40]);
+        Ipv6Addr::from(raw)
+    }
+
+    pub fn header(&self) -> &'a [u8] {
+        &self.data[..Self::HEADER_LEN]
+    }
+
+    /// Data following the fixed header, bounded by the payload length
+    pub fn payload(&self) -> &'a [u8] {
+        let end = (Self::HEADER_LEN + self.payload_len() as usize).min(self.data.len());
+        &self.data[Self::HEADER_LEN..end]
+    }
+
+    /// Skip the extension headers (hop-by-hop, routing, fragment, destination options).
+    ///
+    /// Returns the upper layer protocol, the offset of its header in the payload and its data.
+    /// Non first fragments have no upper layer header and return `None`.
+    pub fn upper_layer(&self) -> Option<(u8, usize, &'a [u8])> {
+
+        let payload = self.payload();
+        let mut next_header = self.next_header();
+        let mut offset = 0;
+
+        loop {
+            match next_header {
+                HOP_BY_HOP | ROUTING | DESTINATION_OPTIONS => {
+                    let ext = payload.get(offset..offset + 2)?;
+                    next_header = ext[0];
+                    offset += (ext[1] as usize + 1) * 8;
+                },
+                FRAGMENT => {
+                    let ext = payload.get(offset..offset + 8)?;
+                    if BigEndian::read_u16(&ext[2..4]) & 0xFFF8 != 0 {
+                        return None;
+                    }
+                    next_header = ext[0];
+                    offset += 8;
+                },
+                _ => return Some((next_header, offset, payload.get(offset..)?))
+            }
+        }
+    }
+}
+
+const HOP_BY_HOP: u8 = 0;
+const ROUTING: u8 = 43;
+const FRAGMENT: u8 = 44;
+const DESTINATION_OPTIONS: u8 = 60;
@@ -0,0 +1,173 @@
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+
+use crate::{
+    myerrors::*,
+    dissect::{check_len, MacAddr}
+};
+
+
+/// Ethernet II header view.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct EthernetFrame<'a> {
+    data: &'a [u8]
+}
+
+impl<'a> EthernetFrame<'a> {
+
+    /// Length of the Ethernet header
+    pub const HEADER_LEN: usize = 14;
+
+    /// Create a view on an Ethernet frame.
+    ///
+    /// `PcapError::IncompleteBuffer` indicates that the slice is shorter than the header.
+    pub fn new(data: &'a [u8]) -> ResultParsing<EthernetFrame<'a>> {
+        check_len(data, Self::HEADER_LEN)?;
+        Ok(EthernetFrame { data })
+    }
+
+    pub fn dst(&self) -> MacAddr {
+        MacAddr::from_slice(&self.data[0..6])
+    }
+
+    pub fn src(&self) -> MacAddr {
+        MacAddr::from_slice(&self.data[6..12])
+    }
+
+    pub fn ethertype(&self) -> u16 {
+        BigEndian::read_u16(&self.data[12..14])
+    }
+
+    /// Header bytes
+    pub fn header(&self) -> &'a [u8] {
+        &self.data[..Self::HEADER_LEN]
+    }
+
+    /// Data following the header
+    pub fn payload(&self) -> &'a [u8] {
+        &self.data[Self::HEADER_LEN..]
+    }
+}
+
+/// 802.1Q (or 802.1ad) VLAN tag view, starting at the Tag Control Information.
+///
+/// The Tag Protocol Identifier is the ethertype of the previous layer.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct VlanTag<'a> {
+    data: &'a [u8]
+}
+
+impl<'a> VlanTag<'a> {
+
+    /// Length of the tag after the TPID
+    pub const HEADER_LEN: usize = 4;
+
+    pub fn new(data: &'a [u8]) -> ResultParsing<VlanTag<'a>> {
+        check_len(data, Self::HEADER_LEN)?;
+        Ok(VlanTag { data })
+    }
+
+    /// Tag Control Information
+    pub fn tci(&self) -> u16 {
+        BigEndian::read_u16(&self.data[0..2])
+    }
+
+    /// Priority Code Point
+    pub fn pcp(&self) -> u8 {
+        (self.tci() >> 13) as u8
+    }
+
+    /// Drop Eligible Indicator
+    pub fn dei(&self) -> bool {
+        self.tci() & 0x1000 != 0
+    }
+
+    /// VLAN identifier
+    pub fn vid(&self) -> u16 {
+        self.tci() & 0x0FFF
+    }
+
+    /// Ethertype of the encapsulated protocol
+    pub fn ethertype(&self) -> u16 {
+        BigEndian::read_u16(&self.data[2..4])
+    }
+
+    pub fn payload(&self) -> &'a [u8] {
+        &self.data[Self::HEADER_LEN..]
+    }
+}
+
+/// Linux "cooked" capture (SLL) header view.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct LinuxSll<'a> {
+    data: &'a [u8]
+}
+
+impl<'a> LinuxSll<'a> {
+
+    pub const HEADER_LEN: usize = 16;
+
+    pub fn new(data: &'a [u8]) -> ResultParsing<LinuxSll<'a>> {
+        check_len(data, Self::HEADER_LEN)?;
+        Ok(LinuxSll { data })
+    }
+
+    /// Packet type (0 to us, 1 broadcast, 2 multicast, 3 to someone else, 4 sent by us)
+    pub fn packet_type(&self) -> u16 {
+        BigEndian::read_u16(&self.data[0..2])
+    }
+
+    /// ARPHRD_ type of the device
+    pub fn arphrd_type(&self) -> u16 {
+        BigEndian::read_u16(&self.data[2..4])
+    }
+
+    /// Link-layer address of the sender, truncated to 8 bytes
+    pub fn address(&self) -> &'a [u8] {
+        let len = (BigEndian::read_u16(&self.data[4..6]) as usize).min(8);
+        &self.data[6..6 + len]
+    }
+
+    /// Ethertype of the encapsulated protocol
+    pub fn protocol(&self) -> u16 {
+        BigEndian::read_u16(&self.data[14..16])
+    }
+
+    pub fn payload(&self) -> &'a [u8] {
+        &self.data[Self::HEADER_LEN..]
+    }
+}
+
+/// BSD loopback header view, used by both `DataLink::NULL` and `DataLink::LOOP`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct NullHeader<'a> {
+    data: &'a [u8],
+    network_order: bool
+}
+
+impl<'a> NullHeader<'a> {
+
+    pub const HEADER_LEN: usize = 4;
+
+    /// `network_order` is true for `DataLink::LOOP`, whose family is always big endian.
+    /// For `DataLink::NULL` the family is in the byte order of the capturing host and is guessed.
+    pub fn new(data: &'a [u8], network_order: bool) -> ResultParsing<NullHeader<'a>> {
+        check_len(data, Self::HEADER_LEN)?;
+        Ok(NullHeader { data, network_order })
+    }
+
+    /// Address family (2 for IPv4, 24, 28 or 30 for IPv6 depending on the OS)
+    pub fn family(&self) -> u32 {
+
+        // Families are small numbers, so a big endian one starts with zeros
+        if self.network_order || self.data[0..2] == [0, 0] {
+            BigEndian::read_u32(&self.data[0..4])
+        }
+        else {
+            LittleEndian::read_u32(&self.data[0..4])
+        }
+    }
+
+    pub fn payload(&self) -> &'a [u8] {
+        &self.data[Self::HEADER_LEN..]
+    }
+}
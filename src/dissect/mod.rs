@@ -0,0 +1,360 @@
+//! Zero-copy dissection of the packet data into protocol layers.
+//!
+//! The first layer is chosen from the `DataLink` of the capture, the next ones
+//! from the ethertype or the IP protocol of the previous layer.
+//! Every layer is a view borrowing the packet data.
+
+mod checksum;
+mod ip;
+mod link;
+mod transport;
+
+pub use checksum::*;
+pub use ip::*;
+pub use link::*;
+pub use transport::*;
+
+use crate::{
+    myerrors::*,
+    DataLink
+};
+
+use std::{fmt, str::FromStr};
+
+pub const ETHERTYPE_IPV4: u16 = 0x0800;
+pub const ETHERTYPE_ARP: u16 = 0x0806;
+pub const ETHERTYPE_VLAN: u16 = 0x8100;
+pub const ETHERTYPE_IPV6: u16 = 0x86DD;
+pub const ETHERTYPE_QINQ: u16 = 0x88A8;
+pub const ETHERTYPE_QINQ_OLD: u16 = 0x9100;
+
+pub const IP_PROTO_ICMP: u8 = 1;
+pub const IP_PROTO_TCP: u8 = 6;
+pub const IP_PROTO_UDP: u8 = 17;
+pub const IP_PROTO_ICMPV6: u8 = 58;
+
+
+/// MAC address.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash, PartialOrd, Ord)]
+pub struct MacAddr(pub [u8; 6]);
+
+impl MacAddr {
+
+    /// # Panics
+    ///
+    /// Panics if the slice isn't 6 bytes long
+    pub fn from_slice(slice: &[u8]) -> MacAddr {
+        let mut raw = [0_u8; 6];
+        raw.copy_from_slice(slice);
+        MacAddr(raw)
+    }
+}
+
+impl fmt::Display for MacAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let b = &self.0;
+        write!(f, "{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}", b[0], b[1], b[2], b[3], b[4], b[5])
+    }
+}
+
+/// Parse `aa:bb:cc:dd:ee:ff` or `aa-bb-cc-dd-ee-ff`.
+impl FromStr for MacAddr {
+
+    type Err = PcapError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+
+        let mut raw = [0_u8; 6];
+        let mut parts = s.split([':', '-']);
+
+        for byte in raw.iter_mut() {
+            let part = parts.next().ok_or(PcapError::InvalidField("MacAddr too short"))?;
+            *byte = u8::from_str_radix(part, 16).map_err(|_| PcapError::InvalidField("MacAddr invalid byte"))?;
+        }
+
+        if parts.next().is_some() {
+            return Err(PcapError::InvalidField("MacAddr too long"));
+        }
+
+        Ok(MacAddr(raw))
+    }
+}
+
+/// A decoded protocol layer.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Layer<'a> {
+    Ethernet(EthernetFrame<'a>),
+    Vlan(VlanTag<'a>),
+    LinuxSll(LinuxSll<'a>),
+    Null(NullHeader<'a>),
+    Ipv4(Ipv4Packet<'a>),
+    Ipv6(Ipv6Packet<'a>),
+    Tcp(TcpSegment<'a>),
+    Udp(UdpDatagram<'a>),
+    Icmp(IcmpMessage<'a>),
+    Icmpv6(IcmpMessage<'a>),
+
+    /// Data which isn't decoded: application data, unknown or truncated protocol
+    Payload(&'a [u8])
+}
+
+/// Layers of a packet, from the outermost one.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Dissection<'a> {
+
+    /// Offset of each layer in the packet data, and the layer
+    pub layers: Vec<(usize, Layer<'a>)>
+}
+
+macro_rules! first_layer {
+    ($name:ident, $variant:ident, $view:ty) => {
+        /// Outermost layer of this protocol, if any.
+        pub fn $name(&self) -> Option<$view> {
+            self.layers.iter().find_map(|(_, layer)| match layer {
+                Layer::$variant(view) => Some(*view),
+                _ => None
+            })
+        }
+    };
+}
+
+impl<'a> Dissection<'a> {
+
+    first_layer!(ethernet, Ethernet, EthernetFrame<'a>);
+    first_layer!(linux_sll, LinuxSll, LinuxSll<'a>);
+    first_layer!(ipv4, Ipv4, Ipv4Packet<'a>);
+    first_layer!(ipv6, Ipv6, Ipv6Packet<'a>);
+    first_layer!(tcp, Tcp, TcpSegment<'a>);
+    first_layer!(udp, Udp, UdpDatagram<'a>);
+    first_layer!(icmp, Icmp, IcmpMessage<'a>);
+    first_layer!(icmpv6, Icmpv6, IcmpMessage<'a>);
+    first_layer!(payload, Payload, &'a [u8]);
+
+    /// VLAN tags, from the outer one.
+    pub fn vlans(&self) -> Vec<VlanTag<'a>> {
+        self.layers.iter().filter_map(|(_, layer)| match layer {
+            Layer::Vlan(tag) => Some(*tag),
+            _ => None
+        }).collect()
+    }
+
+    /// Offset in the packet data of the first layer matching `predicate`.
+    pub fn offset_of<F: Fn(&Layer<'a>) -> bool>(&self, predicate: F) -> Option<usize> {
+        self.layers.iter().find(|(_, layer)| predicate(layer)).map(|(offset, _)| *offset)
+    }
+}
+
+// Protocol expected at the current offset
+enum Next {
+    Ethernet,
+    LinuxSll,
+    Null(bool),
+    Ethertype(u16),
+    Ip,
+    IpProtocol(u8),
+    Payload
+}
+
+/// Dissect the data of a packet captured with the given `DataLink`.
+///
+/// Dissection stops at the first protocol which is unknown or can't be decoded,
+/// the rest of the data is then a `Layer::Payload`.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use std::fs::File;
+/// use pcap_assistant::DataLink;
+/// use pcap_assistant::dissect::dissect;
+/// use pcap_assistant::pcap::{Packet, PcapReader, SomePacket};
+///
+/// let reader = PcapReader::<_, Packet>::new(File::open("test.pcap").unwrap()).unwrap();
+/// let datalink = reader.header.datalink;
+///
+/// for packet in reader {
+///     let packet = packet.unwrap();
+///     if let Some(ipv4) = packet.dissect(datalink).ipv4() {
+///         println!("{} -> {}", ipv4.src(), ipv4.dst());
+///     }
+/// }
+/// ```
+pub fn dissect(datalink: DataLink, data: &[u8]) -> Dissection<'_> {
+
+    let mut dissection = Dissection::default();
+    let mut offset = 0;
+    // End of the current layer, IP and UDP lengths leave out the link-layer padding
+    let mut end = data.len();
+
+    let mut next = match datalink {
+        DataLink::ETHERNET => Next::Ethernet,
+        DataLink::RAW | DataLink::IPV4 | DataLink::IPV6 => Next::Ip,
+        DataLink::LINUX_SLL => Next::LinuxSll,
+        DataLink::NULL => Next::Null(false),
+        DataLink::LOOP => Next::Null(true),
+        _ => Next::Payload
+    };
+
+    loop {
+        let rest = &data[offset..end];
+
+        let decoded = match next {
+
+            Next::Ethernet => EthernetFrame::new(rest).ok().map(|eth| {
+                (Layer::Ethernet(eth), EthernetFrame::HEADER_LEN, Next::Ethertype(eth.ethertype()))
+            }),
+
+            Next::LinuxSll => LinuxSll::new(rest).ok().map(|sll| {
+                (Layer::LinuxSll(sll), LinuxSll::HEADER_LEN, Next::Ethertype(sll.protocol()))
+            }),
+
+            Next::Null(network_order) => NullHeader::new(rest, network_order).ok().map(|null| {
+                let next = match null.family() {
+                    2 | 24 | 28 | 30 => Next::Ip,
+                    _ => Next::Payload
+                };
+                (Layer::Null(null), NullHeader::HEADER_LEN, next)
+            }),
+
+            Next::Ethertype(ETHERTYPE_VLAN | ETHERTYPE_QINQ | ETHERTYPE_QINQ_OLD) => VlanTag::new(rest).ok().map(|tag| {
+                (Layer::Vlan(tag), VlanTag::HEADER_LEN, Next::Ethertype(tag.ethertype()))
+            }),
+
+            Next::Ethertype(ETHERTYPE_IPV4 | ETHERTYPE_IPV6) => {
+                next = Next::Ip;
+                continue;
+            },
+
+            Next::Ip => match rest.first().map(|b| b >> 4) {
+                Some(4) => Ipv4Packet::new(rest).ok().map(|ipv4| {
+                    let next = if ipv4.is_non_first_fragment() { Next::Payload } else { Next::IpProtocol(ipv4.protocol()) };
+                    (Layer::Ipv4(ipv4), ipv4.header_len(), next)
+                }),
+                Some(6) => Ipv6Packet::new(rest).ok().map(|ipv6| match ipv6.upper_layer() {
+                    Some((protocol, ext_len, _)) => (Layer::Ipv6(ipv6), Ipv6Packet::HEADER_LEN + ext_len, Next::IpProtocol(protocol)),
+                    None => (Layer::Ipv6(ipv6), Ipv6Packet::HEADER_LEN, Next::Payload)
+                }),
+                _ => None
+            },
+
+            Next::IpProtocol(IP_PROTO_TCP) => TcpSegment::new(rest).ok().map(|tcp| {
+                (Layer::Tcp(tcp), tcp.header_len(), Next::Payload)
+            }),
+
+            Next::IpProtocol(IP_PROTO_UDP) => UdpDatagram::new(rest).ok().map(|udp| {
+                (Layer::Udp(udp), UdpDatagram::HEADER_LEN, Next::Payload)
+            }),
+
+            Next::IpProtocol(IP_PROTO_ICMP) => IcmpMessage::new(rest).ok().map(|icmp| {
+                (Layer::Icmp(icmp), IcmpMessage::HEADER_LEN, Next::Payload)
+            }),
+
+            Next::IpProtocol(IP_PROTO_ICMPV6) => IcmpMessage::new(rest).ok().map(|icmp| {
+                (Layer::Icmpv6(icmp), IcmpMessage::HEADER_LEN, Next::Payload)
+            }),
+
+            _ => None
+        };
+
+        match decoded {
+            Some((layer, header_len, following)) => {
+                end = match layer {
+                    Layer::Ipv4(ipv4) => offset + ipv4.header_len() + ipv4.payload().len(),
+                    Layer::Ipv6(ipv6) => offset + Ipv6Packet::HEADER_LEN + ipv6.payload().len(),
+                    Layer::Udp(udp) => offset + UdpDatagram::HEADER_LEN + udp.payload().len(),
+                    _ => end
+                };
+                dissection.layers.push((offset, layer));
+                offset += header_len;
+                next = following;
+            },
+            None => {
+                if !rest.is_empty() {
+                    dissection.layers.push((offset, Layer::Payload(rest)));
+                }
+                return dissection;
+            }
+        }
+    }
+}
+
+// Return an IncompleteBuffer error if `data` is shorter than `len`
+pub(crate) fn check_len(data: &[u8], len: usize) -> ResultParsing<()> {
+
+    if data.len() < len {
+        return Err(PcapError::IncompleteBuffer(len - data.len()));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn udp_over_ipv4() -> Vec<u8> {
+        let mut data = vec![
+            0x45, 0x00, 0x00, 0x20, 0x12, 0x34, 0x40, 0x00, 0x40, 0x11, 0x00, 0x00,
+            10, 0, 0, 1, 10, 0, 0, 2,
+            0x30, 0x39, 0x12, 0xB5, 0x00, 0x0C, 0x00, 0x00
+        ];
+        data.extend_from_slice(b"ping");
+        data
+    }
+
+    #[test]
+    fn dissect_ethernet_vlan_ipv4_udp_test() {
+        let mut frame = vec![0xFF; 6];
+        frame.extend_from_slice(&[0x02, 0, 0, 0, 0, 1]);
+        frame.extend_from_slice(&[0x81, 0x00, 0x20, 0x64, 0x08, 0x00]);
+        frame.extend(udp_over_ipv4());
+        // Ethernet padding, outside of the IP total length
+        frame.extend_from_slice(&[0; 6]);
+
+        let dissection = dissect(DataLink::ETHERNET, &frame);
+
+        let eth = dissection.ethernet().unwrap();
+        assert_eq!(eth.src().to_string(), "02:00:00:00:00:01");
+        assert_eq!(eth.dst(), "ff:ff:ff:ff:ff:ff".parse().unwrap());
+        assert_eq!(dissection.vlans()[0].vid(), 100);
+        assert_eq!(dissection.vlans()[0].pcp(), 1);
+
+        let ipv4 = dissection.ipv4().unwrap();
+        assert_eq!(ipv4.src().to_string(), "10.0.0.1");
+        assert_eq!((ipv4.ttl(), ipv4.identification(), ipv4.dont_fragment()), (64, 0x1234, true));
+
+        let udp = dissection.udp().unwrap();
+        assert_eq!((udp.src_port(), udp.dst_port()), (12345, 4789));
+        assert_eq!(dissection.payload(), Some(&b"ping"[..]));
+        assert_eq!(dissection.offset_of(|layer| matches!(layer, Layer::Udp(_))), Some(38));
+    }
+
+    #[test]
+    fn dissect_other_datalinks_test() {
+        let mut ipv6 = vec![0x60, 0, 0, 0, 0, 20, IP_PROTO_TCP, 64];
+        ipv6.extend_from_slice(&[0; 15]);
+        ipv6.push(1);
+        ipv6.extend_from_slice(&[0; 15]);
+        ipv6.push(2);
+        ipv6.extend_from_slice(&[0, 80, 0x1F, 0x90, 0, 0, 0, 1, 0, 0, 0, 0, 0x50, 0x12, 0xFF, 0xFF, 0, 0, 0, 0]);
+
+        let dissection = dissect(DataLink::RAW, &ipv6);
+        assert_eq!(dissection.ipv6().unwrap().dst().to_string(), "::2");
+        let tcp = dissection.tcp().unwrap();
+        assert!(tcp.has_flag(TcpSegment::SYN) && tcp.has_flag(TcpSegment::ACK) && !tcp.has_flag(TcpSegment::FIN));
+        assert_eq!(dissection.payload(), None);
+
+        let mut sll = vec![0, 0, 0, 1, 0, 6, 2, 0, 0, 0, 0, 1, 0, 0, 0x08, 0x00];
+        sll.extend(udp_over_ipv4());
+        assert_eq!(dissect(DataLink::LINUX_SLL, &sll).udp().unwrap().dst_port(), 4789);
+
+        let mut null = vec![2, 0, 0, 0];
+        null.extend(udp_over_ipv4());
+        assert!(dissect(DataLink::NULL, &null).udp().is_some());
+
+        // Truncated UDP header
+        let truncated = &udp_over_ipv4()[..24];
+        let dissection = dissect(DataLink::RAW, truncated);
+        assert!(dissection.udp().is_none());
+        assert_eq!(dissection.payload().unwrap().len(), 4);
+    }
+}
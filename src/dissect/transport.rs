@@ -0,0 +1,170 @@
+use byteorder::{BigEndian, ByteOrder};
+
+use crate::{
+    myerrors::*,
+    dissect::check_len
+};
+
+
+/// TCP header view.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct TcpSegment<'a> {
+    data: &'a [u8]
+}
+
+impl<'a> TcpSegment<'a> {
+
+    /// Length of the header without options
+    pub const MIN_HEADER_LEN: usize = 20;
+
+    pub const FIN: u16 = 0x001;
+    pub const SYN: u16 = 0x002;
+    pub const RST: u16 = 0x004;
+    pub const PSH: u16 = 0x008;
+    pub const ACK: u16 = 0x010;
+    pub const URG: u16 = 0x020;
+    pub const ECE: u16 = 0x040;
+    pub const CWR: u16 = 0x080;
+    pub const NS: u16 = 0x100;
+
+    pub fn new(data: &'a [u8]) -> ResultParsing<TcpSegment<'a>> {
+
+        check_len(data, Self::MIN_HEADER_LEN)?;
+
+        let segment = TcpSegment { data };
+
+        if segment.header_len() < Self::MIN_HEADER_LEN {
+            return Err(PcapError::InvalidField("TcpSegment data offset < 5"));
+        }
+
+        check_len(data, segment.header_len())?;
+
+        Ok(segment)
+    }
+
+    pub fn src_port(&self) -> u16 {
+        BigEndian::read_u16(&self.data[0..2])
+    }
+
+    pub fn dst_port(&self) -> u16 {
+        BigEndian::read_u16(&self.data[2..4])
+    }
+
+    pub fn seq(&self) -> u32 {
+        BigEndian::read_u32(&self.data[4..8])
+    }
+
+    pub fn ack(&self) -> u32 {
+        BigEndian::read_u32(&self.data[8..12])
+    }
+
+    /// Header length in bytes
+    pub fn header_len(&self) -> usize {
+        ((self.data[12] >> 4) as usize) * 4
+    }
+
+    /// Flags, NS included, to be tested against the flag constants
+    pub fn flags(&self) -> u16 {
+        BigEndian::read_u16(&self.data[12..14]) & 0x01FF
+    }
+
+    pub fn has_flag(&self, flag: u16) -> bool {
+        self.flags() & flag != 0
+    }
+
+    pub fn window(&self) -> u16 {
+        BigEndian::read_u16(&self.data[14..16])
+    }
+
+    pub fn checksum(&self) -> u16 {
+        BigEndian::read_u16(&self.data[16..18])
+    }
+
+    pub fn urgent_pointer(&self) -> u16 {
+        BigEndian::read_u16(&self.data[18..20])
+    }
+
+    pub fn options(&self) -> &'a [u8] {
+        &self.data[Self::MIN_HEADER_LEN..self.header_len()]
+    }
+
+    pub fn payload(&self) -> &'a [u8] {
+        &self.data[self.header_len()..]
+    }
+}
+
+/// UDP header view.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct UdpDatagram<'a> {
+    data: &'a [u8]
+}
+
+impl<'a> UdpDatagram<'a> {
+
+    pub const HEADER_LEN: usize = 8;
+
+    pub fn new(data: &'a [u8]) -> ResultParsing<UdpDatagram<'a>> {
+        check_len(data, Self::HEADER_LEN)?;
+        Ok(UdpDatagram { data })
+    }
+
+    pub fn src_port(&self) -> u16 {
+        BigEndian::read_u16(&self.data[0..2])
+    }
+
+    pub fn dst_port(&self) -> u16 {
+        BigEndian::read_u16(&self.data[2..4])
+    }
+
+    /// Length of the header and the data
+    pub fn length(&self) -> u16 {
+        BigEndian::read_u16(&self.data[4..6])
+    }
+
+    pub fn checksum(&self) -> u16 {
+        BigEndian::read_u16(&self.data[6..8])
+    }
+
+    /// Data following the header, bounded by the length field
+    pub fn payload(&self) -> &'a [u8] {
+        let end = (self.length() as usize).clamp(Self::HEADER_LEN, self.data.len());
+        &self.data[Self::HEADER_LEN..end]
+    }
+}
+
+/// ICMP or ICMPv6 message view.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct IcmpMessage<'a> {
+    data: &'a [u8]
+}
+
+impl<'a> IcmpMessage<'a> {
+
+    pub const HEADER_LEN: usize = 8;
+
+    pub fn new(data: &'a [u8]) -> ResultParsing<IcmpMessage<'a>> {
+        check_len(data, Self::HEADER_LEN)?;
+        Ok(IcmpMessage { data })
+    }
+
+    pub fn icmp_type(&self) -> u8 {
+        self.data[0]
+    }
+
+    pub fn code(&self) -> u8 {
+        self.data[1]
+    }
+
+    pub fn checksum(&self) -> u16 {
+        BigEndian::read_u16(&self.data[2..4])
+    }
+
+    /// Type specific part of the header (identifier and sequence number for echo messages)
+    pub fn rest_of_header(&self) -> &'a [u8] {
+        &self.data[4..8]
+    }
+
+    pub fn payload(&self) -> &'a [u8] {
+        &self.data[Self::HEADER_LEN..]
+    }
+}
@@ -0,0 +1,361 @@
+use crate::myerrors::*;
+
+use std::fmt;
+
+
+// Instruction classes
+pub const BPF_LD: u16 = 0x00;
+pub const BPF_LDX: u16 = 0x01;
+pub const BPF_ST: u16 = 0x02;
+pub const BPF_STX: u16 = 0x03;
+pub const BPF_ALU: u16 = 0x04;
+pub const BPF_JMP: u16 = 0x05;
+pub const BPF_RET: u16 = 0x06;
+pub const BPF_MISC: u16 = 0x07;
+
+// Load sizes
+pub const BPF_W: u16 = 0x00;
+pub const BPF_H: u16 = 0x08;
+pub const BPF_B: u16 = 0x10;
+
+// Load modes
+pub const BPF_IMM: u16 = 0x00;
+pub const BPF_ABS: u16 = 0x20;
+pub const BPF_IND: u16 = 0x40;
+pub const BPF_MEM: u16 = 0x60;
+pub const BPF_LEN: u16 = 0x80;
+pub const BPF_MSH: u16 = 0xa0;
+
+// ALU operations
+pub const BPF_ADD: u16 = 0x00;
+pub const BPF_SUB: u16 = 0x10;
+pub const BPF_MUL: u16 = 0x20;
+pub const BPF_DIV: u16 = 0x30;
+pub const BPF_OR: u16 = 0x40;
+pub const BPF_AND: u16 = 0x50;
+pub const BPF_LSH: u16 = 0x60;
+pub const BPF_RSH: u16 = 0x70;
+pub const BPF_NEG: u16 = 0x80;
+pub const BPF_MOD: u16 = 0x90;
+pub const BPF_XOR: u16 = 0xa0;
+
+// Jump conditions
+pub const BPF_JA: u16 = 0x00;
+pub const BPF_JEQ: u16 = 0x10;
+pub const BPF_JGT: u16 = 0x20;
+pub const BPF_JGE: u16 = 0x30;
+pub const BPF_JSET: u16 = 0x40;
+
+// Operand sources
+pub const BPF_K: u16 = 0x00;
+pub const BPF_X: u16 = 0x08;
+pub const BPF_A: u16 = 0x10;
+
+// Register transfers
+pub const BPF_TAX: u16 = 0x00;
+pub const BPF_TXA: u16 = 0x80;
+
+/// Number of words of the scratch memory
+pub const BPF_MEMWORDS: usize = 16;
+
+/// Maximum number of instructions of a program
+pub const BPF_MAXINSNS: usize = 4096;
+
+/// Classic BPF instruction, as `struct bpf_insn`.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct BpfInstruction {
+    pub code: u16,
+    pub jt: u8,
+    pub jf: u8,
+    pub k: u32
+}
+
+impl BpfInstruction {
+
+    /// Non jump instruction.
+    pub fn stmt(code: u16, k: u32) -> BpfInstruction {
+        BpfInstruction { code, jt: 0, jf: 0, k }
+    }
+
+    /// Conditional jump instruction, `jt` and `jf` are relative to the next instruction.
+    pub fn jump(code: u16, k: u32, jt: u8, jf: u8) -> BpfInstruction {
+        BpfInstruction { code, jt, jf, k }
+    }
+
+    fn class(&self) -> u16 {
+        self.code & 0x07
+    }
+}
+
+/// Classic BPF program and its interpreter.
+///
+/// The program is validated at creation: jumps stay in the program, which ends with a return,
+/// and scratch memory accesses are in bounds.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use pcap_assistant::filter::*;
+///
+/// // ldh [12]; jeq #0x800, accept, drop
+/// let program = BpfProgram::new(vec![
+///     BpfInstruction::stmt(BPF_LD | BPF_H | BPF_ABS, 12),
+///     BpfInstruction::jump(BPF_JMP | BPF_JEQ | BPF_K, 0x0800, 0, 1),
+///     BpfInstruction::stmt(BPF_RET | BPF_K, u32::MAX),
+///     BpfInstruction::stmt(BPF_RET | BPF_K, 0),
+/// ]).unwrap();
+///
+/// println!("{}", program);
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BpfProgram {
+    instructions: Vec<BpfInstruction>
+}
+
+impl BpfProgram {
+
+    /// Create a program from its instructions.
+    ///
+    /// # Errors
+    ///
+    /// Returns `FilterError::InvalidProgram` if the program isn't valid.
+    pub fn new(instructions: Vec<BpfInstruction>) -> FilterResult<BpfProgram> {
+
+        let invalid = |pc: usize, message: &str| Err(FilterError::InvalidProgram(format!("({:03}) {}", pc, message)));
+
+        if instructions.is_empty() || instructions.len() > BPF_MAXINSNS {
+            return Err(FilterError::InvalidProgram(format!("{} instructions", instructions.len())));
+        }
+
+        for (pc, instruction) in instructions.iter().enumerate() {
+            let remaining = instructions.len() - pc - 1;
+            let code = instruction.code;
+
+            if code > 0xff {
+                return invalid(pc, "unknown opcode");
+            }
+
+            match instruction.class() {
+                BPF_LD | BPF_LDX => {
+                    let mode = code & 0xe0;
+                    if code & 0x18 == 0x18 {
+                        return invalid(pc, "unknown load size");
+                    }
+                    if mode == BPF_MEM && instruction.k as usize >= BPF_MEMWORDS {
+                        return invalid(pc, "scratch memory index out of bounds");
+                    }
+                    let valid = match instruction.class() {
+                        BPF_LD => matches!(mode, BPF_IMM | BPF_ABS | BPF_IND | BPF_MEM | BPF_LEN),
+                        _ => matches!(mode, BPF_IMM | BPF_MEM | BPF_LEN) || code == BPF_LDX | BPF_B | BPF_MSH
+                    };
+                    if !valid {
+                        return invalid(pc, "unknown load");
+                    }
+                },
+                BPF_ST | BPF_STX => if instruction.k as usize >= BPF_MEMWORDS {
+                    return invalid(pc, "scratch memory index out of bounds");
+                },
+                BPF_ALU => {
+                    let op = code & 0xf0;
+                    if op > BPF_XOR {
+                        return invalid(pc, "unknown ALU operation");
+                    }
+                    if matches!(op, BPF_DIV | BPF_MOD) && code & BPF_X == 0 && instruction.k == 0 {
+                        return invalid(pc, "division by zero");
+                    }
+                },
+                BPF_JMP => {
+                    let op = code & 0xf0;
+                    let (jt, jf) = if op == BPF_JA {
+                        (instruction.k as usize, instruction.k as usize)
+                    } else {
+                        (instruction.jt as usize, instruction.jf as usize)
+                    };
+                    if op > BPF_JSET {
+                        return invalid(pc, "unknown jump");
+                    }
+                    if jt >= remaining || jf >= remaining {
+                        return invalid(pc, "jump out of the program");
+                    }
+                },
+                BPF_RET => if !matches!(code & 0x18, BPF_K | BPF_A) {
+                    return invalid(pc, "unknown return");
+                },
+                _ => if code != BPF_MISC | BPF_TAX && code != BPF_MISC | BPF_TXA {
+                    return invalid(pc, "unknown instruction");
+                }
+            }
+        }
+
+        if instructions[instructions.len() - 1].class() != BPF_RET {
+            return invalid(instructions.len() - 1, "program doesn't end with a return");
+        }
+
+        Ok(BpfProgram { instructions })
+    }
+
+    pub fn instructions(&self) -> &[BpfInstruction] {
+        &self.instructions
+    }
+
+    /// Run the program on the packet data, `wirelen` is the original length of the packet.
+    ///
+    /// Returns the number of bytes to keep, 0 if the packet doesn't match.
+    /// Loads out of the data and divisions by zero stop the program and return 0.
+    pub fn run(&self, data: &[u8], wirelen: u32) -> u32 {
+
+        let mut a: u32 = 0;
+        let mut x: u32 = 0;
+        let mut mem = [0_u32; BPF_MEMWORDS];
+        let mut pc = 0;
+
+        loop {
+            let instruction = self.instructions[pc];
+            let k = instruction.k;
+            let code = instruction.code;
+            pc += 1;
+
+            match instruction.class() {
+                BPF_LD => {
+                    a = match code & 0xe0 {
+                        BPF_IMM => k,
+                        BPF_ABS => match load(data, Some(k), code & 0x18) {
+                            Some(value) => value,
+                            None => return 0
+                        },
+                        BPF_IND => match load(data, x.checked_add(k), code & 0x18) {
+                            Some(value) => value,
+                            None => return 0
+                        },
+                        BPF_MEM => mem[k as usize],
+                        _ => wirelen
+                    };
+                },
+                BPF_LDX => {
+                    x = match code & 0xe0 {
+                        BPF_IMM => k,
+                        BPF_MEM => mem[k as usize],
+                        BPF_LEN => wirelen,
+                        _ => match data.get(k as usize) {
+                            Some(byte) => ((byte & 0x0f) as u32) * 4,
+                            None => return 0
+                        }
+                    };
+                },
+                BPF_ST => mem[k as usize] = a,
+                BPF_STX => mem[k as usize] = x,
+                BPF_ALU => {
+                    let operand = if code & BPF_X != 0 { x } else { k };
+                    a = match code & 0xf0 {
+                        BPF_ADD => a.wrapping_add(operand),
+                        BPF_SUB => a.wrapping_sub(operand),
+                        BPF_MUL => a.wrapping_mul(operand),
+                        BPF_DIV | BPF_MOD if operand == 0 => return 0,
+                        BPF_DIV => a / operand,
+                        BPF_MOD => a % operand,
+                        BPF_OR => a | operand,
+                        BPF_AND => a & operand,
+                        BPF_LSH => a.checked_shl(operand).unwrap_or(0),
+                        BPF_RSH => a.checked_shr(operand).unwrap_or(0),
+                        BPF_NEG => a.wrapping_neg(),
+                        _ => a ^ operand
+                    };
+                },
+                BPF_JMP => {
+                    let operand = if code & BPF_X != 0 { x } else { k };
+                    let taken = match code & 0xf0 {
+                        BPF_JA => {
+                            pc += k as usize;
+                            continue;
+                        },
+                        BPF_JEQ => a == operand,
+                        BPF_JGT => a > operand,
+                        BPF_JGE => a >= operand,
+                        _ => a & operand != 0
+                    };
+                    pc += if taken { instruction.jt } else { instruction.jf } as usize;
+                },
+                BPF_RET => {
+                    return if code & BPF_A != 0 { a } else { k };
+                },
+                _ => {
+                    if code & BPF_TXA != 0 {
+                        a = x;
+                    } else {
+                        x = a;
+                    }
+                }
+            }
+        }
+    }
+
+    /// True if the program accepts the packet.
+    pub fn matches(&self, data: &[u8], wirelen: u32) -> bool {
+        self.run(data, wirelen) != 0
+    }
+}
+
+// Big endian load of a word, half word or byte
+fn load(data: &[u8], offset: Option<u32>, size: u16) -> Option<u32> {
+
+    let offset = offset? as usize;
+    let len = match size { BPF_W => 4, BPF_H => 2, _ => 1 };
+    let bytes = data.get(offset..offset.checked_add(len)?)?;
+
+    Some(bytes.iter().fold(0, |value, byte| (value << 8) | *byte as u32))
+}
+
+/// Print the program like `tcpdump -d`.
+impl fmt::Display for BpfProgram {
+
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+
+        for (pc, instruction) in self.instructions.iter().enumerate() {
+            let code = instruction.code;
+            let k = instruction.k;
+            let size = match code & 0x18 { BPF_W => "", BPF_H => "h", _ => "b" };
+
+            let (op, operand) = match instruction.class() {
+                BPF_LD => (format!("ld{}", size), match code & 0xe0 {
+                    BPF_IMM => format!("#{:#x}", k),
+                    BPF_ABS => format!("[{}]", k),
+                    BPF_IND => format!("[x + {}]", k),
+                    BPF_MEM => format!("M[{}]", k),
+                    _ => "#pktlen".to_string()
+                }),
+                BPF_LDX => match code & 0xe0 {
+                    BPF_IMM => ("ldx".to_string(), format!("#{:#x}", k)),
+                    BPF_MEM => ("ldx".to_string(), format!("M[{}]", k)),
+                    BPF_LEN => ("ldx".to_string(), "#pktlen".to_string()),
+                    _ => ("ldxb".to_string(), format!("4*([{}]&0xf)", k))
+                },
+                BPF_ST => ("st".to_string(), format!("M[{}]", k)),
+                BPF_STX => ("stx".to_string(), format!("M[{}]", k)),
+                BPF_ALU => {
+                    let op = ["add", "sub", "mul", "div", "or", "and", "lsh", "rsh", "neg", "mod", "xor"][(code >> 4) as usize];
+                    let operand = if code & 0xf0 == BPF_NEG { String::new() }
+                        else if code & BPF_X != 0 { "x".to_string() }
+                        else { format!("#{:#x}", k) };
+                    (op.to_string(), operand)
+                },
+                BPF_JMP => {
+                    let next = pc + 1;
+                    if code & 0xf0 == BPF_JA {
+                        ("ja".to_string(), format!("{}", next + k as usize))
+                    }
+                    else {
+                        let op = ["ja", "jeq", "jgt", "jge", "jset"][(code >> 4) as usize];
+                        let operand = if code & BPF_X != 0 { "x".to_string() } else { format!("#{:#x}", k) };
+                        (op.to_string(), format!("{:<16} jt {}\tjf {}", operand, next + instruction.jt as usize, next + instruction.jf as usize))
+                    }
+                },
+                BPF_RET => ("ret".to_string(), if code & BPF_A != 0 { "a".to_string() } else { format!("#{}", k) }),
+                _ => (if code & BPF_TXA != 0 { "txa" } else { "tax" }.to_string(), String::new())
+            };
+
+            writeln!(f, "({:03}) {:<8} {}", pc, op, operand)?;
+        }
+
+        Ok(())
+    }
+}
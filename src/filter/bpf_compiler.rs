@@ -0,0 +1,1295 @@
+use crate::{
+    DataLink,
+    myerrors::*,
+    dissect::*,
+    filter::bpf::*
+};
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+
+/// Value returned by the compiled programs for the accepted packets
+const SNAPLEN: u32 = 262144;
+
+const ETHERTYPE_RARP: u16 = 0x8035;
+const IP_PROTO_SCTP: u8 = 132;
+
+/// Compile a pcap filter expression (tcpdump syntax) to a BPF program for the given `DataLink`.
+///
+/// Supported primitives are the protocols (`ether proto`, `ip`, `ip6`, `arp`, `rarp`, `tcp`, `udp`,
+/// `sctp`, `icmp`, `icmp6`, `ip proto`, `ip6 proto`, `proto`), the addresses (`[src|dst] host`,
+/// `[src|dst] net` with a CIDR prefix or a mask, `ether [src|dst] host`), the ports (`[tcp|udp|sctp]
+/// [src|dst] port|portrange`), `vlan [id]`, `greater`, `less` and the relations between arithmetic
+/// expressions on the packet data (`ip[9] = 6`, `tcp[tcpflags] & tcp-syn != 0`, `len > 100`).
+/// They are combined with `and`, `or`, `not` (or `&&`, `||`, `!`) and parentheses, and a bare value
+/// reuses the previous qualifiers (`port 80 or 443`). An empty expression matches every packet.
+///
+/// As with libpcap, `vlan` moves the offsets of the network layer for the primitives which follow it.
+pub fn compile(expression: &str, datalink: DataLink) -> FilterResult<BpfProgram> {
+
+    let tokens = tokenize(expression)?;
+    let mut parser = Parser { tokens, pos: 0, link: Link::new(datalink)?, last: None };
+
+    let cond = if parser.peek().is_none() { Cond::True } else { parser.expression()? };
+
+    if let Some(token) = parser.tokens.get(parser.pos) {
+        return Err(syntax(token.position, format!("unexpected '{}'", token.text)));
+    }
+
+    generate(cond)
+}
+
+fn syntax(position: usize, message: String) -> FilterError {
+    FilterError::Syntax { position, message }
+}
+
+
+#[derive(Clone, Debug)]
+struct Token {
+    text: String,
+    position: usize,
+    word: bool
+}
+
+const SYMBOLS: [&str; 25] = [
+    "&&", "||", "==", "!=", "<=", ">=", "<<", ">>",
+    "(", ")", "[", "]", "!", "=", "<", ">", "+", "-", "*", "/", "&", "|", "^", "%", ":"
+];
+
+fn tokenize(expression: &str) -> FilterResult<Vec<Token>> {
+
+    let mut tokens = Vec::new();
+    let chars: Vec<(usize, char)> = expression.char_indices().collect();
+    let mut i = 0;
+    // Inside brackets ':' separates the offset from the size instead of being part of an address
+    let mut brackets = 0;
+
+    while i < chars.len() {
+        let (position, c) = chars[i];
+
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        let is_word_char = |c: char| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-') || (c == ':' && brackets == 0);
+
+        if c.is_ascii_alphanumeric() || c == '\\' || c == '_' || (c == ':' && brackets == 0) {
+            // A backslash escapes the protocol names used as values (`ip proto \tcp`)
+            let start = if c == '\\' { i + 1 } else { i };
+            let mut end = start;
+            while end < chars.len() && is_word_char(chars[end].1) {
+                end += 1;
+            }
+            if end == start {
+                return Err(syntax(position, "expected a word after '\\'".to_string()));
+            }
+            let text: String = chars[start..end].iter().map(|(_, c)| c).collect();
+            tokens.push(Token { text, position, word: true });
+            i = end;
+            continue;
+        }
+
+        let rest = &expression[position..];
+        match SYMBOLS.iter().find(|symbol| rest.starts_with(**symbol)) {
+            Some(symbol) => {
+                match *symbol {
+                    "[" => brackets += 1,
+                    "]" => brackets -= 1,
+                    _ => {}
+                }
+                tokens.push(Token { text: symbol.to_string(), position, word: false });
+                i += symbol.len();
+            },
+            None => return Err(syntax(position, format!("unexpected character '{}'", c)))
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn parse_number(text: &str) -> Option<u32> {
+
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok()
+    }
+}
+
+// Named constants of the arithmetic expressions
+fn named_constant(text: &str) -> Option<u32> {
+
+    let value = match text {
+        "tcpflags" => 13,
+        "tcp-fin" => 0x01,
+        "tcp-syn" => 0x02,
+        "tcp-rst" => 0x04,
+        "tcp-push" => 0x08,
+        "tcp-ack" => 0x10,
+        "tcp-urg" => 0x20,
+        "tcp-ece" => 0x40,
+        "tcp-cwr" => 0x80,
+        "icmptype" => 0,
+        "icmpcode" => 1,
+        "icmp-echoreply" => 0,
+        "icmp-unreach" => 3,
+        "icmp-redirect" => 5,
+        "icmp-echo" => 8,
+        "icmp-timxceed" => 11,
+        _ => return None
+    };
+
+    Some(value)
+}
+
+
+// Code leaving a value in A, then a conditional jump on it
+#[derive(Clone, Debug)]
+struct Atom {
+    code: Vec<BpfInstruction>,
+    jump: u16,
+    k: u32
+}
+
+// Boolean expression, compiled to jumps
+#[derive(Clone, Debug)]
+enum Cond {
+    True,
+    False,
+    Atom(Atom),
+    And(Box<Cond>, Box<Cond>),
+    Or(Box<Cond>, Box<Cond>),
+    Not(Box<Cond>)
+}
+
+fn and(lhs: Cond, rhs: Cond) -> Cond {
+
+    match (lhs, rhs) {
+        (Cond::True, cond) | (cond, Cond::True) => cond,
+        (Cond::False, _) => Cond::False,
+        (lhs, rhs) => Cond::And(Box::new(lhs), Box::new(rhs))
+    }
+}
+
+fn or(lhs: Cond, rhs: Cond) -> Cond {
+
+    match (lhs, rhs) {
+        (Cond::False, cond) | (cond, Cond::False) => cond,
+        (Cond::True, _) => Cond::True,
+        (lhs, rhs) => Cond::Or(Box::new(lhs), Box::new(rhs))
+    }
+}
+
+fn not(cond: Cond) -> Cond {
+
+    match cond {
+        Cond::True => Cond::False,
+        Cond::False => Cond::True,
+        Cond::Not(cond) => *cond,
+        cond => Cond::Not(Box::new(cond))
+    }
+}
+
+fn any(conds: impl IntoIterator<Item = Cond>) -> Cond {
+    conds.into_iter().fold(Cond::False, or)
+}
+
+fn all(conds: impl IntoIterator<Item = Cond>) -> Cond {
+    conds.into_iter().fold(Cond::True, and)
+}
+
+fn stmt(code: u16, k: u32) -> BpfInstruction {
+    BpfInstruction::stmt(code, k)
+}
+
+// Absolute load compared to `value` after an optional mask
+fn load_cmp(size: u16, offset: u32, mask: Option<u32>, value: u32) -> Cond {
+
+    let mut code = vec![stmt(BPF_LD | size | BPF_ABS, offset)];
+    if let Some(mask) = mask {
+        code.push(stmt(BPF_ALU | BPF_AND | BPF_K, mask));
+    }
+
+    Cond::Atom(Atom { code, jump: BPF_JEQ | BPF_K, k: value })
+}
+
+
+// Layout of the link layer, `network` is the offset of the network layer
+struct Link {
+    datalink: DataLink,
+    network: u32
+}
+
+impl Link {
+
+    fn new(datalink: DataLink) -> FilterResult<Link> {
+
+        let network = match datalink {
+            DataLink::ETHERNET => 14,
+            DataLink::LINUX_SLL => 16,
+            DataLink::RAW | DataLink::IPV4 | DataLink::IPV6 => 0,
+            DataLink::NULL | DataLink::LOOP => 4,
+            _ => return Err(FilterError::Unsupported(format!("datalink {:?}", datalink)))
+        };
+
+        Ok(Link { datalink, network })
+    }
+
+    fn ethertype_is(&self, ethertype: u16) -> Cond {
+
+        let ethertype = ethertype as u32;
+
+        match self.datalink {
+            DataLink::ETHERNET | DataLink::LINUX_SLL => load_cmp(BPF_H, self.network - 2, None, ethertype),
+            DataLink::RAW | DataLink::IPV4 | DataLink::IPV6 => match ethertype as u16 {
+                ETHERTYPE_IPV4 => load_cmp(BPF_B, 0, Some(0xf0), 0x40),
+                ETHERTYPE_IPV6 => load_cmp(BPF_B, 0, Some(0xf0), 0x60),
+                _ => Cond::False
+            },
+            _ => {
+                let families: &[u32] = match ethertype as u16 {
+                    ETHERTYPE_IPV4 => &[2],
+                    ETHERTYPE_IPV6 => &[24, 28, 30],
+                    _ => &[]
+                };
+                // The family of DataLink::NULL is in the byte order of the capturing host
+                let swapped = self.datalink == DataLink::NULL;
+                any(families.iter().flat_map(|family| {
+                    let mut conds = vec![load_cmp(BPF_W, 0, None, *family)];
+                    if swapped {
+                        conds.push(load_cmp(BPF_W, 0, None, family.swap_bytes()));
+                    }
+                    conds
+                }))
+            }
+        }
+    }
+
+    fn require_ethernet(&self, what: &str) -> FilterResult<()> {
+
+        if self.datalink != DataLink::ETHERNET {
+            return Err(FilterError::Unsupported(format!("{} on datalink {:?}", what, self.datalink)));
+        }
+
+        Ok(())
+    }
+}
+
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Proto {
+    Link,
+    Ip,
+    Ip6,
+    Arp,
+    Rarp,
+    Tcp,
+    Udp,
+    Sctp,
+    Icmp,
+    Icmp6
+}
+
+impl Proto {
+
+    fn from_name(name: &str) -> Option<Proto> {
+
+        let proto = match name {
+            "ether" | "link" => Proto::Link,
+            "ip" => Proto::Ip,
+            "ip6" => Proto::Ip6,
+            "arp" => Proto::Arp,
+            "rarp" => Proto::Rarp,
+            "tcp" => Proto::Tcp,
+            "udp" => Proto::Udp,
+            "sctp" => Proto::Sctp,
+            "icmp" => Proto::Icmp,
+            "icmp6" => Proto::Icmp6,
+            _ => return None
+        };
+
+        Some(proto)
+    }
+
+    fn ip_protocol(&self) -> Option<u8> {
+
+        match self {
+            Proto::Tcp => Some(IP_PROTO_TCP),
+            Proto::Udp => Some(IP_PROTO_UDP),
+            Proto::Sctp => Some(IP_PROTO_SCTP),
+            Proto::Icmp => Some(IP_PROTO_ICMP),
+            Proto::Icmp6 => Some(IP_PROTO_ICMPV6),
+            _ => None
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Arith {
+    Const(u32),
+    Len,
+    Load { proto: Proto, offset: Box<Arith>, size: u16 },
+    Binary(u16, Box<Arith>, Box<Arith>),
+    Neg(Box<Arith>)
+}
+
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Dir {
+    Src,
+    Dst,
+    SrcOrDst,
+    SrcAndDst
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Kind {
+    Host,
+    Net,
+    Port,
+    PortRange,
+    Proto
+}
+
+// Qualifiers of a primitive, reused by the following bare values
+#[derive(Copy, Clone, Debug, Default)]
+struct Qualifiers {
+    proto: Option<Proto>,
+    dir: Option<Dir>,
+    kind: Option<Kind>
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    link: Link,
+    last: Option<Qualifiers>
+}
+
+impl Parser {
+
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.pos).map(|token| token.text.as_str())
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<&str> {
+        self.tokens.get(self.pos + offset).map(|token| token.text.as_str())
+    }
+
+    fn position(&self) -> usize {
+        self.tokens.get(self.pos).map(|token| token.position)
+            .unwrap_or_else(|| self.tokens.last().map(|token| token.position + token.text.len()).unwrap_or(0))
+    }
+
+    fn error<T>(&self, message: &str) -> FilterResult<T> {
+
+        let message = match self.peek() {
+            Some(text) => format!("{}, found '{}'", message, text),
+            None => format!("{}, found the end of the expression", message)
+        };
+
+        Err(syntax(self.position(), message))
+    }
+
+    fn accept(&mut self, texts: &[&str]) -> bool {
+
+        if self.peek().is_some_and(|text| texts.contains(&text)) {
+            self.pos += 1;
+            return true;
+        }
+
+        false
+    }
+
+    fn expect(&mut self, text: &str) -> FilterResult<()> {
+
+        if !self.accept(&[text]) {
+            return self.error(&format!("expected '{}'", text));
+        }
+
+        Ok(())
+    }
+
+    fn word(&mut self, what: &str) -> FilterResult<String> {
+
+        match self.tokens.get(self.pos) {
+            Some(token) if token.word => {
+                self.pos += 1;
+                Ok(token.text.clone())
+            },
+            _ => self.error(&format!("expected {}", what))
+        }
+    }
+
+    fn number(&mut self, what: &str) -> FilterResult<u32> {
+
+        match self.peek().and_then(parse_number) {
+            Some(number) => {
+                self.pos += 1;
+                Ok(number)
+            },
+            None => self.error(&format!("expected {}", what))
+        }
+    }
+
+    // expression := and_expression (("or" | "||") and_expression)*
+    fn expression(&mut self) -> FilterResult<Cond> {
+
+        let mut cond = self.and_expression()?;
+
+        while self.accept(&["or", "||"]) {
+            cond = or(cond, self.and_expression()?);
+        }
+
+        Ok(cond)
+    }
+
+    // and_expression := unary (("and" | "&&") unary)*
+    fn and_expression(&mut self) -> FilterResult<Cond> {
+
+        let mut cond = self.unary()?;
+
+        while self.accept(&["and", "&&"]) {
+            cond = and(cond, self.unary()?);
+        }
+
+        Ok(cond)
+    }
+
+    // unary := ("not" | "!") unary | "(" expression ")" | relation | primitive
+    fn unary(&mut self) -> FilterResult<Cond> {
+
+        if self.accept(&["not", "!"]) {
+            return Ok(not(self.unary()?));
+        }
+
+        // Parentheses and values can start both a relation and a primitive
+        let start = self.pos;
+        if self.starts_relation() {
+            match self.relation() {
+                Ok(cond) => return Ok(cond),
+                Err(FilterError::Syntax { .. }) => self.pos = start,
+                Err(error) => return Err(error)
+            }
+        }
+
+        if self.accept(&["("]) {
+            let cond = self.expression()?;
+            self.expect(")")?;
+            return Ok(cond);
+        }
+
+        self.primitive()
+    }
+
+    fn starts_relation(&self) -> bool {
+
+        match self.peek() {
+            Some("(") | Some("-") | Some("len") => true,
+            Some(text) => {
+                parse_number(text).is_some() || named_constant(text).is_some()
+                    || (Proto::from_name(text).is_some() && self.peek_at(1) == Some("["))
+            },
+            None => false
+        }
+    }
+
+    // relation := arith ("=" | "==" | "!=" | ">" | ">=" | "<" | "<=") arith
+    fn relation(&mut self) -> FilterResult<Cond> {
+
+        let lhs = self.arith()?;
+
+        let operator = match self.peek() {
+            Some(operator @ ("=" | "==" | "!=" | ">" | ">=" | "<" | "<=")) => operator.to_string(),
+            _ => return self.error("expected a comparison operator")
+        };
+        self.pos += 1;
+
+        let rhs = self.arith()?;
+
+        let mut guards = Vec::new();
+        self.guards(&lhs, &mut guards);
+        self.guards(&rhs, &mut guards);
+
+        let (jump, negate) = match operator.as_str() {
+            "=" | "==" => (BPF_JEQ, false),
+            "!=" => (BPF_JEQ, true),
+            ">" => (BPF_JGT, false),
+            ">=" => (BPF_JGE, false),
+            "<" => (BPF_JGE, true),
+            _ => (BPF_JGT, true)
+        };
+
+        let mut code = Vec::new();
+        let atom = match rhs {
+            Arith::Const(k) => {
+                self.arith_code(&lhs, 0, &mut code)?;
+                Atom { code, jump: jump | BPF_K, k }
+            },
+            rhs => {
+                self.arith_code(&rhs, 0, &mut code)?;
+                code.push(stmt(BPF_ST, 0));
+                self.arith_code(&lhs, 1, &mut code)?;
+                code.push(stmt(BPF_LDX | BPF_MEM, 0));
+                Atom { code, jump: jump | BPF_X, k: 0 }
+            }
+        };
+
+        let cond = if negate { not(Cond::Atom(atom)) } else { Cond::Atom(atom) };
+
+        Ok(and(all(guards), cond))
+    }
+
+    // Binary operators from the lowest precedence
+    const BINARY: [&'static [(&'static str, u16)]; 6] = [
+        &[("|", BPF_OR)],
+        &[("^", BPF_XOR)],
+        &[("&", BPF_AND)],
+        &[("<<", BPF_LSH), (">>", BPF_RSH)],
+        &[("+", BPF_ADD), ("-", BPF_SUB)],
+        &[("*", BPF_MUL), ("/", BPF_DIV), ("%", BPF_MOD)]
+    ];
+
+    fn arith(&mut self) -> FilterResult<Arith> {
+        self.binary(0)
+    }
+
+    fn binary(&mut self, level: usize) -> FilterResult<Arith> {
+
+        if level == Self::BINARY.len() {
+            return self.arith_unary();
+        }
+
+        let mut lhs = self.binary(level + 1)?;
+
+        loop {
+            let operator = Self::BINARY[level].iter().find(|(text, _)| self.peek() == Some(*text));
+            match operator {
+                Some((_, op)) => {
+                    self.pos += 1;
+                    let rhs = self.binary(level + 1)?;
+                    lhs = Arith::Binary(*op, Box::new(lhs), Box::new(rhs));
+                },
+                None => return Ok(lhs)
+            }
+        }
+    }
+
+    fn arith_unary(&mut self) -> FilterResult<Arith> {
+
+        if self.accept(&["-"]) {
+            return Ok(Arith::Neg(Box::new(self.arith_unary()?)));
+        }
+
+        if self.accept(&["("]) {
+            let arith = self.arith()?;
+            self.expect(")")?;
+            return Ok(arith);
+        }
+
+        if self.accept(&["len"]) {
+            return Ok(Arith::Len);
+        }
+
+        if let Some(value) = self.peek().and_then(|text| parse_number(text).or_else(|| named_constant(text))) {
+            self.pos += 1;
+            return Ok(Arith::Const(value));
+        }
+
+        // proto "[" arith (":" size)? "]"
+        match self.peek().and_then(Proto::from_name) {
+            Some(proto) if self.peek_at(1) == Some("[") => {
+                self.pos += 2;
+                let offset = self.arith()?;
+                let size = if self.accept(&[":"]) {
+                    match self.number("a size")? {
+                        1 => BPF_B,
+                        2 => BPF_H,
+                        4 => BPF_W,
+                        _ => return Err(syntax(self.tokens[self.pos - 1].position, "size must be 1, 2 or 4".to_string()))
+                    }
+                } else {
+                    BPF_B
+                };
+                self.expect("]")?;
+                Ok(Arith::Load { proto, offset: Box::new(offset), size })
+            },
+            _ => self.error("expected an arithmetic expression")
+        }
+    }
+
+    // Protocol checks required by the loads of an arithmetic expression
+    fn guards(&self, arith: &Arith, guards: &mut Vec<Cond>) {
+
+        match arith {
+            Arith::Load { proto, offset, .. } => {
+                self.guards(offset, guards);
+                match proto {
+                    Proto::Link => {},
+                    Proto::Ip | Proto::Ip6 | Proto::Arp | Proto::Rarp | Proto::Icmp6 => guards.push(self.proto_cond(*proto)),
+                    // Other transport headers are located from the IPv4 header length
+                    _ => guards.push(and(self.ipv4_protocol(proto.ip_protocol().unwrap_or(0)), self.ipv4_not_fragment()))
+                }
+            },
+            Arith::Binary(_, lhs, rhs) => {
+                self.guards(lhs, guards);
+                self.guards(rhs, guards);
+            },
+            Arith::Neg(arith) => self.guards(arith, guards),
+            _ => {}
+        }
+    }
+
+    // Code leaving the value of the expression in A, scratch memory from `depth` is free
+    fn arith_code(&self, arith: &Arith, depth: u32, code: &mut Vec<BpfInstruction>) -> FilterResult<()> {
+
+        if depth as usize >= BPF_MEMWORDS {
+            return Err(FilterError::Unsupported("arithmetic expression too complex".to_string()));
+        }
+
+        match arith {
+            Arith::Const(k) => code.push(stmt(BPF_LD | BPF_IMM, *k)),
+            Arith::Len => code.push(stmt(BPF_LD | BPF_W | BPF_LEN, 0)),
+            Arith::Neg(arith) => {
+                self.arith_code(arith, depth, code)?;
+                code.push(stmt(BPF_ALU | BPF_NEG, 0));
+            },
+            Arith::Binary(op, lhs, rhs) => match **rhs {
+                Arith::Const(k) => {
+                    if matches!(*op, BPF_DIV | BPF_MOD) && k == 0 {
+                        return Err(FilterError::Unsupported("division by zero".to_string()));
+                    }
+                    self.arith_code(lhs, depth, code)?;
+                    code.push(stmt(BPF_ALU | op | BPF_K, k));
+                },
+                _ => {
+                    self.arith_code(rhs, depth, code)?;
+                    code.push(stmt(BPF_ST, depth));
+                    self.arith_code(lhs, depth + 1, code)?;
+                    code.push(stmt(BPF_LDX | BPF_MEM, depth));
+                    code.push(stmt(BPF_ALU | op | BPF_X, 0));
+                }
+            },
+            Arith::Load { proto, offset, size } => {
+                let network = self.link.network;
+                let (base, transport) = match proto {
+                    Proto::Link => (0, false),
+                    Proto::Ip | Proto::Ip6 | Proto::Arp | Proto::Rarp => (network, false),
+                    Proto::Icmp6 => (network + Ipv6Packet::HEADER_LEN as u32, false),
+                    _ => (network, true)
+                };
+
+                let absolute = |k: u32| base.checked_add(k)
+                    .ok_or_else(|| FilterError::Unsupported(format!("offset {} out of range", k)));
+
+                match (&**offset, transport) {
+                    (Arith::Const(k), false) => code.push(stmt(BPF_LD | size | BPF_ABS, absolute(*k)?)),
+                    (Arith::Const(k), true) => {
+                        code.push(stmt(BPF_LDX | BPF_B | BPF_MSH, base));
+                        code.push(stmt(BPF_LD | size | BPF_IND, absolute(*k)?));
+                    },
+                    (offset, false) => {
+                        self.arith_code(offset, depth, code)?;
+                        code.push(stmt(BPF_MISC | BPF_TAX, 0));
+                        code.push(stmt(BPF_LD | size | BPF_IND, base));
+                    },
+                    (offset, true) => {
+                        self.arith_code(offset, depth, code)?;
+                        code.push(stmt(BPF_ST, depth));
+                        code.push(stmt(BPF_LDX | BPF_B | BPF_MSH, base));
+                        code.push(stmt(BPF_LD | BPF_MEM, depth));
+                        code.push(stmt(BPF_ALU | BPF_ADD | BPF_X, 0));
+                        code.push(stmt(BPF_MISC | BPF_TAX, 0));
+                        code.push(stmt(BPF_LD | size | BPF_IND, base));
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // primitive := qualifiers value | proto | "vlan" [id] | ("greater" | "less") number | value
+    fn primitive(&mut self) -> FilterResult<Cond> {
+
+        match self.peek() {
+            Some("vlan") => {
+                self.pos += 1;
+                self.link.require_ethernet("vlan")?;
+                let vid = match self.peek().and_then(parse_number) {
+                    Some(vid) => {
+                        self.pos += 1;
+                        Some(vid)
+                    },
+                    None => None
+                };
+                return Ok(self.vlan(vid));
+            },
+            Some(keyword @ ("greater" | "less")) => {
+                let greater = keyword == "greater";
+                self.pos += 1;
+                let len = self.number("a length")?;
+                let atom = Cond::Atom(Atom { code: vec![stmt(BPF_LD | BPF_W | BPF_LEN, 0)], jump: if greater { BPF_JGE } else { BPF_JGT }, k: len });
+                return Ok(if greater { atom } else { not(atom) });
+            },
+            _ => {}
+        }
+
+        let start = self.pos;
+        let mut qualifiers = Qualifiers::default();
+
+        if let Some(proto) = self.peek().and_then(Proto::from_name) {
+            qualifiers.proto = Some(proto);
+            self.pos += 1;
+        }
+
+        if self.accept(&["src"]) {
+            qualifiers.dir = Some(Dir::Src);
+            if matches!(self.peek(), Some("or" | "and")) && self.peek_at(1) == Some("dst") {
+                qualifiers.dir = Some(if self.peek() == Some("or") { Dir::SrcOrDst } else { Dir::SrcAndDst });
+                self.pos += 2;
+            }
+        }
+        else if self.accept(&["dst"]) {
+            qualifiers.dir = Some(Dir::Dst);
+            if matches!(self.peek(), Some("or" | "and")) && self.peek_at(1) == Some("src") {
+                qualifiers.dir = Some(if self.peek() == Some("or") { Dir::SrcOrDst } else { Dir::SrcAndDst });
+                self.pos += 2;
+            }
+        }
+
+        qualifiers.kind = match self.peek() {
+            Some("host") => Some(Kind::Host),
+            Some("net") => Some(Kind::Net),
+            Some("port") => Some(Kind::Port),
+            Some("portrange") => Some(Kind::PortRange),
+            Some("proto") => Some(Kind::Proto),
+            _ => None
+        };
+        if qualifiers.kind.is_some() {
+            self.pos += 1;
+        }
+
+        if self.pos == start {
+            // Bare value, with the qualifiers of the previous primitive
+            return match self.last {
+                Some(last) if self.peek().is_some_and(|text| text != "(") => self.value(last),
+                _ => self.error("expected a primitive")
+            };
+        }
+
+        if qualifiers.dir.is_none() && qualifiers.kind.is_none() {
+            let proto = qualifiers.proto.unwrap_or(Proto::Link);
+            if proto == Proto::Link {
+                return self.error("expected a qualifier after 'ether'");
+            }
+            return Ok(self.proto_cond(proto));
+        }
+
+        self.last = Some(qualifiers);
+        self.value(qualifiers)
+    }
+
+    fn value(&mut self, qualifiers: Qualifiers) -> FilterResult<Cond> {
+
+        let position = self.position();
+        let dir = qualifiers.dir.unwrap_or(Dir::SrcOrDst);
+
+        match qualifiers.kind.unwrap_or(Kind::Host) {
+            Kind::Proto => {
+                let name = self.word("a protocol")?;
+                let number = match (parse_number(&name), qualifiers.proto) {
+                    (Some(number), _) => number,
+                    (None, Some(Proto::Link)) => match Proto::from_name(&name) {
+                        Some(Proto::Ip) => ETHERTYPE_IPV4 as u32,
+                        Some(Proto::Ip6) => ETHERTYPE_IPV6 as u32,
+                        Some(Proto::Arp) => ETHERTYPE_ARP as u32,
+                        Some(Proto::Rarp) => ETHERTYPE_RARP as u32,
+                        _ => return Err(syntax(position, format!("unknown ethertype '{}'", name)))
+                    },
+                    (None, _) => match Proto::from_name(&name).and_then(|proto| proto.ip_protocol()) {
+                        Some(protocol) => protocol as u32,
+                        None => return Err(syntax(position, format!("unknown protocol '{}'", name)))
+                    }
+                };
+                let limit = if qualifiers.proto == Some(Proto::Link) { u16::MAX as u32 } else { u8::MAX as u32 };
+                if number > limit {
+                    return Err(syntax(position, format!("protocol {} out of range", number)));
+                }
+                match qualifiers.proto {
+                    Some(Proto::Link) => Ok(self.link.ethertype_is(number as u16)),
+                    Some(Proto::Ip) => Ok(self.ipv4_protocol(number as u8)),
+                    Some(Proto::Ip6) => Ok(self.ipv6_protocol(number as u8)),
+                    None => Ok(or(self.ipv4_protocol(number as u8), self.ipv6_protocol(number as u8))),
+                    Some(_) => Err(syntax(position, "'proto' only applies to ether, ip and ip6".to_string()))
+                }
+            },
+            Kind::Port | Kind::PortRange => {
+                let text = self.word("a port")?;
+                let range = if qualifiers.kind == Some(Kind::PortRange) {
+                    let (low, high) = text.split_once('-')
+                        .ok_or_else(|| syntax(position, format!("expected a port range, found '{}'", text)))?;
+                    (parse_number(low), parse_number(high))
+                } else {
+                    (parse_number(&text), parse_number(&text))
+                };
+                let (Some(low), Some(high)) = range else {
+                    return Err(syntax(position, format!("invalid port '{}'", text)));
+                };
+                let protocols: &[u8] = match qualifiers.proto {
+                    None => &[IP_PROTO_TCP, IP_PROTO_UDP, IP_PROTO_SCTP],
+                    Some(Proto::Tcp) => &[IP_PROTO_TCP],
+                    Some(Proto::Udp) => &[IP_PROTO_UDP],
+                    Some(Proto::Sctp) => &[IP_PROTO_SCTP],
+                    Some(_) => return Err(syntax(position, "ports only apply to tcp, udp and sctp".to_string()))
+                };
+                Ok(self.ports(protocols, dir, low, high))
+            },
+            Kind::Host | Kind::Net => {
+                let text = self.word("an address")?;
+
+                if qualifiers.proto == Some(Proto::Link) {
+                    if qualifiers.kind == Some(Kind::Net) {
+                        return Err(syntax(position, "'ether net' isn't supported".to_string()));
+                    }
+                    self.link.require_ethernet("ether host")?;
+                    let mac: MacAddr = text.parse().map_err(|_| syntax(position, format!("invalid MAC address '{}'", text)))?;
+                    return Ok(self.ether_host(dir, mac));
+                }
+
+                let address: IpAddr = text.parse().map_err(|_| syntax(position, format!("invalid address '{}'", text)))?;
+                let max_prefix = if address.is_ipv4() { 32 } else { 128 };
+
+                let prefix = if qualifiers.kind == Some(Kind::Net) && self.accept(&["/"]) {
+                    let position = self.position();
+                    match self.number("a prefix length")? {
+                        prefix if prefix <= max_prefix => prefix,
+                        prefix => return Err(syntax(position, format!("invalid prefix length {}", prefix)))
+                    }
+                }
+                else if qualifiers.kind == Some(Kind::Net) && self.accept(&["mask"]) {
+                    let position = self.position();
+                    let mask: IpAddr = self.word("a mask")?.parse().map_err(|_| syntax(position, "invalid mask".to_string()))?;
+                    let bits = match mask {
+                        IpAddr::V4(mask) => u32::from(mask),
+                        IpAddr::V6(_) => return Err(syntax(position, "masks only apply to IPv4 networks".to_string()))
+                    };
+                    if bits.leading_ones() + bits.trailing_zeros() != 32 {
+                        return Err(syntax(position, "non contiguous mask".to_string()));
+                    }
+                    bits.leading_ones()
+                }
+                else {
+                    max_prefix
+                };
+
+                match (address, qualifiers.proto) {
+                    (IpAddr::V4(address), None | Some(Proto::Ip)) => Ok(self.ipv4_host(dir, address, prefix)),
+                    (IpAddr::V4(address), Some(Proto::Arp | Proto::Rarp)) => Ok(self.arp_host(qualifiers.proto.unwrap(), dir, address)),
+                    (IpAddr::V6(address), None | Some(Proto::Ip6)) => Ok(self.ipv6_host(dir, address, prefix)),
+                    _ => Err(syntax(position, format!("address '{}' doesn't match the protocol", text)))
+                }
+            }
+        }
+    }
+
+    fn proto_cond(&self, proto: Proto) -> Cond {
+
+        match proto {
+            Proto::Link => Cond::True,
+            Proto::Ip => self.link.ethertype_is(ETHERTYPE_IPV4),
+            Proto::Ip6 => self.link.ethertype_is(ETHERTYPE_IPV6),
+            Proto::Arp => self.link.ethertype_is(ETHERTYPE_ARP),
+            Proto::Rarp => self.link.ethertype_is(ETHERTYPE_RARP),
+            Proto::Icmp => self.ipv4_protocol(IP_PROTO_ICMP),
+            Proto::Icmp6 => self.ipv6_protocol(IP_PROTO_ICMPV6),
+            _ => {
+                let protocol = proto.ip_protocol().unwrap_or(0);
+                or(self.ipv4_protocol(protocol), self.ipv6_protocol(protocol))
+            }
+        }
+    }
+
+    fn ipv4_protocol(&self, protocol: u8) -> Cond {
+        and(self.link.ethertype_is(ETHERTYPE_IPV4), load_cmp(BPF_B, self.link.network + 9, None, protocol as u32))
+    }
+
+    fn ipv6_protocol(&self, protocol: u8) -> Cond {
+        and(self.link.ethertype_is(ETHERTYPE_IPV6), load_cmp(BPF_B, self.link.network + 6, None, protocol as u32))
+    }
+
+    fn ipv4_not_fragment(&self) -> Cond {
+        not(Cond::Atom(Atom { code: vec![stmt(BPF_LD | BPF_H | BPF_ABS, self.link.network + 6)], jump: BPF_JSET | BPF_K, k: 0x1fff }))
+    }
+
+    fn vlan(&mut self, vid: Option<u32>) -> Cond {
+
+        let tpid = self.link.network - 2;
+        let tagged = any([ETHERTYPE_VLAN, ETHERTYPE_QINQ, ETHERTYPE_QINQ_OLD].map(|tpid_value| load_cmp(BPF_H, tpid, None, tpid_value as u32)));
+        let cond = match vid {
+            Some(vid) => and(tagged, load_cmp(BPF_H, tpid + 2, Some(0x0fff), vid)),
+            None => tagged
+        };
+
+        // Following primitives apply to the encapsulated packet
+        self.link.network += 4;
+
+        cond
+    }
+
+    fn directions(dir: Dir, src: Cond, dst: Cond) -> Cond {
+
+        match dir {
+            Dir::Src => src,
+            Dir::Dst => dst,
+            Dir::SrcOrDst => or(src, dst),
+            Dir::SrcAndDst => and(src, dst)
+        }
+    }
+
+    fn ports(&self, protocols: &[u8], dir: Dir, low: u32, high: u32) -> Cond {
+
+        let network = self.link.network;
+        let port = |transport_load: Vec<BpfInstruction>| -> Cond {
+            let atom = |jump, k| Cond::Atom(Atom { code: transport_load.clone(), jump, k });
+            if low == high {
+                atom(BPF_JEQ | BPF_K, low)
+            } else {
+                and(atom(BPF_JGE | BPF_K, low), not(atom(BPF_JGT | BPF_K, high)))
+            }
+        };
+        let ipv4_port = |offset| port(vec![stmt(BPF_LDX | BPF_B | BPF_MSH, network), stmt(BPF_LD | BPF_H | BPF_IND, network + offset)]);
+        let ipv6_port = |offset| port(vec![stmt(BPF_LD | BPF_H | BPF_ABS, network + Ipv6Packet::HEADER_LEN as u32 + offset)]);
+
+        let ipv4 = all([
+            self.link.ethertype_is(ETHERTYPE_IPV4),
+            any(protocols.iter().map(|protocol| load_cmp(BPF_B, network + 9, None, *protocol as u32))),
+            self.ipv4_not_fragment(),
+            Self::directions(dir, ipv4_port(0), ipv4_port(2))
+        ]);
+        let ipv6 = all([
+            self.link.ethertype_is(ETHERTYPE_IPV6),
+            any(protocols.iter().map(|protocol| load_cmp(BPF_B, network + 6, None, *protocol as u32))),
+            Self::directions(dir, ipv6_port(0), ipv6_port(2))
+        ]);
+
+        or(ipv4, ipv6)
+    }
+
+    fn ipv4_host(&self, dir: Dir, address: Ipv4Addr, prefix: u32) -> Cond {
+
+        let mask = if prefix == 0 { 0 } else { u32::MAX << (32 - prefix) };
+        let address = u32::from(address) & mask;
+        let network = self.link.network;
+
+        let host = |offset| if mask == 0 { Cond::True } else { load_cmp(BPF_W, network + offset, Some(mask).filter(|mask| *mask != u32::MAX), address) };
+
+        and(self.link.ethertype_is(ETHERTYPE_IPV4), Self::directions(dir, host(12), host(16)))
+    }
+
+    fn ipv6_host(&self, dir: Dir, address: Ipv6Addr, prefix: u32) -> Cond {
+
+        let network = self.link.network;
+        let words: Vec<(u32, u32)> = address.octets().chunks(4).enumerate().map(|(i, chunk)| {
+            let bits = prefix.saturating_sub(i as u32 * 32).min(32);
+            let mask = if bits == 0 { 0 } else { u32::MAX << (32 - bits) };
+            (u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]) & mask, mask)
+        }).collect();
+
+        let host = |offset: u32| all(words.iter().enumerate().filter(|(_, (_, mask))| *mask != 0).map(|(i, (word, mask))| {
+            load_cmp(BPF_W, network + offset + i as u32 * 4, Some(*mask).filter(|mask| *mask != u32::MAX), *word)
+        }));
+
+        and(self.link.ethertype_is(ETHERTYPE_IPV6), Self::directions(dir, host(8), host(24)))
+    }
+
+    fn arp_host(&self, proto: Proto, dir: Dir, address: Ipv4Addr) -> Cond {
+
+        let network = self.link.network;
+        let host = |offset| load_cmp(BPF_W, network + offset, None, u32::from(address));
+
+        and(self.proto_cond(proto), Self::directions(dir, host(14), host(24)))
+    }
+
+    fn ether_host(&self, dir: Dir, mac: MacAddr) -> Cond {
+
+        let b = mac.0;
+        let high = u16::from_be_bytes([b[0], b[1]]) as u32;
+        let low = u32::from_be_bytes([b[2], b[3], b[4], b[5]]);
+        let host = |offset| and(load_cmp(BPF_W, offset + 2, None, low), load_cmp(BPF_H, offset, None, high));
+
+        Self::directions(dir, host(6), host(0))
+    }
+}
+
+
+#[derive(Clone, Debug)]
+enum Slot {
+    Instruction(BpfInstruction),
+    Jump { code: u16, k: u32, jt: usize, jf: usize },
+    Always(usize),
+    Label(usize)
+}
+
+struct Generator {
+    slots: Vec<Slot>,
+    labels: usize
+}
+
+impl Generator {
+
+    fn label(&mut self) -> usize {
+        self.labels += 1;
+        self.labels - 1
+    }
+
+    fn cond(&mut self, cond: Cond, jt: usize, jf: usize) {
+
+        match cond {
+            Cond::True => self.slots.push(Slot::Always(jt)),
+            Cond::False => self.slots.push(Slot::Always(jf)),
+            Cond::Atom(atom) => {
+                self.slots.extend(atom.code.into_iter().map(Slot::Instruction));
+                self.slots.push(Slot::Jump { code: BPF_JMP | atom.jump, k: atom.k, jt, jf });
+            },
+            Cond::And(lhs, rhs) => {
+                let next = self.label();
+                self.cond(*lhs, next, jf);
+                self.slots.push(Slot::Label(next));
+                self.cond(*rhs, jt, jf);
+            },
+            Cond::Or(lhs, rhs) => {
+                let next = self.label();
+                self.cond(*lhs, jt, next);
+                self.slots.push(Slot::Label(next));
+                self.cond(*rhs, jt, jf);
+            },
+            Cond::Not(cond) => self.cond(*cond, jf, jt)
+        }
+    }
+}
+
+fn generate(cond: Cond) -> FilterResult<BpfProgram> {
+
+    let mut generator = Generator { slots: Vec::new(), labels: 0 };
+    let accept = generator.label();
+    let reject = generator.label();
+
+    generator.cond(cond, accept, reject);
+    generator.slots.push(Slot::Label(accept));
+    generator.slots.push(Slot::Instruction(stmt(BPF_RET | BPF_K, SNAPLEN)));
+    generator.slots.push(Slot::Label(reject));
+    generator.slots.push(Slot::Instruction(stmt(BPF_RET | BPF_K, 0)));
+
+    // Unconditional jumps to the next instruction are removed, then labels are resolved
+    let mut slots: Vec<Slot> = generator.slots.iter().enumerate().filter(|(i, slot)| match slot {
+        Slot::Always(label) => !generator.slots[i + 1..].iter()
+            .take_while(|slot| matches!(slot, Slot::Label(_)))
+            .any(|slot| matches!(slot, Slot::Label(next) if next == label)),
+        _ => true
+    }).map(|(_, slot)| slot.clone()).collect();
+
+    // Conditional jumps have 8-bit offsets, farther branches go through a JA trampoline placed after the jump.
+    // Trampolines move the following code, so jumps are checked again until they all reach their targets.
+    let mut labels = generator.labels;
+    while let Some(extended) = add_trampolines(&slots, &mut labels) {
+        slots = extended;
+    }
+
+    let addresses = addresses(&slots, labels);
+    let relative = |from: usize, label: usize| addresses[label] - from - 1;
+
+    let mut instructions = Vec::new();
+    for slot in slots {
+        let pc = instructions.len();
+        match slot {
+            Slot::Instruction(instruction) => instructions.push(instruction),
+            Slot::Jump { code, k, jt, jf } => instructions.push(BpfInstruction::jump(code, k, relative(pc, jt) as u8, relative(pc, jf) as u8)),
+            Slot::Always(label) => instructions.push(stmt(BPF_JMP | BPF_JA, relative(pc, label) as u32)),
+            Slot::Label(_) => {}
+        }
+    }
+
+    BpfProgram::new(instructions)
+}
+
+// Address of every label
+fn addresses(slots: &[Slot], labels: usize) -> Vec<usize> {
+
+    let mut addresses = vec![0; labels];
+    let mut pc = 0;
+
+    for slot in slots {
+        match slot {
+            Slot::Label(label) => addresses[*label] = pc,
+            _ => pc += 1
+        }
+    }
+
+    addresses
+}
+
+// Redirect the conditional branches out of reach to trampolines, None if every branch is in reach
+fn add_trampolines(slots: &[Slot], labels: &mut usize) -> Option<Vec<Slot>> {
+
+    let addresses = addresses(slots, *labels);
+    let mut extended = Vec::with_capacity(slots.len());
+    let mut far = false;
+    let mut pc = 0;
+
+    for slot in slots {
+        match *slot {
+            Slot::Jump { code, k, jt, jf } => {
+                let mut trampolines = Vec::new();
+                let mut reach = |target: usize| {
+                    if addresses[target] - pc - 1 <= u8::MAX as usize {
+                        return target;
+                    }
+                    *labels += 1;
+                    trampolines.extend([Slot::Label(*labels - 1), Slot::Always(target)]);
+                    *labels - 1
+                };
+                let (jt, jf) = (reach(jt), reach(jf));
+
+                far |= !trampolines.is_empty();
+                extended.push(Slot::Jump { code, k, jt, jf });
+                extended.extend(trampolines);
+            },
+            ref slot => extended.push(slot.clone())
+        }
+
+        if !matches!(slot, Slot::Label(_)) {
+            pc += 1;
+        }
+    }
+
+    far.then_some(extended)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Ethernet frame with an IPv4 header from 10.0.0.1 to 192.168.1.2 and a transport header from port 1000 to `dst_port`
+    fn ipv4(protocol: u8, dst_port: u16) -> Vec<u8> {
+        let mut frame = vec![0x02, 0, 0, 0, 0, 2, 0x02, 0, 0, 0, 0, 1, 0x08, 0x00];
+        frame.extend_from_slice(&[0x45, 0, 0, 40, 0, 0, 0x40, 0, 64, protocol, 0, 0, 10, 0, 0, 1, 192, 168, 1, 2]);
+        frame.extend_from_slice(&1000_u16.to_be_bytes());
+        frame.extend_from_slice(&dst_port.to_be_bytes());
+        frame.extend_from_slice(&[0; 16]);
+        frame
+    }
+
+    // Ethernet frame with an IPv6 header from 2001:db8::1 to 2001:db8::2 and a UDP header to `dst_port`
+    fn ipv6_udp(dst_port: u16) -> Vec<u8> {
+        let mut frame = vec![0x02, 0, 0, 0, 0, 2, 0x02, 0, 0, 0, 0, 1, 0x86, 0xDD];
+        frame.extend_from_slice(&[0x60, 0, 0, 0, 0, 8, 17, 64]);
+        frame.extend_from_slice(&"2001:db8::1".parse::<Ipv6Addr>().unwrap().octets());
+        frame.extend_from_slice(&"2001:db8::2".parse::<Ipv6Addr>().unwrap().octets());
+        frame.extend_from_slice(&[0x03, 0xE8]);
+        frame.extend_from_slice(&dst_port.to_be_bytes());
+        frame.extend_from_slice(&[0, 8, 0, 0]);
+        frame
+    }
+
+    fn vlan(vid: u16, mut frame: Vec<u8>) -> Vec<u8> {
+        frame.splice(12..12, [0x81, 0x00, (vid >> 8) as u8, vid as u8]);
+        frame
+    }
+
+    fn run(expression: &str, frame: &[u8]) -> bool {
+        compile(expression, DataLink::ETHERNET).unwrap().matches(frame, frame.len() as u32)
+    }
+
+    #[test]
+    fn host_test() {
+        let udp = ipv4(17, 53);
+        assert!(run("host 10.0.0.1", &udp));
+        assert!(run("src host 10.0.0.1 and dst host 192.168.1.2", &udp));
+        assert!(!run("dst host 10.0.0.1", &udp));
+        assert!(!run("host 10.0.0.3", &udp));
+        assert!(run("host 2001:db8::2", &ipv6_udp(53)));
+        assert!(!run("src host 2001:db8::2", &ipv6_udp(53)));
+        assert!(!run("host 2001:db8::2", &udp));
+    }
+
+    #[test]
+    fn net_test() {
+        let udp = ipv4(17, 53);
+        assert!(run("net 10.0.0.0/8", &udp));
+        assert!(run("dst net 192.168.0.0/16", &udp));
+        assert!(!run("src net 192.168.0.0/16", &udp));
+        assert!(run("net 192.168.1.0 mask 255.255.255.0", &udp));
+        assert!(!run("net 172.16.0.0/12", &udp));
+        assert!(run("net 2001:db8::/32", &ipv6_udp(53)));
+        assert!(!run("net 2001:db9::/32", &ipv6_udp(53)));
+    }
+
+    #[test]
+    fn port_test() {
+        assert!(run("port 53", &ipv4(17, 53)));
+        assert!(run("udp dst port 53 and src port 1000", &ipv4(17, 53)));
+        assert!(!run("tcp port 53", &ipv4(17, 53)));
+        assert!(run("tcp port 80", &ipv4(6, 80)));
+        assert!(!run("port 80", &ipv4(6, 81)));
+        assert!(run("udp port 53", &ipv6_udp(53)));
+        assert!(run("port 443 or 53", &ipv4(17, 53)));
+    }
+
+    #[test]
+    fn portrange_test() {
+        assert!(run("portrange 50-60", &ipv4(17, 53)));
+        assert!(run("dst portrange 53-53", &ipv4(17, 53)));
+        assert!(!run("dst portrange 54-60", &ipv4(17, 53)));
+        assert!(run("tcp src portrange 999-1001", &ipv4(6, 80)));
+        assert!(run("portrange 50-60", &ipv6_udp(53)));
+    }
+
+    #[test]
+    fn proto_test() {
+        assert!(run("ip and udp", &ipv4(17, 53)));
+        assert!(run("tcp", &ipv4(6, 80)));
+        assert!(run("icmp", &ipv4(1, 0)));
+        assert!(run("ip proto 132 and sctp", &ipv4(132, 0)));
+        assert!(!run("ip6 or tcp", &ipv4(17, 53)));
+        assert!(run("ip6 and udp and ip6 proto 17", &ipv6_udp(53)));
+        assert!(run("ether proto 0x86dd", &ipv6_udp(53)));
+        assert!(!run("arp or rarp", &ipv4(17, 53)));
+    }
+
+    #[test]
+    fn vlan_test() {
+        let tagged = vlan(100, ipv4(17, 53));
+        assert!(run("vlan", &tagged));
+        assert!(run("vlan 100 and host 10.0.0.1 and udp port 53", &tagged));
+        assert!(!run("vlan 200", &tagged));
+        assert!(!run("vlan", &ipv4(17, 53)));
+        assert!(run("vlan and vlan 7 and udp", &vlan(100, vlan(7, ipv4(17, 53)))));
+    }
+
+    #[test]
+    fn len_test() {
+        let udp = ipv4(17, 53);
+        assert_eq!(udp.len(), 54);
+        assert!(run("len = 54 and greater 54 and less 54", &udp));
+        assert!(run("len > 50 and len <= 54 and ip[2:2] = 40", &udp));
+        assert!(!run("greater 55", &udp));
+        assert!(!run("less 53", &udp));
+    }
+
+    #[test]
+    fn long_jump_test() {
+        let expression = (1..=40).map(|port| format!("port {}", port)).collect::<Vec<_>>().join(" or ");
+        let program = compile(&expression, DataLink::ETHERNET).unwrap();
+        assert!(program.instructions().len() > 256);
+
+        for port in [1, 13, 40] {
+            let frame = ipv4(17, port);
+            assert!(program.matches(&frame, frame.len() as u32), "port {}", port);
+        }
+        let frame = ipv4(17, 41);
+        assert!(!program.matches(&frame, frame.len() as u32));
+        assert!(!run(&format!("not ({})", expression), &ipv4(6, 20)));
+        assert!(run(&format!("({}) and host 10.0.0.1", expression), &ipv4(6, 40)));
+    }
+}
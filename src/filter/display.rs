@@ -0,0 +1,751 @@
+use crate::{
+    myerrors::*,
+    dissect::*,
+    filter::PacketFilter,
+    pcap_assistant::assistant::{ContextProcessor, PacketContext}
+};
+
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+
+/// Filter compiled from a Wireshark-style display filter expression.
+///
+/// A field is written with its Wireshark name and compared with `==`, `!=`, `>`, `<`, `>=`, `<=`
+/// (or `eq`, `ne`, `gt`, `lt`, `ge`, `le`), tested against a set with `in {80 443 8000..8080}`
+/// or against a bit mask with `&`. The tests are combined with `&&`, `||`, `!` (or `and`, `or`, `not`)
+/// and parentheses. An empty expression matches every packet.
+///
+/// A field present several times in a packet (`ip.addr`, `tcp.port`, `vlan.id`...) matches if one
+/// of its values matches, except for `!=` which matches if none of them is equal. A bare boolean field
+/// (`tcp.flags.syn`) is true if it is set, any other bare field or protocol if it is present.
+///
+/// The fields are:
+/// - `frame.number` (from 1), `frame.len`, `frame.cap_len`, `frame.time` (seconds since the epoch,
+///   or a UTC date `"2020-09-13 12:26:40.5"`) and `vpp.interface`
+/// - `eth.src`, `eth.dst`, `eth.addr`, `eth.type`, `vlan.id`, `vlan.priority`, `vlan.dei`, `vlan.etype`
+/// - `ip.src`, `ip.dst`, `ip.addr` (with an optional CIDR prefix), `ip.version`, `ip.hdr_len`, `ip.len`,
+///   `ip.id`, `ip.dsfield.dscp`, `ip.dsfield.ecn`, `ip.flags.df`, `ip.flags.mf`, `ip.frag_offset`,
+///   `ip.ttl`, `ip.proto`, `ip.checksum`
+/// - `ipv6.src`, `ipv6.dst`, `ipv6.addr`, `ipv6.tclass`, `ipv6.tclass.dscp`, `ipv6.flow`, `ipv6.plen`,
+///   `ipv6.nxt`, `ipv6.hlim`
+/// - `tcp.srcport`, `tcp.dstport`, `tcp.port`, `tcp.seq`, `tcp.ack`, `tcp.hdr_len`, `tcp.flags`,
+///   `tcp.flags.fin|syn|reset|push|ack|urg|ece|cwr|ns`, `tcp.window_size_value`, `tcp.checksum`,
+///   `tcp.urgent_pointer`, `tcp.len`
+/// - `udp.srcport`, `udp.dstport`, `udp.port`, `udp.length`, `udp.checksum`
+/// - `icmp.type`, `icmp.code`, `icmp.checksum`, `icmpv6.type`, `icmpv6.code`, `icmpv6.checksum`, `data.len`
+///
+/// and the protocols `frame`, `vpp`, `eth`, `vlan`, `sll`, `null`, `ip`, `ipv6`, `tcp`, `udp`, `icmp`,
+/// `icmpv6`, `data`.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use std::fs::File;
+/// use pcap_assistant::filter::{DisplayFilter, PacketSource};
+/// use pcap_assistant::pcap::{PcapReader, VppPacket};
+///
+/// let filter = DisplayFilter::new("ip.src == 10.0.0.1 && tcp.flags.syn && vpp.interface == 2").unwrap();
+/// let reader = PcapReader::<_, VppPacket>::new(File::open("vpp_netinfo.pcap").unwrap()).unwrap();
+///
+/// for packet in reader.filtered(&filter) {
+///     let packet = packet.unwrap();
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct DisplayFilter {
+    expression: String,
+    root: Option<Expr>
+}
+
+impl DisplayFilter {
+
+    /// Parse the expression.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `FilterError::Syntax` if the expression is invalid, uses an unknown field
+    /// or a value which doesn't fit the type of its field.
+    pub fn new(expression: &str) -> FilterResult<DisplayFilter> {
+
+        let mut parser = Parser { tokens: tokenize(expression)?, pos: 0 };
+
+        let root = if parser.peek().is_none() { None } else { Some(parser.expression()?) };
+
+        if let Some(token) = parser.tokens.get(parser.pos) {
+            return Err(syntax(token.position, format!("unexpected '{}'", token.text)));
+        }
+
+        Ok(DisplayFilter { expression: expression.to_string(), root })
+    }
+
+    pub fn expression(&self) -> &str {
+        &self.expression
+    }
+}
+
+/// The packet is dissected with the `DataLink` of the context.
+impl PacketFilter for DisplayFilter {
+
+    fn matches(&self, context: &PacketContext, data: &[u8]) -> bool {
+
+        match &self.root {
+            Some(root) => root.eval(&Frame { context, data, dissection: dissect(context.datalink, data) }),
+            None => true
+        }
+    }
+}
+
+/// Drops the packets which don't match.
+impl ContextProcessor for DisplayFilter {
+
+    fn process_with_context(&mut self, context: &mut PacketContext, packet: &mut Vec<u8>) -> Result<bool, String> {
+        Ok(self.matches(context, packet))
+    }
+}
+
+fn syntax(position: usize, message: String) -> FilterError {
+    FilterError::Syntax { position, message }
+}
+
+
+#[derive(Clone, Debug)]
+struct Token {
+    text: String,
+    position: usize,
+    word: bool
+}
+
+const SYMBOLS: [&str; 15] = ["&&", "||", "==", "!=", "<=", ">=", "!", "<", ">", "(", ")", "{", "}", "&", ","];
+
+fn tokenize(expression: &str) -> FilterResult<Vec<Token>> {
+
+    let mut tokens = Vec::new();
+    let chars: Vec<(usize, char)> = expression.char_indices().collect();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let (position, c) = chars[i];
+
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        // Quoted values may contain spaces (dates)
+        if c == '"' {
+            let end = chars[i + 1..].iter().position(|(_, c)| *c == '"')
+                .ok_or_else(|| syntax(position, "unterminated string".to_string()))?;
+            let text = chars[i + 1..i + 1 + end].iter().map(|(_, c)| c).collect();
+            tokens.push(Token { text, position, word: true });
+            i += end + 2;
+            continue;
+        }
+
+        let is_word_char = |c: char| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-' | ':' | '/');
+
+        if is_word_char(c) {
+            let end = chars[i..].iter().position(|(_, c)| !is_word_char(*c)).map_or(chars.len(), |len| i + len);
+            let text = chars[i..end].iter().map(|(_, c)| c).collect();
+            tokens.push(Token { text, position, word: true });
+            i = end;
+            continue;
+        }
+
+        let rest = &expression[position..];
+        match SYMBOLS.iter().find(|symbol| rest.starts_with(**symbol)) {
+            Some(symbol) => {
+                tokens.push(Token { text: symbol.to_string(), position, word: false });
+                i += symbol.len();
+            },
+            None => return Err(syntax(position, format!("unexpected character '{}'", c)))
+        }
+    }
+
+    Ok(tokens)
+}
+
+
+// Type of the values of a field, it decides how the compared values are parsed
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Kind {
+    Protocol,
+    Bool,
+    Unsigned,
+    Ipv4,
+    Ipv6,
+    Mac,
+    Time
+}
+
+// Packet given to the fields
+struct Frame<'a> {
+    context: &'a PacketContext,
+    data: &'a [u8],
+    dissection: Dissection<'a>
+}
+
+impl<'a> Frame<'a> {
+
+    fn layers(&self) -> impl Iterator<Item = &Layer<'a>> {
+        self.dissection.layers.iter().map(|(_, layer)| layer)
+    }
+}
+
+// Field values are all compared as integers: addresses in network order, times in nanoseconds
+#[derive(Copy, Clone, Debug)]
+struct Field {
+    kind: Kind,
+    values: fn(&Frame, &mut Vec<u128>)
+}
+
+// Field pushing values for every layer of the variant
+macro_rules! layer_field {
+    ($kind:ident, $variant:ident, |$view:ident| $($value:expr),+) => {
+        Field {
+            kind: Kind::$kind,
+            values: |frame, values| {
+                for layer in frame.layers() {
+                    if let Layer::$variant($view) = layer {
+                        $(values.push(u128::from($value));)+
+                    }
+                }
+            }
+        }
+    };
+}
+
+// Protocol present once for every layer of the variant
+macro_rules! protocol {
+    ($variant:ident) => {
+        layer_field!(Protocol, $variant, |_layer| 0_u8)
+    };
+}
+
+fn field(name: &str) -> Option<Field> {
+
+    let field = match name {
+        "frame" => Field { kind: Kind::Protocol, values: |_, values| values.push(0) },
+        "frame.number" => Field { kind: Kind::Unsigned, values: |frame, values| values.push(frame.context.index as u128 + 1) },
+        "frame.len" => Field { kind: Kind::Unsigned, values: |frame, values| values.push(frame.context.orig_len.into()) },
+        "frame.cap_len" => Field { kind: Kind::Unsigned, values: |frame, values| values.push(frame.data.len() as u128) },
+        "frame.time" | "frame.time_epoch" => Field {
+            kind: Kind::Time,
+            values: |frame, values| values.push(u128::from(frame.context.ts_sec) * 1_000_000_000 + u128::from(frame.context.ts_nsec))
+        },
+        "vpp" => Field { kind: Kind::Protocol, values: |frame, values| values.extend(frame.context.interface_index.map(|_| 0)) },
+        "vpp.interface" => Field { kind: Kind::Unsigned, values: |frame, values| values.extend(frame.context.interface_index.map(u128::from)) },
+
+        "eth" => protocol!(Ethernet),
+        "eth.src" => layer_field!(Mac, Ethernet, |eth| mac(eth.src())),
+        "eth.dst" => layer_field!(Mac, Ethernet, |eth| mac(eth.dst())),
+        "eth.addr" => layer_field!(Mac, Ethernet, |eth| mac(eth.src()), mac(eth.dst())),
+        "eth.type" => layer_field!(Unsigned, Ethernet, |eth| eth.ethertype()),
+        "vlan" => protocol!(Vlan),
+        "vlan.id" => layer_field!(Unsigned, Vlan, |tag| tag.vid()),
+        "vlan.priority" => layer_field!(Unsigned, Vlan, |tag| tag.pcp()),
+        "vlan.dei" => layer_field!(Bool, Vlan, |tag| tag.dei()),
+        "vlan.etype" => layer_field!(Unsigned, Vlan, |tag| tag.ethertype()),
+        "sll" => protocol!(LinuxSll),
+        "null" => protocol!(Null),
+
+        "ip" => protocol!(Ipv4),
+        "ip.src" => layer_field!(Ipv4, Ipv4, |ip| u32::from(ip.src())),
+        "ip.dst" => layer_field!(Ipv4, Ipv4, |ip| u32::from(ip.dst())),
+        "ip.addr" => layer_field!(Ipv4, Ipv4, |ip| u32::from(ip.src()), u32::from(ip.dst())),
+        "ip.version" => layer_field!(Unsigned, Ipv4, |ip| ip.version()),
+        "ip.hdr_len" => layer_field!(Unsigned, Ipv4, |ip| ip.header_len() as u64),
+        "ip.len" => layer_field!(Unsigned, Ipv4, |ip| ip.total_len()),
+        "ip.id" => layer_field!(Unsigned, Ipv4, |ip| ip.identification()),
+        "ip.dsfield.dscp" => layer_field!(Unsigned, Ipv4, |ip| ip.dscp()),
+        "ip.dsfield.ecn" => layer_field!(Unsigned, Ipv4, |ip| ip.ecn()),
+        "ip.flags.df" => layer_field!(Bool, Ipv4, |ip| ip.dont_fragment()),
+        "ip.flags.mf" => layer_field!(Bool, Ipv4, |ip| ip.more_fragments()),
+        "ip.frag_offset" => layer_field!(Unsigned, Ipv4, |ip| ip.fragment_offset()),
+        "ip.ttl" => layer_field!(Unsigned, Ipv4, |ip| ip.ttl()),
+        "ip.proto" => layer_field!(Unsigned, Ipv4, |ip| ip.protocol()),
+        "ip.checksum" => layer_field!(Unsigned, Ipv4, |ip| ip.checksum()),
+
+        "ipv6" => protocol!(Ipv6),
+        "ipv6.src" => layer_field!(Ipv6, Ipv6, |ip| ip.src()),
+        "ipv6.dst" => layer_field!(Ipv6, Ipv6, |ip| ip.dst()),
+        "ipv6.addr" => layer_field!(Ipv6, Ipv6, |ip| ip.src(), ip.dst()),
+        "ipv6.tclass" => layer_field!(Unsigned, Ipv6, |ip| ip.traffic_class()),
+        "ipv6.tclass.dscp" => layer_field!(Unsigned, Ipv6, |ip| ip.dscp()),
+        "ipv6.flow" => layer_field!(Unsigned, Ipv6, |ip| ip.flow_label()),
+        "ipv6.plen" => layer_field!(Unsigned, Ipv6, |ip| ip.payload_len()),
+        "ipv6.nxt" => layer_field!(Unsigned, Ipv6, |ip| ip.next_header()),
+        "ipv6.hlim" => layer_field!(Unsigned, Ipv6, |ip| ip.hop_limit()),
+
+        "tcp" => protocol!(Tcp),
+        "tcp.srcport" => layer_field!(Unsigned, Tcp, |tcp| tcp.src_port()),
+        "tcp.dstport" => layer_field!(Unsigned, Tcp, |tcp| tcp.dst_port()),
+        "tcp.port" => layer_field!(Unsigned, Tcp, |tcp| tcp.src_port(), tcp.dst_port()),
+        "tcp.seq" => layer_field!(Unsigned, Tcp, |tcp| tcp.seq()),
+        "tcp.ack" => layer_field!(Unsigned, Tcp, |tcp| tcp.ack()),
+        "tcp.hdr_len" => layer_field!(Unsigned, Tcp, |tcp| tcp.header_len() as u64),
+        "tcp.flags" => layer_field!(Unsigned, Tcp, |tcp| tcp.flags()),
+        "tcp.flags.fin" => layer_field!(Bool, Tcp, |tcp| tcp.has_flag(TcpSegment::FIN)),
+        "tcp.flags.syn" => layer_field!(Bool, Tcp, |tcp| tcp.has_flag(TcpSegment::SYN)),
+        "tcp.flags.reset" => layer_field!(Bool, Tcp, |tcp| tcp.has_flag(TcpSegment::RST)),
+        "tcp.flags.push" => layer_field!(Bool, Tcp, |tcp| tcp.has_flag(TcpSegment::PSH)),
+        "tcp.flags.ack" => layer_field!(Bool, Tcp, |tcp| tcp.has_flag(TcpSegment::ACK)),
+        "tcp.flags.urg" => layer_field!(Bool, Tcp, |tcp| tcp.has_flag(TcpSegment::URG)),
+        "tcp.flags.ece" => layer_field!(Bool, Tcp, |tcp| tcp.has_flag(TcpSegment::ECE)),
+        "tcp.flags.cwr" => layer_field!(Bool, Tcp, |tcp| tcp.has_flag(TcpSegment::CWR)),
+        "tcp.flags.ns" => layer_field!(Bool, Tcp, |tcp| tcp.has_flag(TcpSegment::NS)),
+        "tcp.window_size_value" => layer_field!(Unsigned, Tcp, |tcp| tcp.window()),
+        "tcp.checksum" => layer_field!(Unsigned, Tcp, |tcp| tcp.checksum()),
+        "tcp.urgent_pointer" => layer_field!(Unsigned, Tcp, |tcp| tcp.urgent_pointer()),
+        "tcp.len" => layer_field!(Unsigned, Tcp, |tcp| tcp.payload().len() as u64),
+
+        "udp" => protocol!(Udp),
+        "udp.srcport" => layer_field!(Unsigned, Udp, |udp| udp.src_port()),
+        "udp.dstport" => layer_field!(Unsigned, Udp, |udp| udp.dst_port()),
+        "udp.port" => layer_field!(Unsigned, Udp, |udp| udp.src_port(), udp.dst_port()),
+        "udp.length" => layer_field!(Unsigned, Udp, |udp| udp.length()),
+        "udp.checksum" => layer_field!(Unsigned, Udp, |udp| udp.checksum()),
+
+        "icmp" => protocol!(Icmp),
+        "icmp.type" => layer_field!(Unsigned, Icmp, |icmp| icmp.icmp_type()),
+        "icmp.code" => layer_field!(Unsigned, Icmp, |icmp| icmp.code()),
+        "icmp.checksum" => layer_field!(Unsigned, Icmp, |icmp| icmp.checksum()),
+        "icmpv6" => protocol!(Icmpv6),
+        "icmpv6.type" => layer_field!(Unsigned, Icmpv6, |icmp| icmp.icmp_type()),
+        "icmpv6.code" => layer_field!(Unsigned, Icmpv6, |icmp| icmp.code()),
+        "icmpv6.checksum" => layer_field!(Unsigned, Icmpv6, |icmp| icmp.checksum()),
+
+        "data" => protocol!(Payload),
+        "data.len" => layer_field!(Unsigned, Payload, |payload| payload.len() as u64),
+
+        _ => return None
+    };
+
+    Some(field)
+}
+
+fn mac(address: MacAddr) -> u64 {
+    address.0.iter().fold(0, |value, byte| value << 8 | u64::from(*byte))
+}
+
+
+// Value compared with a field, only the bits of the mask are compared for equality (CIDR prefix)
+#[derive(Copy, Clone, Debug)]
+struct Value {
+    value: u128,
+    mask: u128
+}
+
+impl Value {
+
+    fn exact(value: u128) -> Value {
+        Value { value, mask: u128::MAX }
+    }
+
+    fn equals(&self, value: u128) -> bool {
+        value & self.mask == self.value & self.mask
+    }
+}
+
+fn parse_value(kind: Kind, text: &str) -> Option<Value> {
+
+    match kind {
+        Kind::Protocol => None,
+        Kind::Bool => match text {
+            "1" | "true" | "True" | "TRUE" => Some(Value::exact(1)),
+            "0" | "false" | "False" | "FALSE" => Some(Value::exact(0)),
+            _ => None
+        },
+        Kind::Unsigned => parse_unsigned(text).map(Value::exact),
+        Kind::Ipv4 => {
+            let (address, prefix) = parse_prefix(text, 32)?;
+            let address: Ipv4Addr = address.parse().ok()?;
+            Some(Value { value: u32::from(address).into(), mask: prefix_mask(prefix, 32) })
+        },
+        Kind::Ipv6 => {
+            let (address, prefix) = parse_prefix(text, 128)?;
+            let address: Ipv6Addr = address.parse().ok()?;
+            Some(Value { value: address.into(), mask: prefix_mask(prefix, 128) })
+        },
+        Kind::Mac => text.parse().ok().map(|address| Value::exact(mac(address).into())),
+        Kind::Time => parse_time(text).map(Value::exact)
+    }
+}
+
+fn parse_unsigned(text: &str) -> Option<u128> {
+
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok().map(u128::from),
+        None => text.parse::<u64>().ok().map(u128::from)
+    }
+}
+
+// Address and prefix length of `address/prefix`, `bits` if there is no prefix
+fn parse_prefix(text: &str, bits: u32) -> Option<(&str, u32)> {
+
+    match text.split_once('/') {
+        Some((address, prefix)) => prefix.parse().ok().filter(|prefix| *prefix <= bits).map(|prefix| (address, prefix)),
+        None => Some((text, bits))
+    }
+}
+
+// Mask of the first `prefix` bits of a `bits` long address
+fn prefix_mask(prefix: u32, bits: u32) -> u128 {
+
+    let all = u128::MAX >> (128 - bits);
+    all & !(all.checked_shr(prefix).unwrap_or(0))
+}
+
+// Nanoseconds since the epoch of `1600000000.25` or of a UTC date `2020-09-13 12:26:40.25`
+fn parse_time(text: &str) -> Option<u128> {
+
+    let text = text.strip_suffix('Z').unwrap_or(text);
+    let (seconds, fraction) = text.split_once('.').unwrap_or((text, ""));
+
+    if fraction.len() > 9 || !fraction.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let nanoseconds = format!("{:0<9}", fraction).parse::<u128>().ok()?;
+
+    let seconds = match seconds.split_once([' ', 'T']) {
+        Some((date, time)) => {
+            let date: Vec<u64> = date.split('-').map(|part| part.parse().ok()).collect::<Option<_>>()?;
+            let time: Vec<u64> = time.split(':').map(|part| part.parse().ok()).collect::<Option<_>>()?;
+            match (date.as_slice(), time.as_slice()) {
+                ([year, month, day], [hour, minute, second]) if *hour < 24 && *minute < 60 && *second < 60 => {
+                    days_since_epoch(*year, *month, *day)? * 86400 + hour * 3600 + minute * 60 + second
+                },
+                _ => return None
+            }
+        },
+        None => seconds.parse().ok()?
+    };
+
+    Some(u128::from(seconds) * 1_000_000_000 + nanoseconds)
+}
+
+// Days from 1970-01-01 to the date of the proleptic Gregorian calendar
+fn days_since_epoch(year: u64, month: u64, day: u64) -> Option<u64> {
+
+    let leap = year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400));
+    let month_days = match month {
+        2 if leap => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31
+    };
+
+    if !(1970..=9999).contains(&year) || !(1..=12).contains(&month) || !(1..=month_days).contains(&day) {
+        return None;
+    }
+
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let year_of_era = year % 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    Some(era * 146097 + day_of_era - 719468)
+}
+
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Gt,
+    Lt,
+    Ge,
+    Le
+}
+
+#[derive(Clone, Debug)]
+enum Test {
+    // Bare field: set for booleans, present otherwise
+    Exists,
+    Compare(Op, Value),
+    // Values and inclusive ranges
+    In(Vec<(Value, Option<u128>)>),
+    BitAnd(u128)
+}
+
+#[derive(Clone, Debug)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Field(Field, Test)
+}
+
+impl Expr {
+
+    fn eval(&self, frame: &Frame) -> bool {
+
+        let (field, test) = match self {
+            Expr::And(lhs, rhs) => return lhs.eval(frame) && rhs.eval(frame),
+            Expr::Or(lhs, rhs) => return lhs.eval(frame) || rhs.eval(frame),
+            Expr::Not(expr) => return !expr.eval(frame),
+            Expr::Field(field, test) => (field, test)
+        };
+
+        let mut values = Vec::new();
+        (field.values)(frame, &mut values);
+
+        match test {
+            Test::Exists if field.kind == Kind::Bool => values.iter().any(|value| *value != 0),
+            Test::Exists => !values.is_empty(),
+            Test::Compare(Op::Eq, expected) => values.iter().any(|value| expected.equals(*value)),
+            Test::Compare(Op::Ne, expected) => !values.is_empty() && !values.iter().any(|value| expected.equals(*value)),
+            Test::Compare(op, expected) => values.iter().any(|value| match op {
+                Op::Gt => *value > expected.value,
+                Op::Lt => *value < expected.value,
+                Op::Ge => *value >= expected.value,
+                _ => *value <= expected.value
+            }),
+            Test::In(elements) => values.iter().any(|value| elements.iter().any(|(low, high)| match high {
+                Some(high) => (low.value..=*high).contains(value),
+                None => low.equals(*value)
+            })),
+            Test::BitAnd(mask) => values.iter().any(|value| value & mask != 0)
+        }
+    }
+}
+
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize
+}
+
+impl Parser {
+
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.pos).map(|token| token.text.as_str())
+    }
+
+    fn position(&self) -> usize {
+        self.tokens.get(self.pos).map(|token| token.position)
+            .unwrap_or_else(|| self.tokens.last().map(|token| token.position + token.text.len()).unwrap_or(0))
+    }
+
+    fn error<T>(&self, message: &str) -> FilterResult<T> {
+
+        let message = match self.peek() {
+            Some(text) => format!("{}, found '{}'", message, text),
+            None => format!("{}, found the end of the expression", message)
+        };
+
+        Err(syntax(self.position(), message))
+    }
+
+    fn accept(&mut self, texts: &[&str]) -> bool {
+
+        if self.peek().is_some_and(|text| texts.contains(&text)) {
+            self.pos += 1;
+            return true;
+        }
+
+        false
+    }
+
+    fn expect(&mut self, text: &str) -> FilterResult<()> {
+
+        if !self.accept(&[text]) {
+            return self.error(&format!("expected '{}'", text));
+        }
+
+        Ok(())
+    }
+
+    // Word token and its position
+    fn word(&mut self, what: &str) -> FilterResult<(String, usize)> {
+
+        match self.tokens.get(self.pos) {
+            Some(token) if token.word => {
+                self.pos += 1;
+                Ok((token.text.clone(), token.position))
+            },
+            _ => self.error(&format!("expected {}", what))
+        }
+    }
+
+    fn expression(&mut self) -> FilterResult<Expr> {
+
+        let mut expr = self.and()?;
+
+        while self.accept(&["||", "or"]) {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> FilterResult<Expr> {
+
+        let mut expr = self.not()?;
+
+        while self.accept(&["&&", "and"]) {
+            expr = Expr::And(Box::new(expr), Box::new(self.not()?));
+        }
+
+        Ok(expr)
+    }
+
+    fn not(&mut self) -> FilterResult<Expr> {
+
+        if self.accept(&["!", "not"]) {
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+
+        if self.accept(&["("]) {
+            let expr = self.expression()?;
+            self.expect(")")?;
+            return Ok(expr);
+        }
+
+        self.test()
+    }
+
+    fn test(&mut self) -> FilterResult<Expr> {
+
+        let (name, position) = self.word("a field")?;
+        let field = field(&name).ok_or_else(|| syntax(position, format!("unknown field '{}'", name)))?;
+
+        let op = match self.peek() {
+            Some("==" | "eq") => Op::Eq,
+            Some("!=" | "ne") => Op::Ne,
+            Some(">" | "gt") => Op::Gt,
+            Some("<" | "lt") => Op::Lt,
+            Some(">=" | "ge") => Op::Ge,
+            Some("<=" | "le") => Op::Le,
+            Some("in") => {
+                self.pos += 1;
+                return Ok(Expr::Field(field, Test::In(self.set(&name, field.kind)?)));
+            },
+            Some("&") => {
+                self.pos += 1;
+                if !matches!(field.kind, Kind::Bool | Kind::Unsigned) {
+                    return Err(syntax(position, format!("'{}' can't be masked", name)));
+                }
+                let mask = self.value(&name, field.kind)?;
+                return Ok(Expr::Field(field, Test::BitAnd(mask.value)));
+            },
+            _ => return Ok(Expr::Field(field, Test::Exists))
+        };
+        self.pos += 1;
+
+        Ok(Expr::Field(field, Test::Compare(op, self.value(&name, field.kind)?)))
+    }
+
+    // Elements of `{80 443 8000..8080}`, optionally separated by commas
+    fn set(&mut self, name: &str, kind: Kind) -> FilterResult<Vec<(Value, Option<u128>)>> {
+
+        self.expect("{")?;
+
+        let mut elements = Vec::new();
+
+        while !self.accept(&["}"]) {
+            let (text, position) = self.word("a value or '}'")?;
+
+            let element = match text.split_once("..") {
+                Some((low, high)) => parse_value(kind, low).zip(parse_value(kind, high)).map(|(low, high)| (low, Some(high.value))),
+                None => parse_value(kind, &text).map(|value| (value, None))
+            };
+            elements.push(element.ok_or_else(|| invalid_value(name, kind, &text, position))?);
+
+            self.accept(&[","]);
+        }
+
+        if elements.is_empty() {
+            return self.error("expected a value in the set");
+        }
+
+        Ok(elements)
+    }
+
+    fn value(&mut self, name: &str, kind: Kind) -> FilterResult<Value> {
+
+        let (text, position) = self.word("a value")?;
+
+        parse_value(kind, &text).ok_or_else(|| invalid_value(name, kind, &text, position))
+    }
+}
+
+fn invalid_value(name: &str, kind: Kind, text: &str, position: usize) -> FilterError {
+
+    let expected = match kind {
+        Kind::Protocol => return syntax(position, format!("'{}' is a protocol, it can't be compared", name)),
+        Kind::Bool => "a boolean",
+        Kind::Unsigned => "an unsigned integer",
+        Kind::Ipv4 => "an IPv4 address",
+        Kind::Ipv6 => "an IPv6 address",
+        Kind::Mac => "a MAC address",
+        Kind::Time => "a time"
+    };
+
+    syntax(position, format!("'{}' isn't {} as expected by '{}'", text, expected, name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DataLink;
+
+    fn syn() -> Vec<u8> {
+        let mut frame = vec![0x02, 0, 0, 0, 0, 2, 0x02, 0, 0, 0, 0, 1, 0x81, 0x00, 0x00, 0x64, 0x08, 0x00];
+        frame.extend_from_slice(&[0x45, 0, 0, 40, 0, 1, 0x40, 0, 64, 6, 0, 0, 10, 0, 0, 1, 10, 0, 0, 2]);
+        frame.extend_from_slice(&[0x30, 0x39, 0, 80, 0, 0, 0, 1, 0, 0, 0, 0, 0x50, 0x02, 0xFF, 0xFF, 0, 0, 0, 0]);
+        frame
+    }
+
+    fn context(interface_index: Option<u32>) -> PacketContext {
+        PacketContext { index: 4, ts_sec: 1600000000, ts_nsec: 500_000_000, incl_len: 60, orig_len: 60, datalink: DataLink::ETHERNET, interface_index }
+    }
+
+    fn matches(expression: &str) -> bool {
+        DisplayFilter::new(expression).unwrap().matches(&context(Some(2)), &syn())
+    }
+
+    #[test]
+    fn display_filter_test() {
+        assert!(matches(""));
+        assert!(matches("ip.src == 10.0.0.1 && tcp.flags.syn && vpp.interface == 2"));
+        assert!(!matches("tcp.flags.ack or udp"));
+        assert!(matches("not tcp.flags.ack and !(ip.ttl < 64)"));
+        assert!(matches("ip.addr == 10.0.0.2 and ip.addr == 10.0.0.0/24 and ip.dst != 10.0.1.0/24"));
+        assert!(!matches("ip.addr != 10.0.0.2"));
+        assert!(matches("eth.src == 02:00:00:00:00:01 && eth.type == 0x8100 && vlan.id == 100"));
+        assert!(matches("tcp.port in {443, 8000..8080 80} && tcp.srcport gt 1024"));
+        assert!(matches("tcp.flags & 0x12 and ip.flags.df == 1 and ip.flags.mf == false"));
+        assert!(matches("frame.number == 5 && frame.len == 60 && frame.cap_len == 58 && !data && !ipv6"));
+        assert!(matches("frame.time == 1600000000.5 && frame.time >= \"2020-09-13 12:26:40\" && frame.time < 2020-09-13T12:26:41Z"));
+
+        let filter = DisplayFilter::new("vpp").unwrap();
+        assert!(!filter.matches(&context(None), &syn()));
+        assert!(!DisplayFilter::new("ipv6.hlim > 0").unwrap().matches(&context(None), &syn()));
+
+        assert!(matches!(DisplayFilter::new("ip.src ==").unwrap_err(), FilterError::Syntax { position: 9, .. }));
+        assert!(matches!(DisplayFilter::new("ip.source == 1.2.3.4").unwrap_err(), FilterError::Syntax { position: 0, .. }));
+        assert!(matches!(DisplayFilter::new("tcp && ip.src == 1.2.3").unwrap_err(), FilterError::Syntax { position: 17, .. }));
+        assert!(DisplayFilter::new("tcp == 1").is_err());
+        assert!(DisplayFilter::new("(tcp").is_err());
+        assert!(DisplayFilter::new("frame.time > \"2020-13-01 00:00:00\"").is_err());
+        for date in ["2024-02-31", "2023-02-29", "2100-02-29", "2024-04-31", "2024-01-00", "10000-01-01", "18446744073709551615-03-01"] {
+            let expression = format!("frame.time > \"{} 00:00:00\"", date);
+            assert!(matches!(DisplayFilter::new(&expression), Err(FilterError::Syntax { position: 13, .. })), "{}", date);
+        }
+        for date in ["2024-02-29", "2000-02-29", "2024-12-31", "9999-12-31"] {
+            assert!(DisplayFilter::new(&format!("frame.time > \"{} 00:00:00\"", date)).is_ok(), "{}", date);
+        }
+    }
+
+    #[test]
+    fn display_filter_processor_test() {
+        let mut filter = DisplayFilter::new("vlan.id == 100").unwrap();
+        let mut context = context(None);
+        assert_eq!(filter.process_with_context(&mut context, &mut syn()), Ok(true));
+
+        let mut untagged = syn();
+        untagged.drain(12..16);
+        assert_eq!(filter.process_with_context(&mut context, &mut untagged), Ok(false));
+    }
+}
@@ -0,0 +1,297 @@
+//! Packet filters and the reader adapter applying them.
+//!
+//! `BpfFilter` compiles tcpdump-style expressions to classic BPF programs,
+//! `DisplayFilter` evaluates Wireshark-style expressions on the dissected fields.
+
+mod bpf;
+mod bpf_compiler;
+mod display;
+
+pub use bpf::*;
+pub use bpf_compiler::compile;
+pub use display::DisplayFilter;
+
+use crate::{
+    CaptureReader,
+    DataLink,
+    myerrors::*,
+    pcap::{PcapReader, SomePacket},
+    pcapng::PcapNgReader,
+    pcap_assistant::assistant::{PacketContext, PacketProcessor}
+};
+
+use std::io::Read;
+
+
+/// Trait for packet filters.
+pub trait PacketFilter {
+
+    /// True if the packet with the given context and data is kept.
+    fn matches(&self, context: &PacketContext, data: &[u8]) -> bool;
+}
+
+impl<F: PacketFilter + ?Sized> PacketFilter for &F {
+
+    fn matches(&self, context: &PacketContext, data: &[u8]) -> bool {
+        (**self).matches(context, data)
+    }
+}
+
+/// Iterator of packets knowing the `DataLink` of the packets it yields.
+pub trait PacketSource: Iterator {
+
+    /// DataLink of the last yielded packet.
+    fn current_datalink(&self) -> DataLink;
+
+    /// Keep only the packets matching `filter`.
+    fn filtered<F: PacketFilter>(self, filter: F) -> Filtered<Self, F> where Self: Sized {
+        Filtered::new(self, filter)
+    }
+}
+
+impl<T: Read, P: SomePacket<'static>> PacketSource for PcapReader<T, P> {
+
+    fn current_datalink(&self) -> DataLink {
+        self.header.datalink
+    }
+}
+
+impl<T: Read, P: SomePacket<'static>> PacketSource for PcapNgReader<T, P> {
+
+    fn current_datalink(&self) -> DataLink {
+        self.packet_datalink()
+    }
+}
+
+impl<T: Read, P: SomePacket<'static>> PacketSource for CaptureReader<T, P> {
+
+    fn current_datalink(&self) -> DataLink {
+        self.packet_datalink()
+    }
+}
+
+/// Filter compiled from a pcap filter expression (tcpdump syntax), see `compile` for the supported syntax.
+///
+/// It is also a `PacketProcessor` dropping the packets which don't match.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use std::fs::File;
+/// use pcap_assistant::filter::{BpfFilter, PacketSource};
+/// use pcap_assistant::pcap::{Packet, PcapReader};
+///
+/// let reader = PcapReader::<_, Packet>::new(File::open("test.pcap").unwrap()).unwrap();
+/// let filter = BpfFilter::new("udp and dst port 4789", reader.header.datalink).unwrap();
+///
+/// for packet in reader.filtered(filter) {
+///     let packet = packet.unwrap();
+/// }
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BpfFilter {
+    expression: String,
+    datalink: DataLink,
+    program: BpfProgram
+}
+
+impl BpfFilter {
+
+    /// Compile the expression for packets captured with the given `DataLink`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `FilterError` if the expression is invalid or not supported.
+    pub fn new(expression: &str, datalink: DataLink) -> FilterResult<BpfFilter> {
+
+        let program = compile(expression, datalink)?;
+
+        Ok(BpfFilter { expression: expression.to_string(), datalink, program })
+    }
+
+    pub fn expression(&self) -> &str {
+        &self.expression
+    }
+
+    pub fn datalink(&self) -> DataLink {
+        self.datalink
+    }
+
+    pub fn program(&self) -> &BpfProgram {
+        &self.program
+    }
+}
+
+/// The original length of the packet is the one of the context,
+/// packets of another `DataLink` than the compiled one never match.
+impl PacketFilter for BpfFilter {
+
+    fn matches(&self, context: &PacketContext, data: &[u8]) -> bool {
+        context.datalink == self.datalink && self.program.matches(data, context.orig_len)
+    }
+}
+
+/// Drops the packets which don't match, the original length is the data length.
+impl PacketProcessor for BpfFilter {
+
+    fn process_packet(&mut self, packet: &mut Vec<u8>) -> bool {
+        self.program.matches(packet, packet.len() as u32)
+    }
+}
+
+/// Iterator adapter keeping the packets matching a `PacketFilter`, errors are always yielded.
+///
+/// Created by `PacketSource::filtered`. The index of the packets given to the filter
+/// counts all the packets read.
+#[derive(Debug)]
+pub struct Filtered<I, F> {
+    inner: I,
+    filter: F,
+    index: usize
+}
+
+impl<I, F> Filtered<I, F> {
+
+    pub fn new(inner: I, filter: F) -> Filtered<I, F> {
+
+        Filtered { inner, filter, index: 0 }
+    }
+
+    /// Consumes the `Filtered`, returning the wrapped iterator.
+    pub fn into_inner(self) -> I {
+        self.inner
+    }
+}
+
+impl<I, F, P, E> Iterator for Filtered<I, F>
+    where I: PacketSource<Item = Result<P, E>>,
+          P: SomePacket<'static>,
+          F: PacketFilter
+{
+    type Item = Result<P, E>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+
+        loop {
+            let packet = match self.inner.next()? {
+                Ok(packet) => packet,
+                Err(error) => return Some(Err(error))
+            };
+
+            let context = PacketContext::new(self.index, self.inner.current_datalink(), &packet.get_header());
+            self.index += 1;
+
+            if self.filter.matches(&context, packet.get_data()) {
+                return Some(Ok(packet));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pcap::*;
+    use crate::test_fixtures;
+    use std::io::Cursor;
+
+    fn ethernet_ipv4(protocol: u8, transport: &[u8]) -> Vec<u8> {
+        let mut frame = vec![0x02, 0, 0, 0, 0, 2, 0x02, 0, 0, 0, 0, 1, 0x08, 0x00];
+        frame.extend_from_slice(&[0x45, 0, 0, 20 + transport.len() as u8, 0, 0, 0x40, 0, 64, protocol, 0, 0, 10, 0, 0, 1, 10, 0, 0, 2]);
+        frame.extend_from_slice(transport);
+        frame
+    }
+
+    fn vxlan() -> Vec<u8> {
+        ethernet_ipv4(17, &[0x30, 0x39, 0x12, 0xB5, 0, 8, 0, 0])
+    }
+
+    fn syn() -> Vec<u8> {
+        ethernet_ipv4(6, &[0, 80, 0x1F, 0x90, 0, 0, 0, 1, 0, 0, 0, 0, 0x50, 0x02, 0xFF, 0xFF, 0, 0, 0, 0])
+    }
+
+    fn matches(expression: &str, frame: &[u8]) -> bool {
+        compile(expression, DataLink::ETHERNET).unwrap().matches(frame, frame.len() as u32)
+    }
+
+    #[test]
+    fn bpf_compile_test() {
+        assert!(matches("", &vxlan()));
+        assert!(matches("udp and dst port 4789", &vxlan()));
+        assert!(!matches("udp and src port 4789", &vxlan()));
+        assert!(matches("udp port 80 or 4789", &vxlan()));
+        assert!(!matches("tcp", &vxlan()));
+        assert!(matches("ip and not ip6", &vxlan()));
+        assert!(matches("src host 10.0.0.1 && dst net 10.0.0.0/8", &vxlan()));
+        assert!(matches("net 10.0.0.0 mask 255.255.255.0", &vxlan()));
+        assert!(!matches("dst host 10.0.0.1", &vxlan()));
+        assert!(matches("ether src 02:00:00:00:00:01 and ether dst host 02:00:00:00:00:02", &vxlan()));
+        assert!(matches("portrange 4000-5000", &vxlan()));
+        assert!(matches("ip proto \\udp and greater 30 and less 50", &vxlan()));
+        assert!(matches("ip[9] = 17 and len == 42 and (ip[0] & 0xf) * 4 = 20", &vxlan()));
+        assert!(matches("udp[2:2] = 4789 and udp[ip[0] - 0x45 + 2:2] = 4789", &vxlan()));
+
+        assert!(matches("tcp[tcpflags] & tcp-syn != 0 and tcp[tcpflags] & tcp-ack = 0", &syn()));
+        assert!(matches("tcp src port 80 and not (udp or icmp)", &syn()));
+        assert!(!matches("tcp[13] & (tcp-fin | tcp-rst) != 0", &syn()));
+
+        // Primitives after vlan apply to the encapsulated packet
+        let mut tagged = vxlan();
+        tagged.splice(12..12, [0x81, 0x00, 0x00, 0x64]);
+        assert!(matches("vlan 100 and udp dst port 4789", &tagged));
+        assert!(!matches("vlan 200", &tagged));
+        assert!(!matches("udp", &tagged));
+
+        // Truncated packet
+        assert!(!matches("udp port 4789", &vxlan()[..30]));
+
+        assert!(compile("host 10.0.0.1", DataLink::RAW).unwrap().matches(&vxlan()[14..], 28));
+        assert!(compile("ip6 and tcp", DataLink::RAW).unwrap().run(&vxlan()[14..], 28) == 0);
+
+        assert!(matches!(compile("udp and", DataLink::ETHERNET), Err(FilterError::Syntax { position: 7, .. })));
+        assert!(matches!(compile("port http", DataLink::ETHERNET), Err(FilterError::Syntax { position: 5, .. })));
+        assert!(compile("ether host 02:00:00:00:00:01", DataLink::RAW).is_err());
+        assert!(compile("ip", DataLink::USER0).is_err());
+        assert!(matches!(compile("ip[0] / 0 = 1", DataLink::ETHERNET), Err(FilterError::Unsupported(_))));
+        assert!(matches!(compile("ip[0xffffffff] = 1", DataLink::ETHERNET), Err(FilterError::Unsupported(_))));
+        assert!(matches!(compile("tcp[0xfffffff8] = 1", DataLink::ETHERNET), Err(FilterError::Unsupported(_))));
+        assert!(matches!(compile("ip proto 300", DataLink::ETHERNET), Err(FilterError::Syntax { position: 9, .. })));
+        assert!(matches!(compile("ether proto 70000", DataLink::ETHERNET), Err(FilterError::Syntax { .. })));
+        assert!(compile("ip proto 255", DataLink::ETHERNET).is_ok());
+    }
+
+    #[test]
+    fn bpf_program_test() {
+        let program = compile("ip", DataLink::ETHERNET).unwrap();
+        assert_eq!(program.to_string(), "(000) ldh      [12]\n(001) jeq      #0x800           jt 2\tjf 3\n(002) ret      #262144\n(003) ret      #0\n");
+        assert_eq!(BpfProgram::new(program.instructions().to_vec()).unwrap(), program);
+
+        let jump_out = vec![BpfInstruction::jump(BPF_JMP | BPF_JEQ | BPF_K, 0, 0, 1), BpfInstruction::stmt(BPF_RET | BPF_K, 0)];
+        assert!(BpfProgram::new(jump_out).is_err());
+        assert!(BpfProgram::new(vec![BpfInstruction::stmt(BPF_LD | BPF_IMM, 0)]).is_err());
+        assert!(BpfProgram::new(vec![BpfInstruction::stmt(BPF_ST, 16), BpfInstruction::stmt(BPF_RET | BPF_A, 0)]).is_err());
+        assert!(BpfProgram::new(vec![BpfInstruction::stmt(0x104, 0), BpfInstruction::stmt(BPF_RET | BPF_K, 1)]).is_err());
+        assert!(BpfProgram::new(vec![BpfInstruction::stmt(BPF_LD | 0x18 | BPF_ABS, 0), BpfInstruction::stmt(BPF_RET | BPF_A, 0)]).is_err());
+
+        // Returns A, divided by X = 0
+        let program = BpfProgram::new(vec![
+            BpfInstruction::stmt(BPF_LD | BPF_W | BPF_LEN, 0),
+            BpfInstruction::stmt(BPF_ALU | BPF_DIV | BPF_X, 0),
+            BpfInstruction::stmt(BPF_RET | BPF_A, 0)
+        ]).unwrap();
+        assert_eq!(program.run(&[], 10), 0);
+
+        let mut filter = BpfFilter::new("udp", DataLink::ETHERNET).unwrap();
+        assert!(filter.process_packet(&mut vxlan()));
+        assert!(!filter.process_packet(&mut syn()));
+
+        let mut capture = test_fixtures::capture(PcapHeader::default(), test_fixtures::packets(&[&syn(), &vxlan(), &syn()]));
+        capture.truncate(capture.len() - 10);
+
+        let reader = PcapReader::<_, Packet>::new(Cursor::new(capture)).unwrap();
+        let filtered: Vec<_> = reader.filtered(&filter).collect();
+        assert_eq!(filtered.len(), 2);
+        assert_eq!(filtered[0].as_ref().unwrap().get_header().ts_sec(), 1);
+        assert!(filtered[1].is_err());
+    }
+}
@@ -1,5 +1,8 @@
 pub(crate) mod myerrors;
+// Re-export of the `errors` dependency kept for compatibility, the crate errors are in `myerrors`
+#[allow(unused_imports)]
 pub use errors::*;
+pub use myerrors::*;
 
 pub(crate) mod common;
 pub use common::*;
@@ -7,7 +10,19 @@ pub use common::*;
 pub mod pcap;
 pub use pcap::{PcapReader, PcapParser, PcapWriter};
 
+pub mod dissect;
+pub mod filter;
+
+pub mod pcapng;
+pub use pcapng::{PcapNgReader, PcapNgWriter};
+
+pub(crate) mod capture_reader;
+pub use capture_reader::*;
+
 pub(crate) mod peek_reader;
 
 pub mod pcap_assistant;
 
+#[cfg(test)]
+pub(crate) mod test_fixtures;
+
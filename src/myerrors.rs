@@ -1,5 +1,7 @@
 use thiserror::Error;
 
+use crate::DataLink;
+
 pub(crate) type ResultParsing<T> = Result<T, PcapError>;
 
 #[derive(Error, Debug)]
@@ -39,3 +41,95 @@ impl From< std::string::FromUtf8Error> for PcapError {
     }
 }
 
+
+pub type AssistantResult<T> = Result<T, AssistantError>;
+
+/// Errors returned by the `PcapTester` functions.
+#[derive(Error, Debug)]
+pub enum AssistantError {
+
+    #[error("Can't open file {path}")]
+    FileOpen {
+        path: String,
+        #[source] source: std::io::Error
+    },
+
+    #[error("Can't create file {path}")]
+    FileCreate {
+        path: String,
+        #[source] source: std::io::Error
+    },
+
+    #[error("Invalid capture header in {path}")]
+    HeaderParse {
+        path: String,
+        #[source] source: PcapError
+    },
+
+    #[error("Can't parse packet {index} at byte offset {offset}")]
+    PacketParse {
+        index: usize,
+        offset: u64,
+        #[source] source: PcapError
+    },
+
+    #[error("Can't write to {path}")]
+    WriteFailure {
+        path: String,
+        #[source] source: PcapError
+    },
+
+    #[error("Processor failed on packet {index}: {message}")]
+    ProcessorFailure {
+        index: usize,
+        message: String
+    },
+
+    #[error("Can't parse packet {index} of input {input} at byte offset {offset}")]
+    InputParse {
+        input: usize,
+        index: usize,
+        offset: u64,
+        #[source] source: PcapError
+    },
+
+    #[error("Input {input} has the datalink {found:?} instead of {expected:?}")]
+    DataLinkMismatch {
+        input: usize,
+        expected: DataLink,
+        found: DataLink
+    },
+
+    #[error("Invalid filter '{expression}'")]
+    InvalidFilter {
+        expression: String,
+        #[source] source: FilterError
+    },
+
+    #[error("Invalid scenario {path} at line {line}: {message}")]
+    ScenarioParse {
+        path: String,
+        line: usize,
+        message: String
+    },
+}
+
+
+pub type FilterResult<T> = Result<T, FilterError>;
+
+/// Errors returned when compiling or validating a packet filter.
+#[derive(Error, Debug, Eq, PartialEq)]
+pub enum FilterError {
+
+    #[error("Syntax error at character {position}: {message}")]
+    Syntax {
+        position: usize,
+        message: String
+    },
+
+    #[error("Unsupported filter: {0}")]
+    Unsupported(String),
+
+    #[error("Invalid BPF program: {0}")]
+    InvalidProgram(String),
+}
@@ -0,0 +1,379 @@
+use futures_core::Stream;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use crate::{
+    myerrors::*,
+    pcap::length_policy::LengthPolicy,
+    pcap::myheader::PcapHeader,
+    pcap::myparser::PcapParser,
+    pcap::mywriter::{PacketWriter, PcapWriter},
+    pcap::vpp_packet::*
+};
+
+use std::{
+    future::poll_fn,
+    io,
+    marker::PhantomData,
+    pin::Pin,
+    task::{Context, Poll}
+};
+
+
+// Size of the reads from the underlying reader
+const READ_SIZE: usize = 64 * 1024;
+
+/// Reads a pcap stream from an `AsyncRead`, decoding it with a `PcapParser`.
+///
+/// The reader is a `Stream` of packets, `next` returns the next packet as a future without
+/// the `StreamExt` of another crate.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # async fn read() {
+/// use pcap_assistant::pcap::{AsyncPcapReader, Packet};
+///
+/// let capture: &[u8] = &[];
+/// let mut reader = AsyncPcapReader::<_, Packet>::new(capture).await.unwrap();
+///
+/// while let Some(packet) = reader.next().await {
+///     let packet = packet.unwrap();
+///     // Do something
+/// }
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct AsyncPcapReader<R: AsyncRead + Unpin, P: SomePacket<'static>> {
+    reader: R,
+    parser: PcapParser,
+    buffer: Vec<u8>,
+    start: usize,
+    done: bool,
+    phantom_data: PhantomData<fn() -> P>
+}
+
+impl<R: AsyncRead + Unpin, P: SomePacket<'static>> AsyncPcapReader<R, P> {
+
+    /// Create a new `AsyncPcapReader` and read the pcap header of the stream.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the header is invalid or can't be read.
+    pub async fn new(reader: R) -> ResultParsing<AsyncPcapReader<R, P>> {
+
+        let mut buffer = Vec::new();
+        let mut reader = reader;
+
+        let (header_len, parser) = loop {
+            match PcapParser::new(&buffer) {
+                Ok((rest, parser)) => break (buffer.len() - rest.len(), parser),
+                Err(PcapError::IncompleteBuffer(needed)) => {
+                    if poll_fn(|cx| poll_fill(&mut reader, &mut buffer, needed, cx)).await? == 0 {
+                        return Err(PcapError::IncompleteBuffer(needed));
+                    }
+                },
+                Err(err) => return Err(err)
+            }
+        };
+
+        Ok(AsyncPcapReader { reader, parser, buffer, start: header_len, done: false, phantom_data: PhantomData })
+    }
+
+    pub fn header(&self) -> &PcapHeader {
+        self.parser.header()
+    }
+
+    /// Set the validation of the packet lengths, see `PcapParser::set_length_policy`.
+    pub fn set_length_policy(&mut self, length_policy: LengthPolicy) {
+        self.parser.set_length_policy(length_policy);
+    }
+
+    /// Consumes the `AsyncPcapReader`, returning the wrapped reader.
+    ///
+    /// The bytes read from it but not returned as packets are lost.
+    pub fn into_reader(self) -> R {
+        self.reader
+    }
+
+    /// Returns the next packet, `None` at the end of the stream.
+    ///
+    /// A parsing error, e.g. a truncated last packet, is returned once and ends the stream.
+    pub async fn next(&mut self) -> Option<ResultParsing<P::Item>> {
+        poll_fn(|cx| Pin::new(&mut *self).poll_next(cx)).await
+    }
+
+    /// Attempt to read the next packet, as `Stream::poll_next`.
+    pub fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<ResultParsing<P::Item>>> {
+
+        let this = self.get_mut();
+        if this.done {
+            return Poll::Ready(None);
+        }
+
+        loop {
+            let available = &this.buffer[this.start..];
+            let needed = match this.parser.next_header::<P::Header>(available) {
+                Ok((data, header)) => {
+                    let header_len = available.len() - data.len();
+                    let len = header.incl_len() as usize;
+
+                    if data.len() >= len {
+                        let packet = P::from_parts(header, data[..len].to_vec());
+                        this.consume(header_len + len);
+                        return Poll::Ready(Some(Ok(packet)));
+                    }
+                    len - data.len()
+                },
+                Err(PcapError::IncompleteBuffer(needed)) => needed,
+                Err(err) => {
+                    this.done = true;
+                    return Poll::Ready(Some(Err(err)));
+                }
+            };
+
+            match poll_fill(&mut this.reader, &mut this.buffer, needed, cx) {
+                Poll::Ready(Ok(0)) => {
+                    this.done = true;
+                    return match this.start == this.buffer.len() {
+                        true => Poll::Ready(None),
+                        false => Poll::Ready(Some(Err(PcapError::IncompleteBuffer(needed))))
+                    };
+                },
+                Poll::Ready(Ok(_)) => {},
+                Poll::Ready(Err(err)) => {
+                    this.done = true;
+                    return Poll::Ready(Some(Err(err)));
+                },
+                Poll::Pending => return Poll::Pending
+            }
+        }
+    }
+
+    // Drop the `len` next bytes of the buffer
+    fn consume(&mut self, len: usize) {
+
+        self.start += len;
+        if self.start == self.buffer.len() {
+            self.buffer.clear();
+            self.start = 0;
+        } else if self.start >= READ_SIZE {
+            self.buffer.drain(..self.start);
+            self.start = 0;
+        }
+    }
+}
+
+impl<R: AsyncRead + Unpin, P: SomePacket<'static>> Stream for AsyncPcapReader<R, P> {
+
+    type Item = ResultParsing<P::Item>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        AsyncPcapReader::poll_next(self, cx)
+    }
+}
+
+// Read at least one byte, and up to max(`needed`, READ_SIZE) bytes, at the end of `buffer`.
+// Returns the number of bytes read, 0 at the end of the stream.
+fn poll_fill<R: AsyncRead + Unpin>(reader: &mut R, buffer: &mut Vec<u8>, needed: usize, cx: &mut Context<'_>) -> Poll<ResultParsing<usize>> {
+
+    let len = buffer.len();
+    buffer.resize(len + needed.max(READ_SIZE), 0);
+
+    let mut read_buf = ReadBuf::new(&mut buffer[len..]);
+    let poll = Pin::new(reader).poll_read(cx, &mut read_buf);
+    let filled = read_buf.filled().len();
+    buffer.truncate(len + filled);
+
+    match poll {
+        Poll::Ready(Ok(())) => Poll::Ready(Ok(filled)),
+        Poll::Ready(Err(err)) => Poll::Ready(Err(err.into())),
+        Poll::Pending => Poll::Pending
+    }
+}
+
+/// Writes a pcap stream to an `AsyncWrite`, encoding it with a `PcapWriter`.
+///
+/// Every packet is written with its own writes, wrap the writer in a buffered writer
+/// to group them.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # async fn write() {
+/// use pcap_assistant::pcap::{AsyncPcapWriter, Packet, SomePacket};
+///
+/// let mut writer = AsyncPcapWriter::new(Vec::new()).await.unwrap();
+/// writer.write_packet(Packet::new(0, 0, &[1, 2, 3], 3)).await.unwrap();
+///
+/// let capture = writer.into_writer();
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct AsyncPcapWriter<W: AsyncWrite + Unpin> {
+    encoder: PcapWriter<Vec<u8>>,
+    writer: W
+}
+
+impl<W: AsyncWrite + Unpin> AsyncPcapWriter<W> {
+
+    /// Create a new `AsyncPcapWriter` with a default header in native endianness, as `PcapWriter::new`,
+    /// and write the header.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the writer can't be written to.
+    pub async fn new(writer: W) -> ResultParsing<AsyncPcapWriter<W>> {
+        AsyncPcapWriter::from_encoder(PcapWriter::new(Vec::new())?, writer).await
+    }
+
+    /// Create a new `AsyncPcapWriter` with a user defined header, as `PcapWriter::with_header`,
+    /// and write the header.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the writer can't be written to.
+    pub async fn with_header(header: PcapHeader, writer: W) -> ResultParsing<AsyncPcapWriter<W>> {
+        AsyncPcapWriter::from_encoder(PcapWriter::with_header(header, Vec::new())?, writer).await
+    }
+
+    async fn from_encoder(encoder: PcapWriter<Vec<u8>>, writer: W) -> ResultParsing<AsyncPcapWriter<W>> {
+
+        let mut writer = AsyncPcapWriter { encoder, writer };
+        writer.write_encoded().await?;
+
+        Ok(writer)
+    }
+
+    pub fn header(&self) -> &PcapHeader {
+        &self.encoder.header
+    }
+
+    /// Write a packet, encoded as `PcapWriter::write_packet`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the packet can't be encoded or written.
+    pub async fn write_packet<P>(&mut self, packet: P) -> ResultParsing<()>
+        where PcapWriter<Vec<u8>>: PacketWriter<P>
+    {
+        self.encoder.write_packet(packet)?;
+        self.write_encoded().await
+    }
+
+    /// Flush the underlying writer.
+    pub async fn flush(&mut self) -> ResultParsing<()> {
+
+        poll_fn(|cx| Pin::new(&mut self.writer).poll_flush(cx)).await?;
+
+        Ok(())
+    }
+
+    /// Consumes the `AsyncPcapWriter`, returning the wrapped writer.
+    pub fn into_writer(self) -> W {
+        self.writer
+    }
+
+    // Write the bytes of the encoder to the writer
+    async fn write_encoded(&mut self) -> ResultParsing<()> {
+
+        let (encoder, writer) = (&mut self.encoder, &mut self.writer);
+        let encoded = encoder.get_mut();
+        let mut written = 0;
+
+        while written < encoded.len() {
+            match poll_fn(|cx| Pin::new(&mut *writer).poll_write(cx, &encoded[written..])).await? {
+                0 => return Err(io::Error::from(io::ErrorKind::WriteZero).into()),
+                len => written += len
+            }
+        }
+        encoded.clear();
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pcap::*;
+    use tokio::runtime::Builder;
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        Builder::new_current_thread().build().unwrap().block_on(future)
+    }
+
+    // Reader returning the stream in pieces of `step` bytes, pending before every piece
+    struct Chunked<'a> {
+        data: &'a [u8],
+        step: usize,
+        ready: bool
+    }
+
+    impl AsyncRead for Chunked<'_> {
+        fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+            self.ready = !self.ready;
+            if !self.ready {
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+
+            let len = self.step.min(self.data.len()).min(buf.remaining());
+            let (head, tail) = self.data.split_at(len);
+            buf.put_slice(head);
+            self.data = tail;
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[test]
+    fn async_pcap_test() {
+        let mut header = PcapHeader::default();
+        header.set_endianness(crate::Endianness::Little);
+
+        let capture = block_on(async {
+            let mut writer = AsyncPcapWriter::with_header(header, Vec::new()).await.unwrap();
+            for index in 0..20_u8 {
+                writer.write_packet(Packet::new(index.into(), 0, &vec![index; index as usize], 100)).await.unwrap();
+            }
+            writer.flush().await.unwrap();
+            writer.into_writer()
+        });
+
+        let mut sync_writer = PcapWriter::with_header(header, Vec::new()).unwrap();
+        for index in 0..20_u8 {
+            sync_writer.write_packet(Packet::new(index.into(), 0, &vec![index; index as usize], 100)).unwrap();
+        }
+        assert_eq!(capture, sync_writer.into_writer());
+
+        let packets = block_on(async {
+            let mut reader = AsyncPcapReader::<_, Packet>::new(Chunked { data: &capture, step: 7, ready: false }).await.unwrap();
+            assert_eq!(*reader.header(), header);
+
+            let mut packets = Vec::new();
+            while let Some(packet) = reader.next().await {
+                packets.push(packet.unwrap());
+            }
+            packets
+        });
+        let expected: Vec<_> = PcapReader::<_, Packet>::new(&capture[..]).unwrap().map(Result::unwrap).collect();
+        assert_eq!(packets.len(), expected.len());
+        for (packet, expected) in packets.iter().zip(&expected) {
+            assert_eq!((packet.header, &packet.data), (expected.header, &expected.data));
+        }
+
+        // Truncated last packet
+        let truncated = &capture[..capture.len() - 3];
+        let packets: Vec<_> = block_on(async {
+            let mut reader = AsyncPcapReader::<_, Packet>::new(truncated).await.unwrap();
+            let mut packets = Vec::new();
+            while let Some(packet) = poll_fn(|cx| Stream::poll_next(Pin::new(&mut reader), cx)).await {
+                packets.push(packet);
+            }
+            packets
+        });
+        assert_eq!(packets.len(), 20);
+        assert!(matches!(packets[19], Err(PcapError::IncompleteBuffer(3))));
+
+        assert!(matches!(block_on(AsyncPcapReader::<_, Packet>::new(&capture[..10])), Err(PcapError::IncompleteBuffer(14))));
+    }
+}
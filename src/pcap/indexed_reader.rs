@@ -0,0 +1,437 @@
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::{
+    myerrors::*,
+    pcap::myreader::PcapReader,
+    pcap::vpp_packet::*
+};
+
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    iter::Take,
+    ops::{Bound, Range, RangeBounds},
+    path::{Path, PathBuf},
+    time::Duration
+};
+
+
+/// Position and timestamp of a packet in a pcap stream.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct IndexEntry {
+
+    /// Byte offset of the packet header from the start of the stream
+    pub offset: u64,
+    pub ts_sec: u32,
+    pub ts_nsec: u32
+}
+
+impl IndexEntry {
+
+    pub fn timestamp(&self) -> Duration {
+        Duration::new(self.ts_sec.into(), self.ts_nsec)
+    }
+}
+
+/// Offset table of the packets of a pcap stream, see `IndexedPcapReader`.
+///
+/// It is saved in little endian as the magic `PCAPIDX2`, the length and the fingerprint of the
+/// indexed stream, the number of packets and an `IndexEntry` (offset, ts_sec, ts_nsec) per packet.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct PacketIndex {
+    stream_len: u64,
+    fingerprint: u64,
+    entries: Vec<IndexEntry>
+}
+
+const INDEX_MAGIC: &[u8; 8] = b"PCAPIDX2";
+// ts_sec, ts_nsec, incl_len and orig_len
+const PACKET_HEADER_LEN: usize = 16;
+
+impl PacketIndex {
+
+    /// Build the index of the packets from the current position of the reader to the end of the stream.
+    ///
+    /// The packet headers are read and their data is skipped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a packet header is invalid or if the last packet is truncated.
+    pub fn build<T: Read + Seek, P: SomePacket<'static>>(reader: &mut PcapReader<T, P>) -> ResultParsing<PacketIndex> {
+
+        let start = PcapReader::position(reader);
+        let stream_len = reader.get_mut().seek(SeekFrom::End(0))?;
+        reader.seek_to(start)?;
+
+        let mut entries = Vec::new();
+        let mut offset = start;
+
+        while let Some(header) = reader.skip_packet() {
+            let header = header?;
+            entries.push(IndexEntry { offset, ts_sec: header.ts_sec(), ts_nsec: header.ts_nsec() });
+            offset = PcapReader::position(reader);
+        }
+
+        if offset > stream_len {
+            return Err(PcapError::IncompleteBuffer((offset - stream_len) as usize));
+        }
+
+        let fingerprint = fingerprint(reader.get_mut(), &entries, stream_len)?;
+        reader.seek_to(offset)?;
+
+        Ok(PacketIndex { stream_len, fingerprint, entries })
+    }
+
+    /// Read an index written by `write_to`.
+    pub fn from_reader<R: Read>(reader: &mut R) -> ResultParsing<PacketIndex> {
+
+        let mut magic = [0_u8; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != INDEX_MAGIC {
+            return Err(PcapError::InvalidField("PacketIndex wrong magic number"));
+        }
+
+        let stream_len = reader.read_u64::<LittleEndian>()?;
+        let fingerprint = reader.read_u64::<LittleEndian>()?;
+        let count = reader.read_u64::<LittleEndian>()?;
+
+        let mut entries = Vec::new();
+        for _ in 0..count {
+            entries.push(IndexEntry {
+                offset: reader.read_u64::<LittleEndian>()?,
+                ts_sec: reader.read_u32::<LittleEndian>()?,
+                ts_nsec: reader.read_u32::<LittleEndian>()?
+            });
+        }
+
+        Ok(PacketIndex { stream_len, fingerprint, entries })
+    }
+
+    /// Write the index to a writer.
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> ResultParsing<()> {
+
+        writer.write_all(INDEX_MAGIC)?;
+        writer.write_u64::<LittleEndian>(self.stream_len)?;
+        writer.write_u64::<LittleEndian>(self.fingerprint)?;
+        writer.write_u64::<LittleEndian>(self.entries.len() as u64)?;
+
+        for entry in &self.entries {
+            writer.write_u64::<LittleEndian>(entry.offset)?;
+            writer.write_u32::<LittleEndian>(entry.ts_sec)?;
+            writer.write_u32::<LittleEndian>(entry.ts_nsec)?;
+        }
+
+        Ok(())
+    }
+
+    /// Length of the indexed stream, an index doesn't match a stream of another length.
+    pub fn stream_len(&self) -> u64 {
+        self.stream_len
+    }
+
+    /// Hash of the pcap header, of the first and last packets and of every packet header of the
+    /// indexed stream, an index doesn't match a stream rewritten with the same length.
+    pub fn fingerprint(&self) -> u64 {
+        self.fingerprint
+    }
+
+    pub fn entries(&self) -> &[IndexEntry] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Index of the first packet whose timestamp is not before `timestamp`, `len()` if there is none.
+    ///
+    /// It is a binary search, the packets must be in timestamp order.
+    pub fn find_timestamp(&self, timestamp: Duration) -> usize {
+        self.entries.partition_point(|entry| entry.timestamp() < timestamp)
+    }
+
+    /// Indexes of the packets whose timestamp is in `start..end`, the packets must be in timestamp order.
+    pub fn time_range(&self, start: Duration, end: Duration) -> Range<usize> {
+
+        let start = self.find_timestamp(start);
+
+        start..self.find_timestamp(end).max(start)
+    }
+}
+
+/// `PcapReader` with random access to its packets through a `PacketIndex`.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use std::time::Duration;
+/// use pcap_assistant::pcap::{IndexedPcapReader, Packet};
+///
+/// // Reuses or creates big.pcap.idx
+/// let mut reader = IndexedPcapReader::<_, Packet>::open_with_sidecar("big.pcap").unwrap();
+///
+/// let packet = reader.get(500000).unwrap().unwrap();
+///
+/// let second = reader.index().time_range(Duration::from_secs(1600000000), Duration::from_secs(1600000001));
+/// for packet in reader.range(second).unwrap() {
+///     let packet = packet.unwrap();
+/// }
+/// ```
+#[derive(Debug)]
+pub struct IndexedPcapReader<T: Read + Seek, P: SomePacket<'static>> {
+    reader: PcapReader<T, P>,
+    index: PacketIndex
+}
+
+impl<T: Read + Seek, P: SomePacket<'static>> IndexedPcapReader<T, P> {
+
+    /// Read the pcap header and build the index of the packets.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the stream isn't a valid pcap, see `PacketIndex::build`.
+    pub fn new(reader: T) -> ResultParsing<Self> {
+
+        let mut reader = PcapReader::new(reader)?;
+        let index = PacketIndex::build(&mut reader)?;
+
+        Ok(IndexedPcapReader { reader, index })
+    }
+
+    /// Read the pcap header and use an index built before.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the stream isn't a valid pcap or doesn't have the length and the
+    /// fingerprint of the indexed stream.
+    pub fn with_index(reader: T, index: PacketIndex) -> ResultParsing<Self> {
+
+        let mut reader = PcapReader::new(reader)?;
+
+        let start = reader.position();
+        if reader.get_mut().seek(SeekFrom::End(0))? != index.stream_len {
+            return Err(PcapError::InvalidField("PacketIndex stream length doesn't match"));
+        }
+        if fingerprint(reader.get_mut(), &index.entries, index.stream_len)? != index.fingerprint {
+            return Err(PcapError::InvalidField("PacketIndex stream fingerprint doesn't match"));
+        }
+        reader.seek_to(start)?;
+
+        Ok(IndexedPcapReader { reader, index })
+    }
+
+    pub fn index(&self) -> &PacketIndex {
+        &self.index
+    }
+
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    /// Read the packet `n`, `None` if there are not so many packets.
+    pub fn get(&mut self, n: usize) -> ResultParsing<Option<P::Item>> {
+
+        match self.range(n..n + 1)?.next() {
+            Some(packet) => packet.map(Some),
+            None => Ok(None)
+        }
+    }
+
+    /// Iterate over the packets of the range, limited to the indexed packets.
+    pub fn range<R: RangeBounds<usize>>(&mut self, range: R) -> ResultParsing<Take<&mut PcapReader<T, P>>> {
+
+        let start = match range.start_bound() {
+            Bound::Included(start) => *start,
+            Bound::Excluded(start) => start.saturating_add(1),
+            Bound::Unbounded => 0
+        }.min(self.len());
+
+        let end = match range.end_bound() {
+            Bound::Included(end) => end.saturating_add(1),
+            Bound::Excluded(end) => *end,
+            Bound::Unbounded => self.len()
+        }.clamp(start, self.len());
+
+        let offset = self.index.entries.get(start).map_or(self.index.stream_len, |entry| entry.offset);
+        self.reader.seek_to(offset)?;
+
+        Ok(self.reader.by_ref().take(end - start))
+    }
+
+    /// Consumes the `IndexedPcapReader`, returning the wrapped `PcapReader`.
+    pub fn into_reader(self) -> PcapReader<T, P> {
+        self.reader
+    }
+}
+
+impl<P: SomePacket<'static>> IndexedPcapReader<File, P> {
+
+    /// Open a pcap file with the index saved in its sidecar file `<path>.idx`.
+    ///
+    /// The index is built and saved if the sidecar file is missing, invalid or made for another
+    /// version of the capture, whose length or fingerprint differs.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the capture can't be read or the sidecar file can't be written.
+    pub fn open_with_sidecar<F: AsRef<Path>>(path: F) -> ResultParsing<Self> {
+
+        let path = path.as_ref();
+        let mut sidecar = PathBuf::from(path).into_os_string();
+        sidecar.push(".idx");
+
+        let saved = File::open(&sidecar).ok()
+            .and_then(|file| PacketIndex::from_reader(&mut BufReader::new(file)).ok());
+
+        if let Some(index) = saved {
+            if let Ok(reader) = Self::with_index(File::open(path)?, index) {
+                return Ok(reader);
+            }
+        }
+
+        let reader = Self::new(File::open(path)?)?;
+
+        let mut writer = BufWriter::new(File::create(&sidecar)?);
+        reader.index.write_to(&mut writer)?;
+        writer.flush()?;
+
+        Ok(reader)
+    }
+}
+
+// FNV-1a hash of the pcap header, of the first and last packets of the stream and of the offset and
+// packet header of every entry, read back from the indexed offsets
+fn fingerprint<T: Read + Seek>(stream: &mut T, entries: &[IndexEntry], stream_len: u64) -> ResultParsing<u64> {
+
+    let first_end = entries.get(1).map_or(stream_len, |entry| entry.offset);
+    let last_start = entries.last().map_or(stream_len, |entry| entry.offset).max(first_end);
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let mut add = |bytes: &[u8]| for byte in bytes {
+        hash = (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3);
+    };
+    let mut buffer = [0_u8; 8192];
+
+    for (start, end) in [(0, first_end), (last_start, stream_len)] {
+        stream.seek(SeekFrom::Start(start))?;
+        let mut region = stream.by_ref().take(end.saturating_sub(start));
+
+        loop {
+            let len = region.read(&mut buffer)?;
+            if len == 0 {
+                break;
+            }
+            add(&buffer[..len]);
+        }
+    }
+
+    // Timestamps and lengths of the packet headers, the entries of a stream whose packets moved point elsewhere
+    let mut header = [0_u8; PACKET_HEADER_LEN];
+    for entry in entries {
+        if entry.offset + PACKET_HEADER_LEN as u64 > stream_len {
+            return Err(PcapError::InvalidField("PacketIndex entry out of the stream"));
+        }
+        stream.seek(SeekFrom::Start(entry.offset))?;
+        stream.read_exact(&mut header)?;
+        add(&entry.offset.to_le_bytes());
+        add(&header);
+    }
+
+    Ok(hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pcap::{Packet, PcapHeader};
+    use crate::test_fixtures;
+    use std::{fs, io::Cursor};
+
+    fn capture(count: u32) -> Vec<u8> {
+        let packets = (0..count).map(|index| Packet::new_owned(index / 2, index * 1000, vec![index as u8; index as usize + 1], index + 1));
+        test_fixtures::capture(PcapHeader::default(), packets)
+    }
+
+    #[test]
+    fn indexed_reader_test() {
+        let mut reader = IndexedPcapReader::<_, Packet>::new(Cursor::new(capture(10))).unwrap();
+        assert_eq!(reader.len(), 10);
+        assert_eq!(reader.index().entries()[2], IndexEntry { offset: 24 + 17 + 18, ts_sec: 1, ts_nsec: 2000 });
+
+        assert_eq!(reader.get(7).unwrap().unwrap().data.as_ref(), &[7; 8]);
+        assert_eq!(reader.get(3).unwrap().unwrap().header.orig_len, 4);
+        assert!(reader.get(10).unwrap().is_none());
+
+        let lengths: Vec<usize> = reader.range(8..).unwrap().map(|packet| packet.unwrap().data.len()).collect();
+        assert_eq!(lengths, vec![9, 10]);
+        assert_eq!(reader.range(..=2).unwrap().count(), 3);
+        assert_eq!(reader.range(12..20).unwrap().count(), 0);
+
+        let index = reader.index();
+        assert_eq!(index.find_timestamp(Duration::new(2, 0)), 4);
+        assert_eq!(index.find_timestamp(Duration::new(2, 4001)), 5);
+        assert_eq!(index.find_timestamp(Duration::from_secs(100)), 10);
+        assert_eq!(index.time_range(Duration::from_secs(1), Duration::from_secs(3)), 2..6);
+
+        // Saved index
+        let mut saved = Vec::new();
+        index.write_to(&mut saved).unwrap();
+        let index = PacketIndex::from_reader(&mut &saved[..]).unwrap();
+        assert_eq!(&index, reader.index());
+        let mut reader = IndexedPcapReader::<_, Packet>::with_index(Cursor::new(capture(10)), index.clone()).unwrap();
+        assert_eq!(reader.get(9).unwrap().unwrap().header.ts_sec, 4);
+        assert!(IndexedPcapReader::<_, Packet>::with_index(Cursor::new(capture(9)), index).is_err());
+
+        let mut truncated = capture(3);
+        truncated.pop();
+        assert!(IndexedPcapReader::<_, Packet>::new(Cursor::new(truncated)).is_err());
+    }
+
+    #[test]
+    fn sidecar_index_test() {
+        fs::write("sidecar_test.pcap", capture(4)).unwrap();
+
+        let mut reader = IndexedPcapReader::<_, Packet>::open_with_sidecar("sidecar_test.pcap").unwrap();
+        assert_eq!(reader.get(3).unwrap().unwrap().data.len(), 4);
+        let saved = PacketIndex::from_reader(&mut File::open("sidecar_test.pcap.idx").unwrap()).unwrap();
+        assert_eq!(&saved, reader.index());
+
+        // Stale sidecar file of the previous capture
+        fs::write("sidecar_test.pcap", capture(6)).unwrap();
+        let reader = IndexedPcapReader::<_, Packet>::open_with_sidecar("sidecar_test.pcap").unwrap();
+        assert_eq!(reader.len(), 6);
+        assert_eq!(PacketIndex::from_reader(&mut File::open("sidecar_test.pcap.idx").unwrap()).unwrap().len(), 6);
+
+        // Capture rewritten with the same length, the timestamp of the last packet differs
+        let mut rewritten = capture(6);
+        let last = reader.index().entries()[5].offset as usize;
+        rewritten[last..last + 4].copy_from_slice(&9_u32.to_be_bytes());
+        fs::write("sidecar_test.pcap", &rewritten).unwrap();
+        assert!(IndexedPcapReader::<_, Packet>::with_index(Cursor::new(&rewritten), reader.index().clone()).is_err());
+        let reader = IndexedPcapReader::<_, Packet>::open_with_sidecar("sidecar_test.pcap").unwrap();
+        assert_eq!(reader.index().entries()[5].ts_sec, 9);
+
+        // Middle packets rewritten with other lengths, the first and last packets and the length are the same
+        fs::write("sidecar_test.pcap", capture(6)).unwrap();
+        let reader = IndexedPcapReader::<_, Packet>::open_with_sidecar("sidecar_test.pcap").unwrap();
+        let packets = [1, 2, 4, 3, 5, 6].into_iter().enumerate()
+            .map(|(index, len)| Packet::new_owned(index as u32 / 2, index as u32 * 1000, vec![index as u8; len], len as u32));
+        let shifted = test_fixtures::capture(PcapHeader::default(), packets);
+        let last = reader.index().entries()[5].offset as usize;
+        assert_eq!((&shifted[..41], &shifted[last..]), (&capture(6)[..41], &capture(6)[last..]));
+        fs::write("sidecar_test.pcap", &shifted).unwrap();
+        assert!(IndexedPcapReader::<_, Packet>::with_index(Cursor::new(&shifted), reader.index().clone()).is_err());
+        let mut reader = IndexedPcapReader::<_, Packet>::open_with_sidecar("sidecar_test.pcap").unwrap();
+        assert_eq!(reader.get(3).unwrap().unwrap().data.as_ref(), &[3; 3]);
+
+        fs::remove_file("sidecar_test.pcap").unwrap();
+        fs::remove_file("sidecar_test.pcap.idx").unwrap();
+    }
+}
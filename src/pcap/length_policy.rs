@@ -0,0 +1,122 @@
+use crate::{
+    myerrors::*,
+    pcap::vpp_packet::SomePacketHeader
+};
+
+
+/// Snaplen assumed when the pcap header has none (0), as the maximum snaplen of libpcap.
+pub const DEFAULT_SNAPLEN: u32 = 262_144;
+
+/// Largest `incl_len` accepted by the packet header parsers whatever the `LengthPolicy`, it bounds
+/// the allocation of a packet when the header is garbage.
+pub const MAX_PACKET_LEN: u32 = 0x1000_0000;
+
+/// Validation of the lengths of the packet headers, against the snaplen of the capture.
+///
+/// Packets with `orig_len > incl_len` are valid whatever the policy, they were truncated
+/// by the capture, see `SomePacketHeader::is_truncated`.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum LengthPolicy {
+
+    /// `incl_len` is at most the snaplen of the capture and at most `orig_len`
+    #[default]
+    Strict,
+
+    /// `incl_len` may exceed the snaplen and `orig_len`, as written by some tools for
+    /// offloaded (GSO/TSO) super-frames, up to `MAX_PACKET_LEN`
+    Lenient,
+
+    /// `incl_len` is at most the given bound, whatever the snaplen, and at most `orig_len`
+    Max(u32)
+}
+
+impl LengthPolicy {
+
+    /// Check the lengths of `header`, read from a capture whose header has the given snaplen.
+    ///
+    /// # Errors
+    ///
+    /// Returns `PcapError::InvalidField` if a length isn't allowed by the policy.
+    pub fn check<H: SomePacketHeader>(&self, header: &H, snaplen: u32) -> ResultParsing<()> {
+
+        let incl_len = header.incl_len();
+        let (max_len, message) = match *self {
+            LengthPolicy::Strict => (effective_snaplen(snaplen), "PacketHeader incl_len > snaplen"),
+            LengthPolicy::Lenient => (MAX_PACKET_LEN, "PacketHeader incl_len > maximum packet length"),
+            LengthPolicy::Max(bound) => (bound, "PacketHeader incl_len > maximum packet length")
+        };
+
+        if incl_len > max_len {
+            return Err(PcapError::InvalidField(message));
+        }
+
+        if *self != LengthPolicy::Lenient && incl_len > header.orig_len() {
+            return Err(PcapError::InvalidField("PacketHeader incl_len > orig_len"));
+        }
+
+        Ok(())
+    }
+}
+
+// Snaplen of a capture, `DEFAULT_SNAPLEN` when the header has none
+fn effective_snaplen(snaplen: u32) -> u32 {
+
+    match snaplen {
+        0 => DEFAULT_SNAPLEN,
+        snaplen => snaplen
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pcap::*;
+    use crate::test_fixtures;
+    use crate::TsResolution;
+    use byteorder::LittleEndian;
+    use std::io::Cursor;
+
+    #[test]
+    fn length_policy_test() {
+        let jumbo = PacketHeader::new(0, 0, 9000, 9000);
+        let super_frame = VppPacketHeader::new(0, 0, 70_000, 64_000);
+        let truncated = PacketHeader::new(0, 0, 128, 100_000);
+
+        assert!(LengthPolicy::Strict.check(&jumbo, 9216).is_ok());
+        assert!(matches!(LengthPolicy::Strict.check(&jumbo, 1500), Err(PcapError::InvalidField("PacketHeader incl_len > snaplen"))));
+        assert!(LengthPolicy::Strict.check(&jumbo, 0).is_ok());
+        assert!(matches!(LengthPolicy::Strict.check(&super_frame, 0), Err(PcapError::InvalidField("PacketHeader incl_len > orig_len"))));
+        assert!(LengthPolicy::Lenient.check(&super_frame, 1500).is_ok());
+        assert!(LengthPolicy::Lenient.check(&PacketHeader::new(0, 0, MAX_PACKET_LEN + 1, 0), 0).is_err());
+        assert!(LengthPolicy::Max(10_000).check(&jumbo, 1500).is_ok());
+        assert!(LengthPolicy::Max(8000).check(&jumbo, 65535).is_err());
+
+        // Garbage lengths are rejected by the header parsers before any allocation
+        let mut garbage = vec![0; 20];
+        garbage[8..12].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(Packet::from_slice::<LittleEndian>(&garbage, TsResolution::MicroSecond), Err(PcapError::InvalidField("PacketHeader incl_len > maximum packet length"))));
+        assert!(matches!(Packet::from_reader::<_, LittleEndian>(&mut garbage.as_slice(), TsResolution::MicroSecond), Err(PcapError::InvalidField(_))));
+        assert!(matches!(VppPacket::from_reader::<_, LittleEndian>(&mut garbage.as_slice(), TsResolution::NanoSecond), Err(PcapError::InvalidField(_))));
+
+        assert!(LengthPolicy::Strict.check(&truncated, 65535).is_ok());
+        assert!(truncated.is_truncated() && !jumbo.is_truncated());
+        assert_eq!((truncated.truncated_len(), super_frame.truncated_len()), (99_872, 0));
+
+        // A jumbo frame above the former 0xFFFF limit is read with a matching snaplen
+        let header = PcapHeader { snaplen: 0x20000, ..Default::default() };
+        let capture = test_fixtures::capture(header, [Packet::new_owned(0, 0, vec![1; 0x10000], 0x18000), Packet::new_owned(1, 0, vec![2; 4], 4)]);
+
+        let packets: Vec<_> = PcapReader::<_, Packet>::new(Cursor::new(&capture)).unwrap().collect::<ResultParsing<_>>().unwrap();
+        assert_eq!(packets.len(), 2);
+        assert!(packets[0].header.is_truncated() && packets[0].data.len() == 0x10000);
+
+        let mut reader = PcapReader::<_, Packet>::new(Cursor::new(&capture)).unwrap();
+        reader.set_length_policy(LengthPolicy::Max(1500));
+        assert!(matches!(reader.next(), Some(Err(PcapError::InvalidField(_)))));
+
+        let (rest, mut parser) = PcapParser::new(&capture).unwrap();
+        assert!(parser.next_packet::<Packet>(rest).is_ok());
+        parser.set_length_policy(LengthPolicy::Max(1500));
+        assert!(parser.next_packet::<Packet>(rest).is_err());
+    }
+}
@@ -0,0 +1,196 @@
+use memmap2::Mmap;
+
+use crate::{
+    myerrors::*,
+    pcap::length_policy::LengthPolicy,
+    pcap::myheader::PcapHeader,
+    pcap::mypacket::Packet,
+    pcap::myparser::PcapParser,
+    pcap::vpp_packet::*
+};
+
+use std::{fs::File, marker::PhantomData, path::Path};
+
+
+/// Pcap file mapped in memory, its packets borrow their data from the mapping.
+///
+/// Unlike `PcapReader`, no buffer is allocated per packet: `packets` yields `Packet<'a>`
+/// or `VppPacket<'a>` whose data is a `Cow::Borrowed` slice of the file.
+///
+/// The file must not be modified or truncated while it is mapped.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use pcap_assistant::pcap::{MmapPcapReader, Packet};
+///
+/// let reader = MmapPcapReader::open("test.pcap").unwrap();
+///
+/// for packet in reader.packets::<Packet>() {
+///     let packet = packet.unwrap();
+///     // packet.data borrows from the mapped file
+/// }
+/// ```
+#[derive(Debug)]
+pub struct MmapPcapReader {
+    mmap: Mmap,
+    parser: PcapParser,
+    start: usize
+}
+
+impl MmapPcapReader {
+
+    /// Map the file at `path` and parse its pcap header.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be opened or mapped, or if its header is invalid.
+    pub fn open<Pa: AsRef<Path>>(path: Pa) -> ResultParsing<MmapPcapReader> {
+        MmapPcapReader::new(&File::open(path)?)
+    }
+
+    /// Map `file` and parse its pcap header.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be mapped or if its header is invalid.
+    pub fn new(file: &File) -> ResultParsing<MmapPcapReader> {
+
+        // Safety: the mapping is read only, the caller must not modify the file while it's mapped
+        let mmap = unsafe { Mmap::map(file)? };
+        let (rest, parser) = PcapParser::new(&mmap)?;
+        let start = mmap.len() - rest.len();
+
+        Ok(MmapPcapReader { mmap, parser, start })
+    }
+
+    pub fn header(&self) -> &PcapHeader {
+        self.parser.header()
+    }
+
+    /// Set the validation of the packet lengths, see `PcapParser::set_length_policy`.
+    pub fn set_length_policy(&mut self, length_policy: LengthPolicy) {
+        self.parser.set_length_policy(length_policy);
+    }
+
+    /// Bytes of the mapped file, including its pcap header.
+    pub fn as_slice(&self) -> &[u8] {
+        &self.mmap
+    }
+
+    /// Iterator over the packets of the file, borrowing from the mapping.
+    pub fn packets<'a, P: SomePacket<'a>>(&'a self) -> MmapPackets<'a, P> {
+        MmapPackets {
+            parser: &self.parser,
+            data: &self.mmap,
+            position: self.start,
+            done: false,
+            phantom_data: PhantomData
+        }
+    }
+}
+
+/// Iterator over the packets of a `MmapPcapReader`, see `MmapPcapReader::packets`.
+///
+/// A parsing error, e.g. a truncated last packet, is yielded once and ends the iteration.
+#[derive(Debug)]
+pub struct MmapPackets<'a, P: SomePacket<'a>> {
+    parser: &'a PcapParser,
+    data: &'a [u8],
+    position: usize,
+    done: bool,
+    phantom_data: PhantomData<P>
+}
+
+impl<'a, P: SomePacket<'a>> MmapPackets<'a, P> {
+
+    /// Byte offset of the next packet from the start of the file.
+    pub fn position(&self) -> u64 {
+        self.position as u64
+    }
+}
+
+impl<'a, P: SomePacket<'a>> Iterator for MmapPackets<'a, P> {
+
+    type Item = ResultParsing<P::Item>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+
+        let remainder = &self.data[self.position..];
+        if self.done || remainder.is_empty() {
+            return None;
+        }
+
+        match self.parser.next_packet::<P>(remainder) {
+            Ok((rest, packet)) => {
+                self.position = self.data.len() - rest.len();
+                Some(Ok(packet))
+            },
+            Err(err) => {
+                self.done = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+/// Packet type which can borrow its data for any lifetime, to name it without the lifetime
+/// of a mapping, e.g. `compare_files_mapped::<Packet, VppPacket>`.
+pub trait BorrowedPacket {
+    type Borrowed<'a>: SomePacket<'a, Item = Self::Borrowed<'a>>;
+}
+
+impl BorrowedPacket for Packet<'_> {
+    type Borrowed<'a> = Packet<'a>;
+}
+
+impl BorrowedPacket for VppPacket<'_> {
+    type Borrowed<'a> = VppPacket<'a>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pcap::*;
+    use crate::test_fixtures;
+    use std::borrow::Cow;
+    use std::fs;
+
+    #[test]
+    fn mmap_reader_test() {
+        let path = "mmap_reader_test.pcap";
+        let mut bytes = test_fixtures::capture(PcapHeader::default(), test_fixtures::packets(&[&[0; 4], &[1; 4], &[2; 4]]));
+        fs::write(path, &bytes).unwrap();
+
+        let reader = MmapPcapReader::open(path).unwrap();
+        assert_eq!(*reader.header(), PcapHeader::from_slice(&bytes).unwrap().1);
+
+        let mut packets = reader.packets::<Packet>();
+        let first = packets.next().unwrap().unwrap();
+        assert!(matches!(first.data, Cow::Borrowed(_)));
+        assert_eq!((first.header.ts_sec, first.data.as_ref()), (0, &[0; 4][..]));
+        assert_eq!(packets.position(), 44);
+        assert_eq!(packets.map(|packet| packet.unwrap().header.ts_sec).collect::<Vec<_>>(), vec![1, 2]);
+
+        // Truncated last packet
+        bytes.truncate(bytes.len() - 1);
+        fs::write(path, &bytes).unwrap();
+        let reader = MmapPcapReader::open(path).unwrap();
+        let packets: Vec<_> = reader.packets::<Packet>().collect();
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(packets.len(), 3);
+        assert!(matches!(packets[2], Err(PcapError::IncompleteBuffer(1))));
+
+        let vpp_path = "mmap_reader_vpp_test.pcap";
+        let mut packet = VppPacket::new(1, 0, &[1, 2], 2);
+        packet.header.interface_index = 5;
+        fs::write(vpp_path, test_fixtures::capture(PcapHeader::default(), [packet])).unwrap();
+
+        let reader = MmapPcapReader::open(vpp_path).unwrap();
+        let packets: Vec<_> = reader.packets::<VppPacket>().collect::<ResultParsing<_>>().unwrap();
+        fs::remove_file(vpp_path).unwrap();
+
+        assert_eq!((packets.len(), packets[0].header.interface_index, packets[0].data.as_ref()), (1, 5, &[1, 2][..]));
+    }
+}
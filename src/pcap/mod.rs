@@ -4,10 +4,22 @@ mod myreader;
 mod mywriter;
 mod myparser;
 mod vpp_packet;
+mod indexed_reader;
+mod length_policy;
+mod mmap_reader;
+mod recovering_reader;
+#[cfg(feature = "tokio")]
+mod async_io;
 
 pub use myheader::*;
 pub use mypacket::*;
 pub use myparser::*;
 pub use myreader::*;
 pub use mywriter::*;
-pub use vpp_packet::*;
\ No newline at end of file
+pub use vpp_packet::*;
+pub use indexed_reader::*;
+pub use length_policy::*;
+pub use mmap_reader::*;
+pub use recovering_reader::*;
+#[cfg(feature = "tokio")]
+pub use async_io::*;
\ No newline at end of file
@@ -40,6 +40,22 @@ impl SomePacketHeader for  PacketHeader {
         self.orig_len = orig_len;
     }
 
+    fn ts_sec(&self) -> u32 {
+        self.ts_sec
+    }
+
+    fn ts_nsec(&self) -> u32 {
+        self.ts_nsec
+    }
+
+    fn incl_len(&self) -> u32 {
+        self.incl_len
+    }
+
+    fn orig_len(&self) -> u32 {
+        self.orig_len
+    }
+
     /// Create a new `PacketHeader` with the given parameters.
     fn new(ts_sec: u32, ts_nsec: u32, incl_len:u32, orig_len:u32) -> PacketHeader {
 
@@ -57,24 +73,15 @@ impl SomePacketHeader for  PacketHeader {
         let ts_sec = reader.read_u32::<B>()?;
         let mut ts_nsec = reader.read_u32::<B>()?;
         if ts_resolution == TsResolution::MicroSecond {
-            ts_nsec *= 1000;
+            ts_nsec = ts_nsec.checked_mul(1000).ok_or(PcapError::InvalidField("PacketHeader ts_usec > 0xFFFFFFFF ns"))?;
         }
         let incl_len = reader.read_u32::<B>()?;
         let orig_len = reader.read_u32::<B>()?;
-
-        if incl_len > 0xFFFF {
-            return Err(PcapError::InvalidField("PacketHeader incl_len > 0xFFFF"));
-        }
-
-        if orig_len > 0xFFFF {
-            return Err(PcapError::InvalidField("PacketHeader orig_len > 0xFFFF"));
-        }
-
-        if incl_len > orig_len {
-            return Err(PcapError::InvalidField("PacketHeader incl_len > orig_len"));
+        // Hard bound on the allocation of the packet data, whatever the `LengthPolicy`
+        if incl_len > MAX_PACKET_LEN {
+            return Err(PcapError::InvalidField("PacketHeader incl_len > maximum packet length"));
         }
 
-
         Ok(
             PacketHeader {
 
@@ -188,6 +195,14 @@ impl<'a> SomePacket<'a> for Packet<'a> {
         }
     }
 
+    /// Create a new owned `Packet` from a header and its payload.
+    fn from_parts(header: PacketHeader, data: Vec<u8>) -> Self::Item {
+        Packet {
+            header,
+            data: Cow::Owned(data)
+        }
+    }
+
     /// Create a new owned `Packet` from a reader.
     fn from_reader<R: Read, B: ByteOrder>(reader: &mut R, ts_resolution: TsResolution) -> ResultParsing<Self::Item> {
 
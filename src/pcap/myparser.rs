@@ -3,8 +3,9 @@ use byteorder::{BigEndian, LittleEndian};
 use crate::{
     Endianness,
     myerrors::*,
-    pcap::vpp_packet::SomePacket,
+    pcap::vpp_packet::{SomePacket, SomePacketHeader},
     //pcap::Packet,
+    pcap::LengthPolicy,
     pcap::PcapHeader
 };
 
@@ -45,7 +46,8 @@ use crate::{
 /// ```
 #[derive(Debug)]
 pub struct PcapParser {
-    header: PcapHeader
+    header: PcapHeader,
+    length_policy: LengthPolicy
 }
 
 impl PcapParser {
@@ -57,15 +59,31 @@ impl PcapParser {
         let (slice, header) = PcapHeader::from_slice(slice)?;
 
         let parser = PcapParser {
-            header
+            header,
+            length_policy: LengthPolicy::default()
         };
 
         Ok((slice, parser))
     }
 
+    /// Header of the parsed stream.
+    pub fn header(&self) -> &PcapHeader {
+        &self.header
+    }
+
+    /// Validation of the packet lengths against the snaplen, `LengthPolicy::Strict` by default.
+    pub fn length_policy(&self) -> LengthPolicy {
+        self.length_policy
+    }
+
+    pub fn set_length_policy(&mut self, length_policy: LengthPolicy) {
+        self.length_policy = length_policy;
+    }
+
     /// Returns the next packet and the remainder.
     pub fn next_packet<'a, P: SomePacket<'a>>(&self, slice: &'a[u8]) -> ResultParsing<(&'a [u8], P::Item)> {
 
+        self.next_header::<P::Header>(slice)?;
         let ts_resolution = self.header.ts_resolution();
 
         match self.header.endianness() {
@@ -73,4 +91,20 @@ impl PcapParser {
             Endianness::Little => P::from_slice::<LittleEndian>(slice, ts_resolution)
         }
     }
-}
\ No newline at end of file
+
+    /// Returns the header of the next packet and the remainder, starting with the packet data.
+    ///
+    /// The lengths of the header are checked with the `LengthPolicy` of the parser.
+    pub fn next_header<'a, H: SomePacketHeader>(&self, slice: &'a [u8]) -> ResultParsing<(&'a [u8], H)> {
+
+        let ts_resolution = self.header.ts_resolution();
+
+        let (slice, header) = match self.header.endianness() {
+            Endianness::Big => H::from_slice::<BigEndian>(slice, ts_resolution)?,
+            Endianness::Little => H::from_slice::<LittleEndian>(slice, ts_resolution)?
+        };
+        self.length_policy.check(&header, self.header.snaplen)?;
+
+        Ok((slice, header))
+    }
+}
@@ -4,12 +4,13 @@ use byteorder::{BigEndian, LittleEndian};
 use crate::{
     Endianness,
     myerrors::*,
+    pcap::length_policy::LengthPolicy,
     pcap::myheader::PcapHeader,
     pcap::vpp_packet::*,
     peek_reader::PeekReader
 };
 
-use std::{io::Read, marker::PhantomData};
+use std::{io::{Read, Seek, SeekFrom}, marker::PhantomData};
 
 
 /// Wraps another reader and uses it to read a Pcap formated stream.
@@ -39,7 +40,8 @@ pub struct PcapReader<T:Read, P: SomePacket<'static>> {
 
     phantom_data: PhantomData<P>,
     pub header: PcapHeader,
-    reader: PeekReader<T>
+    reader: PeekReader<T>,
+    length_policy: LengthPolicy
 }
 
 impl <T:Read, P: SomePacket<'static>> PcapReader<T, P>{
@@ -61,18 +63,35 @@ impl <T:Read, P: SomePacket<'static>> PcapReader<T, P>{
     /// let file_in = File::open("test.pcap").expect("Error opening file");
     /// let pcap_reader = PcapReader::new(file_in).unwrap();
     /// ```
-    pub fn new(mut reader:T) -> ResultParsing<Self> {
+    pub fn new(reader:T) -> ResultParsing<Self> {
+
+        let mut reader = PeekReader::new(reader);
 
         Ok(
             Self {
 
                 phantom_data: Default::default(),
                 header : PcapHeader::from_reader(&mut reader)?,
-                reader : PeekReader::new(reader)
+                reader,
+                length_policy: LengthPolicy::default()
             }
         )
     }
 
+    /// Byte offset of the next packet from the start of the stream.
+    pub fn position(&self) -> u64 {
+        self.reader.position
+    }
+
+    /// Validation of the packet lengths against the snaplen, `LengthPolicy::Strict` by default.
+    pub fn length_policy(&self) -> LengthPolicy {
+        self.length_policy
+    }
+
+    pub fn set_length_policy(&mut self, length_policy: LengthPolicy) {
+        self.length_policy = length_policy;
+    }
+
     /// Consumes the `PcapReader`, returning the wrapped reader.
     pub fn into_reader(self) -> T{
         self.reader.inner
@@ -92,6 +111,47 @@ impl <T:Read, P: SomePacket<'static>> PcapReader<T, P>{
         &mut self.reader.inner
     }
 
+    // Read the header of the next packet and check its lengths
+    fn read_header(&mut self) -> ResultParsing<P::Header> {
+
+        let ts_resolution = self.header.ts_resolution();
+
+        let header = match self.header.endianness() {
+            Endianness::Big => P::Header::from_reader::<_, BigEndian>(&mut self.reader, ts_resolution)?,
+            Endianness::Little => P::Header::from_reader::<_, LittleEndian>(&mut self.reader, ts_resolution)?
+        };
+        self.length_policy.check(&header, self.header.snaplen)?;
+
+        Ok(header)
+    }
+}
+
+impl <T: Read + Seek, P: SomePacket<'static>> PcapReader<T, P> {
+
+    /// Move to the packet starting at `offset` bytes from the start of the stream, as given by `position`.
+    pub fn seek_to(&mut self, offset: u64) -> ResultParsing<()> {
+
+        self.reader.seek(SeekFrom::Start(offset))?;
+
+        Ok(())
+    }
+
+    /// Read the header of the next packet and skip its data.
+    ///
+    /// The data isn't read, a truncated last packet isn't detected.
+    pub fn skip_packet(&mut self) -> Option<ResultParsing<P::Header>> {
+
+        match self.reader.is_empty() {
+            Ok(true) => return None,
+            Err(err) => return Some(Err(err.into())),
+            _ => {}
+        }
+
+        Some(self.read_header().and_then(|header| {
+            self.reader.seek(SeekFrom::Current(header.incl_len().into()))?;
+            Ok(header)
+        }))
+    }
 }
 
 
@@ -109,14 +169,12 @@ impl <T: Read, P: SomePacket<'static>> Iterator for PcapReader<T, P> {
             _ => {}
         }
 
-        let ts_resolution = self.header.ts_resolution();
+        Some(self.read_header().and_then(|header| {
+            let mut data = vec![0_u8; header.incl_len() as usize];
+            self.reader.read_exact(&mut data)?;
 
-        Some(
-            match self.header.endianness() {
-                Endianness::Big => P::from_reader::<_, BigEndian>(&mut self.reader, ts_resolution),
-                Endianness::Little => P::from_reader::<_, LittleEndian>(&mut self.reader, ts_resolution)
-            }
-        )
+            Ok(P::from_parts(header, data))
+        }))
     }
 
 }
@@ -252,6 +252,12 @@ impl<W: Write> PcapWriter<W> {
         )
     }
 
+    /// Create a new `PcapWriter` which writes packets after the `header` already in the writer,
+    /// e.g. to append packets to an existing file opened in append mode.
+    pub fn without_header(header: PcapHeader, writer: W) -> PcapWriter<W> {
+        PcapWriter { header, writer }
+    }
+
     /// Consumes the `PcapWriter`, returning the wrapped writer.
     pub fn into_writer(self) -> W {
         self.writer
@@ -0,0 +1,239 @@
+use crate::{
+    myerrors::*,
+    pcap::myheader::PcapHeader,
+    pcap::myparser::PcapParser,
+    pcap::vpp_packet::*
+};
+
+use std::marker::PhantomData;
+
+
+/// Largest difference, in seconds, between the timestamps of consecutive packets
+/// for a packet header to be plausible when resynchronizing.
+pub const MAX_TIMESTAMP_GAP: u32 = 24 * 3600;
+
+/// Why a region of a capture was skipped by a `RecoveringReader`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SkipReason {
+
+    /// Bytes which don't start a plausible packet, skipped up to the next plausible packet header
+    CorruptData,
+
+    /// Last packet of the stream, cut before the end of its data
+    TruncatedPacket,
+
+    /// Last bytes of the stream, too short for a packet header
+    TruncatedHeader
+}
+
+/// Region of a capture skipped by a `RecoveringReader`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct SkippedRegion {
+
+    /// Byte offset of the region from the start of the stream
+    pub offset: u64,
+    pub len: u64,
+    pub reason: SkipReason
+}
+
+/// Reader of a corrupted or truncated pcap stream, which skips the packets it can't read.
+///
+/// A packet is read if its header is valid for the `LengthPolicy` of the parser, its timestamp is
+/// within `MAX_TIMESTAMP_GAP` of the previous packet and its data is complete. Otherwise the reader
+/// scans the next bytes for such a packet which is also followed by another plausible header or by
+/// the end of the stream, and the bytes in between are reported by `skipped`.
+///
+/// It yields packets borrowing from the stream, which can be a mapped file, see `MmapPcapReader::as_slice`.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use pcap_assistant::pcap::{MmapPcapReader, Packet, RecoveringReader};
+///
+/// let file = MmapPcapReader::open("crashed.pcap").unwrap();
+/// let mut reader = RecoveringReader::<Packet>::new(file.as_slice()).unwrap();
+///
+/// for packet in reader.by_ref() {
+///     // Do something
+/// }
+///
+/// for region in reader.skipped() {
+///     println!("{:?}", region);
+/// }
+/// ```
+#[derive(Debug)]
+pub struct RecoveringReader<'a, P: SomePacket<'a>> {
+    parser: PcapParser,
+    data: &'a [u8],
+    position: usize,
+    last_ts_sec: Option<u32>,
+    skipped: Vec<SkippedRegion>,
+    phantom_data: PhantomData<P>
+}
+
+impl<'a, P: SomePacket<'a>> RecoveringReader<'a, P> {
+
+    /// Create a new `RecoveringReader` of a pcap stream and parse its header.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the pcap header is invalid, nothing can be recovered without it.
+    pub fn new(data: &'a [u8]) -> ResultParsing<RecoveringReader<'a, P>> {
+
+        let (rest, parser) = PcapParser::new(data)?;
+
+        Ok(RecoveringReader {
+            parser,
+            data,
+            position: data.len() - rest.len(),
+            last_ts_sec: None,
+            skipped: Vec::new(),
+            phantom_data: PhantomData
+        })
+    }
+
+    pub fn header(&self) -> &PcapHeader {
+        self.parser.header()
+    }
+
+    /// Parser of the packets, whose `LengthPolicy` can be changed.
+    pub fn parser_mut(&mut self) -> &mut PcapParser {
+        &mut self.parser
+    }
+
+    /// Byte offset of the next packet from the start of the stream.
+    pub fn position(&self) -> u64 {
+        self.position as u64
+    }
+
+    /// Regions skipped so far, in the order of the stream.
+    pub fn skipped(&self) -> &[SkippedRegion] {
+        &self.skipped
+    }
+
+    // End and timestamp of the packet starting at `offset` if it's plausible, and followed by a plausible header if `chained`
+    fn packet_end(&self, offset: usize, chained: bool) -> Option<(usize, u32)> {
+
+        let slice = &self.data[offset..];
+        let (data, header) = self.parser.next_header::<P::Header>(slice).ok()?;
+        if !plausible(&header, self.last_ts_sec) {
+            return None;
+        }
+
+        let end = offset + slice.len() - data.len() + header.incl_len() as usize;
+        if end > self.data.len() {
+            return None;
+        }
+        if !chained {
+            return Some((end, header.ts_sec()));
+        }
+
+        match self.parser.next_header::<P::Header>(&self.data[end..]) {
+            Ok((_, next)) if plausible(&next, Some(header.ts_sec())) => Some((end, header.ts_sec())),
+            Err(PcapError::IncompleteBuffer(_)) => Some((end, header.ts_sec())),
+            _ => None
+        }
+    }
+
+    // Reason to skip the end of the stream from `offset`
+    fn tail_reason(&self, offset: usize) -> SkipReason {
+
+        match self.parser.next_header::<P::Header>(&self.data[offset..]) {
+            Err(PcapError::IncompleteBuffer(_)) => SkipReason::TruncatedHeader,
+            Ok((_, header)) if plausible(&header, self.last_ts_sec) => SkipReason::TruncatedPacket,
+            _ => SkipReason::CorruptData
+        }
+    }
+
+    fn skip(&mut self, end: usize, reason: SkipReason) {
+
+        self.skipped.push(SkippedRegion { offset: self.position as u64, len: (end - self.position) as u64, reason });
+        self.position = end;
+    }
+}
+
+impl<'a, P: SomePacket<'a>> Iterator for RecoveringReader<'a, P> {
+
+    type Item = P::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+
+        while self.position < self.data.len() {
+            if let Some((end, ts_sec)) = self.packet_end(self.position, false) {
+                let (_, packet) = self.parser.next_packet::<P>(&self.data[self.position..end]).ok()?;
+
+                self.last_ts_sec = Some(ts_sec);
+                self.position = end;
+                return Some(packet);
+            }
+
+            match (self.position + 1..self.data.len()).find(|offset| self.packet_end(*offset, true).is_some()) {
+                Some(offset) => self.skip(offset, SkipReason::CorruptData),
+                None => {
+                    let reason = self.tail_reason(self.position);
+                    self.skip(self.data.len(), reason);
+                }
+            }
+        }
+
+        None
+    }
+}
+
+// Timestamp of the header valid and close to the previous one
+fn plausible<H: SomePacketHeader>(header: &H, last_ts_sec: Option<u32>) -> bool {
+    header.ts_nsec() < 1_000_000_000 && last_ts_sec.is_none_or(|last| last.abs_diff(header.ts_sec()) <= MAX_TIMESTAMP_GAP)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pcap::*;
+    use crate::test_fixtures;
+
+    const TS: u32 = 1_700_000_000;
+
+    fn capture(count: u32) -> Vec<u8> {
+        let packets = (0..count).map(|index| Packet::new_owned(TS + index, 0, vec![index as u8; 20], 20));
+        test_fixtures::capture(PcapHeader { snaplen: 1500, ..Default::default() }, packets)
+    }
+
+    fn read(data: &[u8]) -> (Vec<u32>, Vec<SkippedRegion>) {
+        let mut reader = RecoveringReader::<Packet>::new(data).unwrap();
+        let packets = reader.by_ref().map(|packet| packet.header.ts_sec - TS).collect();
+        (packets, reader.skipped().to_vec())
+    }
+
+    #[test]
+    fn recovering_reader_test() {
+        // Packets of 36 bytes after the header of 24 bytes
+        let clean = capture(5);
+        assert_eq!(read(&clean), (vec![0, 1, 2, 3, 4], vec![]));
+
+        // Truncated last packet
+        let (packets, skipped) = read(&clean[..clean.len() - 5]);
+        assert_eq!(packets, vec![0, 1, 2, 3]);
+        assert_eq!(skipped, vec![SkippedRegion { offset: 168, len: 31, reason: SkipReason::TruncatedPacket }]);
+
+        let (packets, skipped) = read(&clean[..24 + 36 + 10]);
+        assert_eq!(packets, vec![0]);
+        assert_eq!(skipped, vec![SkippedRegion { offset: 60, len: 10, reason: SkipReason::TruncatedHeader }]);
+
+        // Corrupt header of the third packet, incl_len > snaplen
+        let mut corrupt = clean.clone();
+        corrupt[24 + 2 * 36 + 8..24 + 2 * 36 + 12].copy_from_slice(&0xFFFF_u32.to_be_bytes());
+        let (packets, skipped) = read(&corrupt);
+        assert_eq!(packets, vec![0, 1, 3, 4]);
+        assert_eq!(skipped, vec![SkippedRegion { offset: 96, len: 36, reason: SkipReason::CorruptData }]);
+
+        // Garbage inserted between packets, and a header cut by a crash
+        let mut garbage = clean[..60].to_vec();
+        garbage.extend_from_slice(&[0xAB; 13]);
+        garbage.extend_from_slice(&clean[60..]);
+        let (packets, skipped) = read(&garbage);
+        assert_eq!(packets, vec![0, 1, 2, 3, 4]);
+        assert_eq!(skipped, vec![SkippedRegion { offset: 60, len: 13, reason: SkipReason::CorruptData }]);
+
+        assert!(RecoveringReader::<Packet>::new(&clean[..10]).is_err());
+    }
+}
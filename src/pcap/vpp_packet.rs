@@ -2,8 +2,10 @@ use byteorder::{ByteOrder, ReadBytesExt, WriteBytesExt};
 
 use crate::{
     myerrors::*,
+    DataLink,
     TsResolution,
-    pcap::{Packet, PacketHeader}
+    dissect::{dissect, Dissection},
+    pcap::{Packet, PacketHeader, MAX_PACKET_LEN}
 };
  
 use std::{
@@ -24,6 +26,28 @@ pub trait SomePacketHeader: Sized {
     fn timestamp(&self) -> Duration;
     fn set_orig_len(&mut self, orig_len: u32);
     fn set_incl_len(&mut self, incl_len: u32);
+    fn ts_sec(&self) -> u32;
+    fn ts_nsec(&self) -> u32;
+    fn incl_len(&self) -> u32;
+    fn orig_len(&self) -> u32;
+
+    /// True if the packet was truncated by the capture, `orig_len > incl_len`.
+    fn is_truncated(&self) -> bool {
+        self.orig_len() > self.incl_len()
+    }
+
+    /// Number of bytes of the packet missing from the capture.
+    fn truncated_len(&self) -> u32 {
+        self.orig_len().saturating_sub(self.incl_len())
+    }
+
+    /// Index of the interface the packet was captured on, if the format records it.
+    fn interface_index(&self) -> Option<u32> {
+        None
+    }
+
+    /// Set the interface index, ignored by formats that don't record it.
+    fn set_interface_index(&mut self, _interface_index: u32) {}
     
 }
 
@@ -32,16 +56,22 @@ pub trait SomePacketHeader: Sized {
 pub trait SomePacket<'a> {
 
     type Item;
-    type Header;
+    type Header: SomePacketHeader;
 
     fn new(ts_sec: u32, ts_nsec: u32, data: &'a [u8], orig_len: u32) -> Self;
     fn new_with_params(&self, header: Self::Header, data: Vec<u8>) -> Self;
     fn new_owned(ts_sec: u32, ts_nsec: u32, data: Vec<u8>, orig_len: u32) -> Self::Item;
+    fn from_parts(header: Self::Header, data: Vec<u8>) -> Self::Item;
     fn from_reader<R: Read, B: ByteOrder>(reader: &mut R, ts_resolution: TsResolution) -> ResultParsing<Self::Item>;
     fn to_owned(& self) -> Self::Item;
     fn from_slice< B: ByteOrder>(slice: &'a[u8], ts_resolution: TsResolution) -> ResultParsing<(&'a[u8], Self::Item)>;
     fn get_data(&self) -> &Cow<'a, [u8]>;
     fn get_header(&self) -> Self::Header;
+
+    /// Dissect the packet data, `datalink` being the one of its capture.
+    fn dissect<'s>(&'s self, datalink: DataLink) -> Dissection<'s> where 'a: 's {
+        dissect(datalink, self.get_data())
+    }
 }
 
 
@@ -76,6 +106,30 @@ impl SomePacketHeader for VppPacketHeader {
         self.orig_len = orig_len;
     }
 
+    fn ts_sec(&self) -> u32 {
+        self.ts_sec
+    }
+
+    fn ts_nsec(&self) -> u32 {
+        self.ts_nsec
+    }
+
+    fn incl_len(&self) -> u32 {
+        self.incl_len
+    }
+
+    fn orig_len(&self) -> u32 {
+        self.orig_len
+    }
+
+    fn interface_index(&self) -> Option<u32> {
+        Some(self.interface_index)
+    }
+
+    fn set_interface_index(&mut self, interface_index: u32) {
+        self.interface_index = interface_index;
+    }
+
     /// Create a new `VppPacketHeader` with the given parameters.
    fn new(ts_sec: u32, ts_nsec: u32, incl_len:u32, orig_len:u32) -> VppPacketHeader {
 
@@ -94,25 +148,16 @@ impl SomePacketHeader for VppPacketHeader {
         let ts_sec = reader.read_u32::<B>()?;
         let mut ts_nsec = reader.read_u32::<B>()?;
         if ts_resolution == TsResolution::MicroSecond {
-            ts_nsec *= 1000;
+            ts_nsec = ts_nsec.checked_mul(1000).ok_or(PcapError::InvalidField("PacketHeader ts_usec > 0xFFFFFFFF ns"))?;
         }
         let incl_len = reader.read_u32::<B>()?;
         let orig_len = reader.read_u32::<B>()?;
         let interface_index = reader.read_u32::<B>()?;
-
-        if incl_len > 0xFFFF {
-            return Err(PcapError::InvalidField("PacketHeader incl_len > 0xFFFF"));
-        }
-
-        if orig_len > 0xFFFF {
-            return Err(PcapError::InvalidField("PacketHeader orig_len > 0xFFFF"));
-        }
-
-        if incl_len > orig_len {
-            return Err(PcapError::InvalidField("PacketHeader incl_len > orig_len"));
+        // Hard bound on the allocation of the packet data, whatever the `LengthPolicy`
+        if incl_len > MAX_PACKET_LEN {
+            return Err(PcapError::InvalidField("PacketHeader incl_len > maximum packet length"));
         }
 
-
         Ok(
             VppPacketHeader {
 
@@ -224,6 +269,14 @@ impl<'a> SomePacket<'a> for VppPacket<'a> {
         }
     }
 
+    /// Create a new owned `VppPacket` from a header and its payload.
+    fn from_parts(header: VppPacketHeader, data: Vec<u8>) -> Self::Item {
+        VppPacket {
+            header,
+            data: Cow::Owned(data)
+        }
+    }
+
     /// Create a new owned `VppPacket` from a reader.
     fn from_reader<R: Read, B: ByteOrder>(reader: &mut R, ts_resolution: TsResolution) -> ResultParsing<Self::Item> {
 
@@ -1,11 +1,41 @@
 
+pub mod chain;
+pub mod compare;
+pub mod context;
+pub mod matching;
+pub mod merge;
+pub mod parallel;
+pub mod report;
+pub mod rewrite;
+pub mod scenario;
+pub mod snapshot;
+pub mod split;
+
 pub mod assistant {
 
     use crate::pcap::*;
+    use crate::{AssistantError, AssistantResult, CaptureReader, DataLink, PcapError};
+    use crate::pcapng::PcapNgWriter;
+    use crate::filter::{PacketFilter, PacketSource};
+    use super::matching::match_packets;
+    use super::parallel::{chunks, run_ordered};
+    pub use super::chain::*;
+    pub use super::compare::*;
+    pub use super::context::*;
+    pub use super::matching::MatchStrategy;
+    pub use super::merge::*;
+    pub use super::parallel::ParallelOptions;
+    pub use super::report::*;
+    pub use super::rewrite::*;
+    pub use super::scenario::*;
+    pub use super::snapshot::*;
+    pub use super::split::*;
     use std::cmp::Ordering;
     use std::fmt::Debug;
     use colored::Colorize;
     use std::fs::File;
+    use std::io::BufWriter;
+    use std::time::Duration;
 
     /// Trait for packet processor realization.
     /// 
@@ -13,6 +43,12 @@ pub mod assistant {
     pub trait PacketProcessor {
         /// Processes the packet (`Vec<u8>`) and returns false if packet must be dropped and true otherwise.
         fn process_packet(&mut self, _: &mut Vec<u8>) -> bool;
+
+        /// Processes the packet like `process_packet` but returns an error message instead of panicking
+        /// when the packet can't be processed. Used by `PcapTester`, defaults to `process_packet`.
+        fn try_process_packet(&mut self, packet: &mut Vec<u8>) -> Result<bool, String> {
+            Ok(self.process_packet(packet))
+        }
     }
     
     /// PcapTester with original_file and processor.
@@ -64,29 +100,40 @@ pub mod assistant {
         /// ```
         /// 
         fn process_packet(&mut self, packet: &mut Vec<u8>) -> bool {
+            match self.try_process_packet(packet) {
+                Ok(keep) => keep,
+                Err(message) => panic!("{}", message)
+            }
+        }
+
+        /// Same as `process_packet`, returns the panic reasons as errors.
+        fn try_process_packet(&mut self, packet: &mut Vec<u8>) -> Result<bool, String> {
             if self.start > packet.len() || self.end > packet.len(){
-                panic!("Range is out of packet bounds");
+                return Err("Range is out of packet bounds".to_string());
             }  
 
              // If there are no enough data for range size
             if self.start < self.end && (self.end - self.start) > self.new_data.len()  { 
-                panic!("Range is bigger than data set! Uundefined scenario!");
+                return Err("Range is bigger than data set! Uundefined scenario!".to_string());
             } 
             // Add dataset to selected range
             if self.start < self.end && (self.end - self.start) == self.new_data.len() {
                 packet.splice(self.start..self.end, self.new_data.clone());
 
-                return true;
+                return Ok(true);
             }  
 
             // Add dataset from start point till end of dataset and trim of packet end
             if self.start > 0 && self.end == 0 {
+                if self.new_data.len() + self.start > packet.len() {
+                    return Err("Dataset is out of packet bounds".to_string());
+                }
                 packet.splice(self.start..(self.new_data.len() + self.start), self.new_data.clone());
                 for _ in (self.new_data.len() + self.start)..packet.len() {
                     packet.pop(); 
                 }
 
-                return true;
+                return Ok(true);
             }  
             
             // If range is smaller than dataset size displace packet data to accommodate dataset
@@ -100,7 +147,7 @@ pub mod assistant {
                 }
                 packet.append(&mut temp_vec);
 
-                return true;
+                return Ok(true);
             } 
 
             // If range is not defined add dataset to start of packet
@@ -109,7 +156,7 @@ pub mod assistant {
                     packet.insert(i,*self.new_data.get(i).unwrap());
                 }
 
-                return true;
+                return Ok(true);
             } 
 
             // If range is equal to dataset size add dataset to end of packet
@@ -118,10 +165,10 @@ pub mod assistant {
                     packet.push(*self.new_data.get(i).unwrap());
                 }
                 
-                return true;
+                return Ok(true);
             } 
             
-            false
+            Ok(false)
         }
     
     }
@@ -133,17 +180,18 @@ pub mod assistant {
         }
 
         /// Open and print out encoded packets from file.
-        pub fn print_file <P: SomePacket<'static>> (file: &str)
+        pub fn print_file <P: SomePacket<'static>> (file: &str) -> AssistantResult<()>
             where P::Item: SomePacket<'static>, 
                  <P::Item as SomePacket<'static>>::Header: Debug + SomePacketHeader 
         {
-            let file = File::open(file).expect("Can`t open file!");
-            let pcap_reader = PcapReader::<File, P>::new(file).expect("Can`t create reader");
+            let pcap_reader = open_capture::<P>(file)?;
 
             for packet in pcap_reader {
-                let packet = packet.unwrap();                                       
+                let packet = packet?;
                 println!("{:?} \n {} \n", packet.get_header(), hex::encode(packet.get_data().as_ref()));
             }
+
+            Ok(())
         }
            
         /// Compare data from 2 vectors and return tuple with hex string with visual difference 
@@ -202,6 +250,9 @@ pub mod assistant {
  
         /// Process original file and save result to new file.
         /// 
+        /// Any `PacketProcessor`, `ContextProcessor` or `EmittingProcessor` can be used, every emitted packet is saved.
+        /// A `ProcessorChain` applies several processors in order.
+        /// 
         /// # Example
         /// 
         /// ```
@@ -212,38 +263,82 @@ pub mod assistant {
         /// env.process_and_save("new_file.pcap", &mut processor);
         /// 
         /// ```
-        pub fn process_and_save <P: SomePacket<'static>> (&self, file_to: &str, processor: &mut ProcessorExample) -> Result<bool,()> 
+        pub fn process_and_save <P: SomePacket<'static>> (&self, file_to: &str, processor: &mut impl EmittingProcessor) -> AssistantResult<bool> 
             where P::Item: SomePacket<'static>, 
                  <P::Item as SomePacket<'static>>::Header: Debug + SomePacketHeader,
                  PcapWriter<File>: PacketWriter<<P as SomePacket<'static>>::Item>
         { 
-            let file_from = File::open(&self.original_file).map_err(|_|())?;
-            let file_to = File::create(file_to).map_err(|_|())?;
-            let reader = PcapReader::<File, P>::new(file_from).map_err(|_|())?;
-            let mut writer = PcapWriter::new(file_to).map_err(|_|())?;
+            let mut reader = open_capture::<P>(&self.original_file)?;
+            let mut writer = PcapWriter::new(create_file(file_to)?).map_err(write_failure(file_to))?;
+            let mut index = 0;
         
-            for packet in reader {
-                let packet  = packet.map_err(|_|())?;
-                let mut data: Vec<u8> = packet.get_data().iter().copied().collect();
-                let mut header = packet.get_header();
-
-                let is_dropped = !processor.process_packet(&mut data);
-                if !is_dropped {
-                    
-                    header.set_incl_len(data.len() as u32);
-                    header.set_orig_len(data.len() as u32);
-                    
-                    let packet = packet.new_with_params(header, data);
-                    writer.write_packet(packet).map_err(|_|())?;  
+            while let Some(packet) = reader.next() {
+                let packet  = packet?;
+                let datalink = reader.current_datalink();
 
+                for (header, data) in process_packet(processor, datalink, index, &packet)? {
+                    let packet = packet.new_with_params(header, data);
+                    writer.write_packet(packet).map_err(write_failure(file_to))?;  
                 }                
+                index += 1;
             }
             Ok(true)
         }
 
+        /// Process original file and save result to new file like `process_and_save`, with a pool of `options.threads` threads.
+        ///
+        /// The packets are processed by chunks of `options.chunk_size`, each thread with its own clone of `processor`,
+        /// so a processor keeping a state between packets only sees the packets of the chunks of its thread.
+        /// The packets are saved in the order of the original file.
+        ///
+        /// # Examples
+        ///
+        /// ```rust,no_run
+        /// use pcap_assistant::pcap::Packet;
+        /// use pcap_assistant::pcap_assistant::assistant::{ParallelOptions, PcapTester, ProcessorExample};
+        ///
+        /// let processor = ProcessorExample::new(0, 0, vec![1, 2, 3]);
+        /// let env = PcapTester::new("netinfo.pcap");
+        ///
+        /// env.process_and_save_parallel::<Packet, _>("new_file.pcap", &processor, ParallelOptions::default()).unwrap();
+        /// ```
+        pub fn process_and_save_parallel <P: SomePacket<'static>, Processor> (&self, file_to: &str, processor: &Processor, options: ParallelOptions) -> AssistantResult<bool>
+            where P::Item: SomePacket<'static> + Send,
+                 <P::Item as SomePacket<'static>>::Header: Debug + SomePacketHeader,
+                 PcapWriter<File>: PacketWriter<<P as SomePacket<'static>>::Item>,
+                 Processor: EmittingProcessor + Clone + Send
+        {
+            let mut reader = open_capture::<P>(&self.original_file)?;
+            let mut writer = PcapWriter::new(create_file(file_to)?).map_err(write_failure(file_to))?;
+
+            let packets = std::iter::from_fn(|| {
+                let packet = reader.next()?;
+                Some(packet.map(|packet| (reader.current_datalink(), packet)))
+            });
+            let processors = vec![processor.clone(); options.threads.max(1)];
+
+            run_ordered(processors, chunks(packets, options.chunk_size), |processor, start, packets| {
+                let mut processed = Vec::with_capacity(packets.len());
+                for (offset, (datalink, packet)) in packets.into_iter().enumerate() {
+                    for (header, data) in process_packet(processor, datalink, start + offset, &packet)? {
+                        processed.push(packet.new_with_params(header, data));
+                    }
+                }
+                Ok(processed)
+            }, |processed| {
+                for packet in processed {
+                    writer.write_packet(packet).map_err(write_failure(file_to))?;
+                }
+                Ok(())
+            })?;
+
+            Ok(true)
+        }
+
         /// Process given file and compare it with original file.
         /// 
-        /// Returns true if file ended and false if self.file ended.
+        /// Returns the report of the comparison between the original file and the processed packets,
+        /// dropped packets are not part of the comparison and every emitted packet is compared in order.
         /// 
         ///  # Examples
         /// 
@@ -252,157 +347,449 @@ pub mod assistant {
         /// let mut processor = ProcessorExample::new(dataset.len(), dataset.len(), dataset);  //add to end of packet 
         /// 
         /// let env = PcapTester::new("netinfo.pcap");
-        /// env.process_and_compare_files("netinfo.pcap", &mut processor);
+        /// env.process_and_compare_files("netinfo.pcap", &mut processor).unwrap().print();
         /// ```
         /// 
-        pub fn process_and_compare_files<Processor: PacketProcessor, P: SomePacket<'static>> (&self, file: &str, processor: &mut Processor) -> Result<bool,()>
+        pub fn process_and_compare_files<Processor: EmittingProcessor, P: SomePacket<'static>> (&self, file: &str, processor: &mut Processor) -> AssistantResult<ComparisonReport>
             where P::Item: SomePacket<'static>, 
                  <P::Item as SomePacket<'static>>::Header: Debug + SomePacketHeader 
         {
-            let file_lhs = File::open(&self.original_file).map_err(|_|())?;
-            let file_rhs = File::open(file).map_err(|_|())?;
-            let mut reader_lhs = PcapReader::<File, P>::new(file_lhs).map_err(|_|())?;
-            let mut reader_rhs = PcapReader::<File, P>::new(file_rhs).map_err(|_|())?;
-            
-            let mut index: usize = 0;
-            let mut packet_lhs_opt = reader_lhs.next();
-
-            for packet_rhs in reader_rhs.by_ref() { 
-                let mut data_rhs: Vec<u8> = packet_rhs.unwrap().get_data().iter().copied().collect();
-                let is_dropped = !processor.process_packet(&mut data_rhs);
-                if !is_dropped {
-                    if packet_lhs_opt.is_none() {
-                        return Ok(false);
-                    }
-
-                    let data_lhs:Vec<u8> = packet_lhs_opt.unwrap().unwrap().get_data().iter().copied().collect();
+            self.process_and_compare_files_with::<Processor, P>(file, processor, &PacketComparator::default())
+        }
 
-                    if data_rhs == data_lhs {
-                        println!("Packet {}: {}", index + 1, "OK".green().bold());
-                    } else {
-                        println!("Packet {}: {} \n {}", index + 1, "FAIL".red().bold(), Self::compare_pakets_data(&data_lhs, &data_rhs).0);
+        /// Process given file and compare it with original file like `process_and_compare_files`,
+        /// with the ignore rules, the `MatchStrategy` and the `HeaderComparison` of `comparator`.
+        ///
+        /// # Errors
+        ///
+        /// Returns `AssistantError::DataLinkMismatch` if a file isn't captured with the `DataLink` of `comparator`
+        /// while it has ignore rules.
+        pub fn process_and_compare_files_with<Processor: EmittingProcessor, P: SomePacket<'static>> (&self, file: &str, processor: &mut Processor, comparator: &PacketComparator) -> AssistantResult<ComparisonReport>
+            where P::Item: SomePacket<'static>
+        {
+            let reader_lhs = open_capture::<P>(&self.original_file)?;
+            let mut reader_rhs = open_capture::<P>(file)?;
+            check_datalinks(comparator, [reader_lhs.current_datalink(), reader_rhs.current_datalink()])?;
+
+            let mut emitted = std::collections::VecDeque::new();
+            let mut index_rhs = 0;
+
+            let processed = std::iter::from_fn(|| {
+                while emitted.is_empty() {
+                    let packet_rhs = match reader_rhs.next()? {
+                        Ok(packet_rhs) => packet_rhs,
+                        Err(err) => return Some(Err(err))
+                    };
+                    let datalink = reader_rhs.current_datalink();
+
+                    match process_packet(processor, datalink, index_rhs, &packet_rhs) {
+                        Ok(packets) => emitted.extend(packets.into_iter().map(|(header, data)| packet_rhs.new_with_params(header, data))),
+                        Err(err) => return Some(Err(err))
                     }
+                    index_rhs += 1;
+                }
 
-                    index += 1;
-                    packet_lhs_opt = reader_lhs.next();
-                }                
-            }
+                emitted.pop_front().map(Ok)
+            });
 
-            Ok(reader_rhs.next().is_none()) 
+            compare_packets(reader_lhs, processed, comparator)
         }
         
         /// Compare .pcap files (original and provided).
         /// 
-        /// Returns true if files are same and false if different.
+        /// Returns the report of the comparison, packet by packet.
         /// # Example
         /// ```
         /// let env = PcapTester::new("file.pcap");
-        /// env.compare_files("file2.pcap");
+        /// assert!(env.compare_files::<Packet, Packet>("file2.pcap").unwrap().is_equal());
         /// ```
-        pub fn compare_files <P: SomePacket<'static>, V: SomePacket<'static>> (&self, file: &str) -> Result<bool, ()>
+        pub fn compare_files <P: SomePacket<'static>, V: SomePacket<'static>> (&self, file: &str) -> AssistantResult<ComparisonReport>
             where P::Item: SomePacket<'static>, 
                  <P::Item as SomePacket<'static>>::Header: Debug + SomePacketHeader,
                   V::Item: SomePacket<'static>, 
                  <V::Item as SomePacket<'static>>::Header: Debug + SomePacketHeader
         {
-            let file_lhs = File::open(&self.original_file).map_err(|_|())?;
-            let file_rhs = File::open(file).map_err(|_|())?;
-            let mut reader_lhs = PcapReader::<File, P>::new(file_lhs).map_err(|_|())?;
-            let reader_rhs = PcapReader::<File, V>::new(file_rhs).map_err(|_|())?;
-            let mut is_same = true;
+            self.compare_files_with::<P, V>(file, &PacketComparator::default())
+        }
 
-            for (index, packet_rhs) in reader_rhs.enumerate() { 
-                let packet_lhs_opt = reader_lhs.next();
+        /// Compare .pcap files (original and provided) like `compare_files`, leaving out the fields
+        /// ignored by `comparator`, e.g. the TTL and the checksums rewritten by a router.
+        ///
+        /// Packets are paired by the `MatchStrategy` of `comparator`, both files are read in memory
+        /// unless they're paired by index.
+        ///
+        /// # Errors
+        ///
+        /// Returns `AssistantError::DataLinkMismatch` if a file isn't captured with the `DataLink` of `comparator`
+        /// while it has ignore rules.
+        pub fn compare_files_with <P: SomePacket<'static>, V: SomePacket<'static>> (&self, file: &str, comparator: &PacketComparator) -> AssistantResult<ComparisonReport>
+            where P::Item: SomePacket<'static>,
+                  V::Item: SomePacket<'static>
+        {
+            let reader_lhs = open_capture::<P>(&self.original_file)?;
+            let reader_rhs = open_capture::<V>(file)?;
+            check_datalinks(comparator, [reader_lhs.current_datalink(), reader_rhs.current_datalink()])?;
 
-                if packet_lhs_opt.is_none() && is_same {
-                    return Ok(true);
-                } else if packet_lhs_opt.is_none() && !is_same {
-                    return Ok(false);
-                }
-                let data_rhs: Vec<u8> = packet_rhs.unwrap().get_data().iter().copied().collect();
-                let data_lhs: Vec<u8> = packet_lhs_opt.unwrap().unwrap().get_data().iter().copied().collect();
+            compare_packets(reader_lhs, reader_rhs, comparator)
+        }
 
-                if data_rhs == data_lhs {
-                    println!("Packet {}: {}", index, "OK".green().bold());
-                } else {
-                    is_same = false;
-                    println!("Packet {}: {} \n {}", index, "FAIL".red().bold(), Self::compare_pakets_data(&data_lhs, &data_rhs).0);
+        /// Compare .pcap files (original and provided) like `compare_files`, with a pool of `options.threads` threads.
+        ///
+        /// The files are read in order and the chunks of `options.chunk_size` packets are compared in parallel,
+        /// the report is the same as the one of `compare_files`.
+        pub fn compare_files_parallel <P: SomePacket<'static>, V: SomePacket<'static>> (&self, file: &str, options: ParallelOptions) -> AssistantResult<ComparisonReport>
+            where P::Item: SomePacket<'static> + Send,
+                  V::Item: SomePacket<'static> + Send
+        {
+            let mut reader_lhs = open_capture::<P>(&self.original_file)?;
+            let mut reader_rhs = open_capture::<V>(file)?;
+            let mut report = ComparisonReport::default();
+
+            let pairs = std::iter::from_fn(|| {
+                match (reader_lhs.next().transpose(), reader_rhs.next().transpose()) {
+                    (Err(err), _) | (_, Err(err)) => Some(Err(err)),
+                    (Ok(None), Ok(None)) => None,
+                    (Ok(lhs), Ok(rhs)) => Some(Ok((lhs, rhs)))
                 }
+            });
+
+            run_ordered(vec![(); options.threads.max(1)], chunks(pairs, options.chunk_size), |_, start, pairs| {
+                Ok(pairs.iter().enumerate()
+                    .filter_map(|(offset, (lhs, rhs))| compare_pair(start + offset, lhs.as_ref(), rhs.as_ref(), &PacketComparator::default(), (Duration::ZERO, Duration::ZERO)))
+                    .collect::<Vec<_>>())
+            }, |comparisons| {
+                report.packets.extend(comparisons);
+                Ok(())
+            })?;
+
+            Ok(report)
+        }
 
-            }
+        /// Compare .pcap files (original and provided) like `compare_files`, reading them with a `MmapPcapReader`.
+        ///
+        /// The compared packets borrow their data from the mapped files instead of being copied,
+        /// which suits large captures. pcapng files are not supported.
+        pub fn compare_files_mapped <P: BorrowedPacket, V: BorrowedPacket> (&self, file: &str) -> AssistantResult<ComparisonReport> {
 
-            if is_same {
-                return Ok(true);
-            }
+            let reader_lhs = open_mapped(&self.original_file)?;
+            let reader_rhs = open_mapped(file)?;
 
-            Ok(false)
+            compare_packets(mapped_packets::<P::Borrowed<'_>>(&reader_lhs), mapped_packets::<V::Borrowed<'_>>(&reader_rhs), &PacketComparator::default())
+        }
+
+        /// Compare the packets of .pcap files (original and provided) matching `filter`, e.g. a `BpfFilter` or a `DisplayFilter`.
+        /// 
+        /// Packets which don't match are skipped on both sides before comparing the others in order.
+        pub fn compare_files_filtered <P: SomePacket<'static>, V: SomePacket<'static>, F: PacketFilter> (&self, file: &str, filter: &F) -> AssistantResult<ComparisonReport>
+            where P::Item: SomePacket<'static>, 
+                 <P::Item as SomePacket<'static>>::Header: Debug + SomePacketHeader,
+                  V::Item: SomePacket<'static>, 
+                 <V::Item as SomePacket<'static>>::Header: Debug + SomePacketHeader
+        {
+            self.compare_files_filtered_with::<P, V, F>(file, filter, &PacketComparator::default())
         }
 
+        /// Compare the packets of .pcap files (original and provided) matching `filter` like `compare_files_filtered`,
+        /// leaving out the fields ignored by `comparator`, see `compare_files_with`.
+        pub fn compare_files_filtered_with <P: SomePacket<'static>, V: SomePacket<'static>, F: PacketFilter> (&self, file: &str, filter: &F, comparator: &PacketComparator) -> AssistantResult<ComparisonReport>
+            where P::Item: SomePacket<'static>,
+                  V::Item: SomePacket<'static>
+        {
+            let reader_lhs = open_capture::<P>(&self.original_file)?;
+            let reader_rhs = open_capture::<V>(file)?;
+            check_datalinks(comparator, [reader_lhs.current_datalink(), reader_rhs.current_datalink()])?;
+
+            compare_packets(reader_lhs.filtered(filter), reader_rhs.filtered(filter), comparator)
+        }
 
         /// Convert 'VppPackets' to 'Packets' and save to new file.
-        pub fn convert_from_vpp (&self, file_to: &str) -> Result<(), ()> {
-            let file_from = File::open(&self.original_file).map_err(|_|())?;
-            let file_to = File::create(file_to).map_err(|_|())?;
-            let reader = PcapReader::<File, VppPacket<'static>>::new(file_from).map_err(|_|())?;
-            let mut writer = PcapWriter::new(file_to).map_err(|_|())?;
+        pub fn convert_from_vpp (&self, file_to: &str) -> AssistantResult<()> {
+            let reader = open_capture::<VppPacket<'static>>(&self.original_file)?;
+            let mut writer = PcapWriter::new(create_file(file_to)?).map_err(write_failure(file_to))?;
 
             for data in reader {
-                let packet = VppPacket::convert(&data.unwrap());
-                writer.write_packet(packet).map_err(|_|())?;
+                let packet = VppPacket::convert(&data?);
+                writer.write_packet(packet).map_err(write_failure(file_to))?;
             }
 
             Ok(())
         }
 
         /// Convert 'Packets' to 'VppPackets' and save to new file.
-        pub fn convert_to_vpp (&self, file_to: &str) -> Result<(), ()> {
-            let file_from = File::open(&self.original_file).map_err(|_|())?;
-            let file_to = File::create(file_to).map_err(|_|())?;
-            let reader = PcapReader::<File, Packet<'static>>::new(file_from).map_err(|_|())?;
-            let mut writer = PcapWriter::new(file_to).map_err(|_|())?;
+        pub fn convert_to_vpp (&self, file_to: &str) -> AssistantResult<()> {
+            let reader = open_capture::<Packet<'static>>(&self.original_file)?;
+            let mut writer = PcapWriter::new(create_file(file_to)?).map_err(write_failure(file_to))?;
 
             for packet in reader {
-                let packet = packet.unwrap();
+                let packet = packet?;
                 
                 let vpp_packet = Packet::convert(&packet);
-                writer.write_packet(vpp_packet).map_err(|_|())?;
+                writer.write_packet(vpp_packet).map_err(write_failure(file_to))?;
+            }
+
+            Ok(())
+        }
+
+        /// Convert 'Packets' or 'VppPackets' to pcapng and save to new file.
+        ///
+        /// Each distinct 'VppPacketHeader::interface_index' is written as its own interface.
+        pub fn convert_to_pcapng <P: SomePacket<'static>> (&self, file_to: &str) -> AssistantResult<()>
+            where PcapNgWriter<File>: PacketWriter<<P as SomePacket<'static>>::Item>
+        {
+            let reader = open_capture::<P>(&self.original_file)?;
+            let header = PcapHeader { datalink: reader.reader.datalink(), ..Default::default() };
+            let mut writer = PcapNgWriter::with_header(header, create_file(file_to)?).map_err(write_failure(file_to))?;
+
+            for packet in reader {
+                writer.write_packet(packet?).map_err(write_failure(file_to))?;
             }
 
             Ok(())
         }
 
+        /// Merge the packets of .pcap files in timestamp order and save them to a new file, see `CaptureMerger`.
+        ///
+        /// With `tag_interfaces` the packets are saved as 'VppPackets' whose interface index is the position
+        /// of their file in `files`.
+        pub fn merge_files <P: SomePacket<'static>> (files: &[&str], file_to: &str, tag_interfaces: bool) -> AssistantResult<()>
+            where P::Item: SomePacket<'static>,
+            PcapWriter<File>: PacketWriter<<P as SomePacket<'static>>::Item>
+        {
+            let mut readers = Vec::with_capacity(files.len());
+
+            for path in files {
+                let file = File::open(path).map_err(|source| AssistantError::FileOpen { path: path.to_string(), source })?;
+                readers.push(PcapReader::<_, P>::new(file).map_err(|source| AssistantError::HeaderParse { path: path.to_string(), source })?);
+            }
+
+            let merger = CaptureMerger::new(readers)?;
+            let mut writer = PcapWriter::with_header(*merger.header(), create_file(file_to)?).map_err(write_failure(file_to))?;
+
+            for merged in merger {
+                let merged = merged?;
+
+                if tag_interfaces {
+                    PacketWriter::<VppPacket>::write_packet(&mut writer, merged.tagged()).map_err(write_failure(file_to))?;
+                }
+                else {
+                    writer.write_packet(merged.packet).map_err(write_failure(file_to))?;
+                }
+            }
+
+            Ok(())
+        }
+
+        /// Split the original file according to `policy`, returns the paths of the new files, see `CaptureSplitter`.
+        pub fn split_file <P: SomePacket<'static>> (&self, policy: SplitPolicy, template: &str) -> AssistantResult<Vec<String>>
+            where P::Item: SomePacket<'static>,
+            PcapWriter<BufWriter<File>>: PacketWriter<<P as SomePacket<'static>>::Item>
+        {
+            let file = File::open(&self.original_file).map_err(|source| AssistantError::FileOpen { path: self.original_file.clone(), source })?;
+            let reader = PcapReader::<_, P>::new(file).map_err(|source| AssistantError::HeaderParse { path: self.original_file.clone(), source })?;
+
+            CaptureSplitter::new(policy, template).split(reader)
+        }
+
+        /// Read the original file with a `RecoveringReader` and save the packets it recovers to a new file.
+        ///
+        /// Returns the regions of the original file which were skipped, empty if the file is clean.
+        pub fn repair_file <P: BorrowedPacket> (&self, file_to: &str) -> AssistantResult<Vec<SkippedRegion>>
+            where PcapWriter<BufWriter<File>>: for<'a> PacketWriter<P::Borrowed<'a>>
+        {
+            let mapped = open_mapped(&self.original_file)?;
+            let mut reader = RecoveringReader::<P::Borrowed<'_>>::new(mapped.as_slice())
+                .map_err(|source| AssistantError::HeaderParse { path: self.original_file.clone(), source })?;
+
+            let file = BufWriter::new(create_file(file_to)?);
+            let mut writer = PcapWriter::with_header(*reader.header(), file).map_err(write_failure(file_to))?;
+
+            for packet in reader.by_ref() {
+                writer.write_packet(packet).map_err(write_failure(file_to))?;
+            }
+            writer.into_writer().into_inner().map_err(|error| AssistantError::WriteFailure { path: file_to.to_string(), source: error.into_error().into() })?;
+
+            Ok(reader.skipped().to_vec())
+        }
+
         /// Save 'PcapReader' with Packets to given file.
-        pub fn save_reader_to_new_pcap <P: SomePacket<'static>> (file_to: &str, pcap_reader: PcapReader<File, P>) -> Result<(),()> 
+        pub fn save_reader_to_new_pcap <P: SomePacket<'static>> (file_to: &str, mut pcap_reader: PcapReader<File, P>) -> AssistantResult<()> 
             where P::Item: SomePacket<'static>,
             PcapWriter<File>: PacketWriter<<P as SomePacket<'static>>::Item>
         {
-            let file_to = File::create(file_to).map_err(|_|())?;
-            let mut pcap_writer = PcapWriter::new(file_to).map_err(|_|())?;
-            
-            for packet in pcap_reader {
-                let packet = packet.unwrap();
-                pcap_writer.write_packet(packet).unwrap();
+            let mut pcap_writer = PcapWriter::new(create_file(file_to)?).map_err(write_failure(file_to))?;
+            let mut index = 0;
+
+            loop {
+                let offset = pcap_reader.position();
+                let packet = match pcap_reader.next() {
+                    Some(packet) => packet.map_err(|source| AssistantError::PacketParse { index, offset, source })?,
+                    None => break
+                };
+
+                pcap_writer.write_packet(packet).map_err(write_failure(file_to))?;
+                index += 1;
             }
             Ok(())
         }
 
 
         /// Save 'PacketHeader' and Packets to given file.
-        pub fn save_packets_to_new_pcap <P: SomePacket<'static, Item = P>> (file_to: &str, pcap_header: PcapHeader, packets: Vec<P>) -> Result<(),()> 
+        pub fn save_packets_to_new_pcap <P: SomePacket<'static, Item = P>> (file_to: &str, pcap_header: PcapHeader, packets: Vec<P>) -> AssistantResult<()> 
             where P::Item: SomePacket<'static>, 
             PcapWriter<File>: PacketWriter<<P as SomePacket<'static>>::Item>
         {
-            let file_to = File::create(file_to).map_err(|_|())?;
-            let mut pcap_writer = PcapWriter::with_header(pcap_header, file_to).map_err(|_|())?;
+            let mut pcap_writer = PcapWriter::with_header(pcap_header, create_file(file_to)?).map_err(write_failure(file_to))?;
     
             for packet in packets {
-                pcap_writer.write_packet(packet).map_err(|_|())?;
+                pcap_writer.write_packet(packet).map_err(write_failure(file_to))?;
             }
             Ok(())
         }
 
     
     }
+
+    /// Packets of an opened capture file.
+    ///
+    /// Parsing errors are located by the packet index and its byte offset in the file.
+    pub(crate) struct CapturePackets<P: SomePacket<'static>> {
+        pub(crate) reader: CaptureReader<File, P>,
+        index: usize
+    }
+
+    impl<P: SomePacket<'static>> Iterator for CapturePackets<P> {
+
+        type Item = AssistantResult<P::Item>;
+
+        fn next(&mut self) -> Option<Self::Item> {
+            let offset = self.reader.position();
+            let index = self.index;
+            let packet = self.reader.next()?;
+
+            self.index += 1;
+
+            Some(packet.map_err(|source| AssistantError::PacketParse { index, offset, source }))
+        }
+    }
+
+    /// Compare two sequences of packets, in order or read in memory to be paired by the `MatchStrategy` of `comparator`.
+    pub(crate) fn compare_packets<'l, 'r, L, R, LP, RP>(mut lhs: L, mut rhs: R, comparator: &PacketComparator) -> AssistantResult<ComparisonReport>
+        where L: Iterator<Item = AssistantResult<LP>>,
+              R: Iterator<Item = AssistantResult<RP>>,
+              LP: SomePacket<'l>,
+              RP: SomePacket<'r>
+    {
+        if comparator.strategy() != MatchStrategy::Index {
+            let lhs = lhs.collect::<AssistantResult<Vec<_>>>()?;
+            let rhs = rhs.collect::<AssistantResult<Vec<_>>>()?;
+
+            return Ok(match_packets(&lhs, &rhs, comparator));
+        }
+
+        let mut report = ComparisonReport::default();
+        let mut origins = (Duration::ZERO, Duration::ZERO);
+
+        for index in 0.. { 
+            let (packet_lhs, packet_rhs) = (lhs.next().transpose()?, rhs.next().transpose()?);
+            if let (0, Some(first_lhs), Some(first_rhs)) = (index, &packet_lhs, &packet_rhs) {
+                origins = (first_lhs.get_header().timestamp(), first_rhs.get_header().timestamp());
+            }
+
+            match compare_pair(index, packet_lhs.as_ref(), packet_rhs.as_ref(), comparator, origins) {
+                Some(comparison) => report.packets.push(comparison),
+                None => break
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Compare the packets read at `index`, `None` when both sides are at their end.
+    ///
+    /// `origins` are the timestamps of the first packets, see `PacketComparator::compare_from`.
+    fn compare_pair<'l, 'r, LP: SomePacket<'l>, RP: SomePacket<'r>>(index: usize, lhs: Option<&LP>, rhs: Option<&RP>, comparator: &PacketComparator, origins: (Duration, Duration)) -> Option<PacketComparison> {
+
+        match (lhs, rhs) {
+            (Some(packet_lhs), Some(packet_rhs)) => {
+                Some(comparator.compare_from(origins, index, &packet_lhs.get_header(), packet_lhs.get_data(), &packet_rhs.get_header(), packet_rhs.get_data()))
+            },
+            (None, Some(_)) => Some(PacketComparison::missing_left(index)),
+            (Some(_), None) => Some(PacketComparison::missing_right(index)),
+            (None, None) => None
+        }
+    }
+
+    /// Check that the compared files have the `DataLink` of `comparator`, when its rules dissect the packets.
+    fn check_datalinks(comparator: &PacketComparator, datalinks: [DataLink; 2]) -> AssistantResult<()> {
+
+        if comparator.rules().is_empty() {
+            return Ok(());
+        }
+
+        match datalinks.into_iter().position(|datalink| datalink != comparator.datalink()) {
+            Some(input) => Err(AssistantError::DataLinkMismatch { input, expected: comparator.datalink(), found: datalinks[input] }),
+            None => Ok(())
+        }
+    }
+
+    /// Process a packet read at `index` with its context, returns the header and data of the emitted packets.
+    fn process_packet<Processor, P>(processor: &mut Processor, datalink: DataLink, index: usize, packet: &P) -> AssistantResult<Vec<(P::Header, Vec<u8>)>>
+        where Processor: EmittingProcessor + ?Sized,
+              P: SomePacket<'static>
+    {
+        let initial = PacketContext::new(index, datalink, &packet.get_header());
+
+        let emitted = processor.emit_packets(initial, packet.get_data().to_vec())
+            .map_err(|message| AssistantError::ProcessorFailure { index, message })?;
+
+        Ok(emitted.into_iter()
+            .map(|emitted| (emitted.context.to_header(&initial, emitted.data.len()), emitted.data))
+            .collect())
+    }
+
+    impl<P: SomePacket<'static>> PacketSource for CapturePackets<P> {
+
+        fn current_datalink(&self) -> DataLink {
+            self.reader.packet_datalink()
+        }
+    }
+
+    /// Open a pcap or pcapng file and read its header.
+    pub(crate) fn open_capture<P: SomePacket<'static>>(path: &str) -> AssistantResult<CapturePackets<P>> {
+        let file = File::open(path).map_err(|source| AssistantError::FileOpen { path: path.to_string(), source })?;
+        let reader = CaptureReader::new(file).map_err(|source| AssistantError::HeaderParse { path: path.to_string(), source })?;
+
+        Ok(CapturePackets { reader, index: 0 })
+    }
+
+    /// Map a pcap file in memory and read its header.
+    fn open_mapped(path: &str) -> AssistantResult<MmapPcapReader> {
+        let file = File::open(path).map_err(|source| AssistantError::FileOpen { path: path.to_string(), source })?;
+
+        MmapPcapReader::new(&file).map_err(|source| AssistantError::HeaderParse { path: path.to_string(), source })
+    }
+
+    /// Packets of a mapped file, with parsing errors located like the ones of `CapturePackets`.
+    fn mapped_packets<'a, P: SomePacket<'a> + 'a>(reader: &'a MmapPcapReader) -> impl Iterator<Item = AssistantResult<P::Item>> + 'a {
+        let mut packets = reader.packets::<P>();
+
+        (0..).map_while(move |index| {
+            let offset = packets.position();
+            let packet = packets.next()?;
+
+            Some(packet.map_err(|source| AssistantError::PacketParse { index, offset, source }))
+        })
+    }
+
+    /// Create (or truncate) a file to write a capture to.
+    pub(crate) fn create_file(path: &str) -> AssistantResult<File> {
+        File::create(path).map_err(|source| AssistantError::FileCreate { path: path.to_string(), source })
+    }
+
+    /// Map a writing error on `path` to an `AssistantError`.
+    pub(crate) fn write_failure(path: &str) -> impl Fn(PcapError) -> AssistantError + '_ {
+        move |source| AssistantError::WriteFailure { path: path.to_string(), source }
+    }
 }
     
 
@@ -410,7 +797,11 @@ pub mod assistant {
 mod tests {
     use crate::pcap_assistant::assistant::*;
     use crate::pcap::*;
+    use crate::{AssistantError, DataLink};
+    use crate::test_fixtures::write_capture;
+    use crate::filter::{BpfFilter, DisplayFilter};
     use std::fs::File;
+    use std::io::Write;
     use std::vec;
     use std::fs;
  
@@ -418,10 +809,10 @@ mod tests {
     fn compare_files_test() {
         let env = PcapTester::new("netinfo2.pcap");
 
-        assert!(env.compare_files::<Packet, Packet>("netinfo2.pcap").unwrap());
-        //assert!(env.compare_files::<Packet, VppPacket>("vpp_netinfo2.pcap").unwrap());
-        assert!(!env.compare_files::<Packet, Packet>("netinfo.pcap").unwrap());
-        assert!(!env.compare_files::<Packet, VppPacket>("vpp_netinfo.pcap").unwrap());
+        assert!(env.compare_files::<Packet, Packet>("netinfo2.pcap").unwrap().is_equal());
+        //assert!(env.compare_files::<Packet, VppPacket>("vpp_netinfo2.pcap").unwrap().is_equal());
+        assert!(!env.compare_files::<Packet, Packet>("netinfo.pcap").unwrap().is_equal());
+        assert!(!env.compare_files::<Packet, VppPacket>("vpp_netinfo.pcap").unwrap().is_equal());
 
     }
 
@@ -431,7 +822,7 @@ mod tests {
         env.convert_to_vpp("new_vpp_netinfo.pcap").unwrap();
         File::open("new_vpp_netinfo.pcap").unwrap();
 
-        assert!(env.compare_files::<Packet, VppPacket>("new_vpp_netinfo.pcap").unwrap());
+        assert!(env.compare_files::<Packet, VppPacket>("new_vpp_netinfo.pcap").unwrap().is_equal());
 
         fs::remove_file("new_vpp_netinfo.pcap").unwrap();
     }
@@ -442,7 +833,7 @@ mod tests {
         env.convert_from_vpp("new.pcap").unwrap();
         File::open("new.pcap").unwrap();
 
-        assert!(env.compare_files::<VppPacket, Packet>("new.pcap").unwrap());
+        assert!(env.compare_files::<VppPacket, Packet>("new.pcap").unwrap().is_equal());
 
         fs::remove_file("new.pcap").unwrap();
     }    
@@ -507,8 +898,8 @@ mod tests {
         File::open("new_file_test.pcap").expect("Can`t open file!");
         File::open("new_vppfile_test.pcap").expect("Can`t open file!");
 
-        assert!(!env.compare_files::<Packet, Packet>("new_file_test.pcap").unwrap());
-        //assert!(env_vpp.compare_files::<VppPacket, VppPacket>("new_vppfile_test.pcap").unwrap());
+        assert!(!env.compare_files::<Packet, Packet>("new_file_test.pcap").unwrap().is_equal());
+        //assert!(env_vpp.compare_files::<VppPacket, VppPacket>("new_vppfile_test.pcap").unwrap().is_equal());
 
         fs::remove_file("new_file_test.pcap").unwrap();
         fs::remove_file("new_vppfile_test.pcap").unwrap();
@@ -528,8 +919,8 @@ mod tests {
         File::open("new_file_from_reader.pcap").unwrap();
         File::open("new_file_from_vppreader.pcap").unwrap();
 
-        assert!(env.compare_files::<Packet, Packet>("new_file_from_reader.pcap").unwrap());
-        assert!(env_vpp.compare_files::<VppPacket, VppPacket>("new_file_from_vppreader.pcap").unwrap());
+        assert!(env.compare_files::<Packet, Packet>("new_file_from_reader.pcap").unwrap().is_equal());
+        assert!(env_vpp.compare_files::<VppPacket, VppPacket>("new_file_from_vppreader.pcap").unwrap().is_equal());
     
         fs::remove_file("new_file_from_reader.pcap").unwrap();
         fs::remove_file("new_file_from_vppreader.pcap").unwrap();
@@ -562,12 +953,296 @@ mod tests {
         File::open("new_file_from_data.pcap").unwrap();
         File::open("new_file_from_vppdata.pcap").unwrap();
 
-        assert!(env.compare_files::<Packet, Packet>("new_file_from_data.pcap").unwrap());
-        assert!(_env_vpp.compare_files::<VppPacket, VppPacket>("new_file_from_vppdata.pcap").unwrap());
+        assert!(env.compare_files::<Packet, Packet>("new_file_from_data.pcap").unwrap().is_equal());
+        assert!(_env_vpp.compare_files::<VppPacket, VppPacket>("new_file_from_vppdata.pcap").unwrap().is_equal());
     
         fs::remove_file("new_file_from_data.pcap").unwrap();
         fs::remove_file("new_file_from_vppdata.pcap").unwrap();
     }
 
-}
+    #[test]
+    fn assistant_error_test() {
+        let env = PcapTester::new("missing_file.pcap");
+        assert!(matches!(env.compare_files::<Packet, Packet>("missing_file.pcap"), Err(AssistantError::FileOpen { .. })));
+
+        // Second packet header is cut in the middle
+        let file = File::create("truncated_test.pcap").unwrap();
+        let mut writer = PcapWriter::with_header(PcapHeader::default(), file).unwrap();
+        writer.write_packet(Packet::new(1, 0, &[1, 2, 3], 3)).unwrap();
+        writer.get_mut().write_all(&[0; 6]).unwrap();
+        drop(writer);
+
+        let env = PcapTester::new("truncated_test.pcap");
+        match env.compare_files::<Packet, Packet>("truncated_test.pcap") {
+            Err(AssistantError::PacketParse { index, offset, .. }) => assert_eq!((index, offset), (1, 24 + 16 + 3)),
+            other => panic!("Unexpected result {:?}", other)
+        }
+
+        let mut processor = ProcessorExample::new(10, 0, vec![1, 2]);
+        let result = PcapTester::new("truncated_test.pcap").process_and_save::<Packet>("truncated_out_test.pcap", &mut processor);
+        assert!(matches!(result, Err(AssistantError::ProcessorFailure { index: 0, .. })));
 
+        fs::remove_file("truncated_test.pcap").unwrap();
+        fs::remove_file("truncated_out_test.pcap").unwrap();
+    }
+
+    #[test]
+    fn comparison_report_test() {
+        write_capture("report_lhs_test.pcap", &[&[1, 2, 3], &[4, 5, 6], &[7]]);
+        write_capture("report_rhs_test.pcap", &[&[1, 2, 3], &[4, 9, 6, 0]]);
+
+        let report = PcapTester::new("report_lhs_test.pcap").compare_files::<Packet, Packet>("report_rhs_test.pcap").unwrap();
+
+        assert!(!report.is_equal());
+        assert_eq!(report.summary(), ComparisonSummary { total: 3, equal: 1, different: 1, missing_left: 0, missing_right: 1 });
+        assert!(matches!(report.packets[1].outcome, PacketOutcome::Different { first_mismatch: 1, .. }));
+        assert_eq!(
+            report.packets[1].header_differences,
+            vec![
+                HeaderDifference { field: HeaderField::InclLen, lhs: 3, rhs: 4, checked: false },
+                HeaderDifference { field: HeaderField::OrigLen, lhs: 3, rhs: 4, checked: false }
+            ]
+        );
+        assert!(report.to_string().contains("3 packets: 1 equal, 1 different, 0 missing left, 1 missing right"));
+
+        fs::remove_file("report_lhs_test.pcap").unwrap();
+        fs::remove_file("report_rhs_test.pcap").unwrap();
+    }
+
+    #[test]
+    fn compare_files_filtered_test() {
+        let arp = [[0; 12].as_ref(), &[0x08, 0x06, 1]].concat();
+        let ip = [[0; 12].as_ref(), &[0x08, 0x00, 2]].concat();
+        write_capture("filtered_lhs_test.pcap", &[&arp, &ip, &arp]);
+        write_capture("filtered_rhs_test.pcap", &[&ip, &arp]);
+
+        let env = PcapTester::new("filtered_lhs_test.pcap");
+        assert!(!env.compare_files::<Packet, Packet>("filtered_rhs_test.pcap").unwrap().is_equal());
+
+        let filter = BpfFilter::new("not arp", DataLink::ETHERNET).unwrap();
+        let report = env.compare_files_filtered::<Packet, Packet, _>("filtered_rhs_test.pcap", &filter).unwrap();
+        assert!(report.is_equal());
+        assert_eq!(report.summary().total, 1);
+
+        let filter = DisplayFilter::new("eth.type != 0x0806").unwrap();
+        assert!(env.compare_files_filtered::<Packet, Packet, _>("filtered_rhs_test.pcap", &filter).unwrap().is_equal());
+
+        let mut filter = BpfFilter::new("arp", DataLink::ETHERNET).unwrap();
+        assert!(env.process_and_save::<Packet>("filtered_out_test.pcap", &mut filter).unwrap());
+        let reader = PcapReader::<_, Packet>::new(File::open("filtered_out_test.pcap").unwrap()).unwrap();
+        assert_eq!(reader.count(), 2);
+
+        fs::remove_file("filtered_lhs_test.pcap").unwrap();
+        fs::remove_file("filtered_rhs_test.pcap").unwrap();
+        fs::remove_file("filtered_out_test.pcap").unwrap();
+    }
+
+    #[test]
+    fn compare_files_with_test() {
+        let ipv4 = |ttl: u8, payload: u8| [[0; 12].as_ref(), &[0x08, 0x00, 0x45, 0, 0, 21, 0, 0, 0, 0, ttl, 17, ttl, 0], &[10, 0, 0, 1, 10, 0, 0, 2, payload]].concat();
+        write_capture("ignore_lhs_test.pcap", &[&ipv4(64, 1), &ipv4(64, 2)]);
+        write_capture("ignore_rhs_test.pcap", &[&ipv4(63, 1), &ipv4(63, 3)]);
+
+        let env = PcapTester::new("ignore_lhs_test.pcap");
+        assert_eq!(env.compare_files::<Packet, Packet>("ignore_rhs_test.pcap").unwrap().summary().different, 2);
+
+        let comparator = PacketComparator::new(DataLink::ETHERNET).ignore("ip.ttl").unwrap().ignore("ip.checksum").unwrap();
+        let report = env.compare_files_with::<Packet, Packet>("ignore_rhs_test.pcap", &comparator).unwrap();
+        assert_eq!(report.failures().map(|packet| packet.index).collect::<Vec<_>>(), vec![1]);
+
+        let filter = DisplayFilter::new("frame.len == 35").unwrap();
+        assert!(env.compare_files_filtered_with::<Packet, Packet, _>("ignore_rhs_test.pcap", &filter, &comparator).is_ok());
+
+        let comparator = PacketComparator::new(DataLink::RAW).ignore("ip.ttl").unwrap();
+        let result = env.compare_files_with::<Packet, Packet>("ignore_rhs_test.pcap", &comparator);
+
+        fs::remove_file("ignore_lhs_test.pcap").unwrap();
+        fs::remove_file("ignore_rhs_test.pcap").unwrap();
+
+        assert!(matches!(result, Err(AssistantError::DataLinkMismatch { input: 0, .. })));
+    }
+
+    #[test]
+    fn compare_files_mapped_test() {
+        write_capture("mapped_lhs_test.pcap", &[&[1, 2, 3], &[4, 5, 6], &[7]]);
+        write_capture("mapped_rhs_test.pcap", &[&[1, 2, 3], &[4, 9, 6, 0]]);
+
+        let env = PcapTester::new("mapped_lhs_test.pcap");
+        let mapped = env.compare_files_mapped::<Packet, Packet>("mapped_rhs_test.pcap").unwrap();
+        assert_eq!(mapped, env.compare_files::<Packet, Packet>("mapped_rhs_test.pcap").unwrap());
+        assert_eq!((mapped.summary().total, mapped.is_equal()), (3, false));
+
+        let mut truncated = fs::read("mapped_rhs_test.pcap").unwrap();
+        truncated.truncate(truncated.len() - 1);
+        fs::write("mapped_rhs_test.pcap", truncated).unwrap();
+        let result = env.compare_files_mapped::<Packet, Packet>("mapped_rhs_test.pcap");
+
+        fs::remove_file("mapped_lhs_test.pcap").unwrap();
+        fs::remove_file("mapped_rhs_test.pcap").unwrap();
+
+        assert!(matches!(result, Err(AssistantError::PacketParse { index: 1, offset: 43, .. })));
+        assert!(matches!(env.compare_files_mapped::<Packet, Packet>("mapped_missing_test.pcap"), Err(AssistantError::FileOpen { .. })));
+    }
+
+    #[test]
+    fn parallel_test() {
+        let packets: Vec<Vec<u8>> = (0..50_u8).map(|index| vec![index; 1 + index as usize % 5]).collect();
+        let lhs: Vec<&[u8]> = packets.iter().map(Vec::as_slice).collect();
+        let mut rhs = lhs.clone();
+        rhs[17] = &[0xFF];
+        rhs.truncate(45);
+        write_capture("parallel_lhs_test.pcap", &lhs);
+        write_capture("parallel_rhs_test.pcap", &rhs);
+
+        let env = PcapTester::new("parallel_lhs_test.pcap");
+        let options = ParallelOptions::new(3, 4);
+        let report = env.compare_files_parallel::<Packet, Packet>("parallel_rhs_test.pcap", options).unwrap();
+        assert_eq!(report, env.compare_files::<Packet, Packet>("parallel_rhs_test.pcap").unwrap());
+        assert_eq!((report.summary().total, report.is_equal()), (50, false));
+
+        let processor = ProcessorExample::new(0, 0, vec![0xAA]);
+        env.process_and_save::<Packet>("parallel_seq_test.pcap", &mut processor.clone()).unwrap();
+        env.process_and_save_parallel::<Packet, _>("parallel_out_test.pcap", &processor, options).unwrap();
+        let sequential = fs::read("parallel_seq_test.pcap").unwrap();
+        let parallel = fs::read("parallel_out_test.pcap").unwrap();
+
+        for file in ["parallel_lhs_test.pcap", "parallel_rhs_test.pcap", "parallel_seq_test.pcap", "parallel_out_test.pcap"] {
+            fs::remove_file(file).unwrap();
+        }
+        assert_eq!(parallel, sequential);
+    }
+
+    #[test]
+    fn repair_file_test() {
+        write_capture("repair_in_test.pcap", &[&[1; 8], &[2; 8], &[3; 8]]);
+        let mut data = fs::read("repair_in_test.pcap").unwrap();
+        data.truncate(data.len() - 3);
+        fs::write("repair_in_test.pcap", data).unwrap();
+
+        // PcapReader fails on the truncated packet
+        let reader = PcapReader::<_, Packet>::new(File::open("repair_in_test.pcap").unwrap()).unwrap();
+        assert!(reader.last().unwrap().is_err());
+
+        let env = PcapTester::new("repair_in_test.pcap");
+        let skipped = env.repair_file::<Packet>("repair_out_test.pcap").unwrap();
+        let repaired: Vec<_> = PcapReader::<_, Packet>::new(File::open("repair_out_test.pcap").unwrap()).unwrap()
+            .map(|packet| packet.unwrap().data[0])
+            .collect();
+
+        fs::remove_file("repair_in_test.pcap").unwrap();
+        fs::remove_file("repair_out_test.pcap").unwrap();
+
+        assert_eq!(skipped, vec![SkippedRegion { offset: 72, len: 21, reason: SkipReason::TruncatedPacket }]);
+        assert_eq!(repaired, vec![1, 2]);
+    }
+
+    #[test]
+    fn merge_files_test() {
+        write_capture("merge_lhs_test.pcap", &[&[1], &[2], &[3]]);
+        let file = File::create("merge_rhs_test.pcap").unwrap();
+        let mut writer = PcapWriter::with_header(PcapHeader::default(), file).unwrap();
+        writer.write_packet(Packet::new(1, 500_000, &[4], 1)).unwrap();
+        drop(writer);
+
+        PcapTester::merge_files::<Packet>(&["merge_lhs_test.pcap", "merge_rhs_test.pcap"], "merge_out_test.pcap", true).unwrap();
+
+        let reader = PcapReader::<_, VppPacket>::new(File::open("merge_out_test.pcap").unwrap()).unwrap();
+        let packets: Vec<(u8, u32)> = reader.map(|packet| {
+            let packet = packet.unwrap();
+            (packet.data[0], packet.header.interface_index)
+        }).collect();
+        assert_eq!(packets, vec![(1, 0), (2, 0), (4, 1), (3, 0)]);
+
+        let result = PcapTester::merge_files::<Packet>(&["merge_lhs_test.pcap", "missing_file.pcap"], "merge_out_test.pcap", false);
+        assert!(matches!(result, Err(AssistantError::FileOpen { .. })));
+
+        fs::remove_file("merge_lhs_test.pcap").unwrap();
+        fs::remove_file("merge_rhs_test.pcap").unwrap();
+        fs::remove_file("merge_out_test.pcap").unwrap();
+    }
+
+    // Drops the second packet and delays the other ones by 10 seconds
+    struct DelayProcessor;
+
+    impl ContextProcessor for DelayProcessor {
+        fn process_with_context(&mut self, context: &mut PacketContext, packet: &mut Vec<u8>) -> Result<bool, String> {
+            assert_eq!(context.datalink, DataLink::ETHERNET);
+            assert_eq!(context.interface_index, None);
+            packet.push(context.index as u8);
+            context.ts_sec += 10;
+            Ok(context.index != 1)
+        }
+    }
+
+    #[test]
+    fn convert_to_pcapng_test() {
+        let file = File::create("pcapng_lhs_test.pcap").unwrap();
+        let mut writer = PcapWriter::with_header(PcapHeader::default(), file).unwrap();
+        for (index, interface_index) in [7, 3, 7].into_iter().enumerate() {
+            let header = VppPacketHeader { ts_sec: index as u32, ts_nsec: 0, incl_len: 1, orig_len: 1, interface_index };
+            writer.write_packet(VppPacket::from_parts(header, vec![index as u8])).unwrap();
+        }
+        drop(writer);
+
+        PcapTester::new("pcapng_lhs_test.pcap").convert_to_pcapng::<VppPacket>("pcapng_out_test.pcapng").unwrap();
+
+        let reader = crate::PcapNgReader::<_, VppPacket>::new(File::open("pcapng_out_test.pcapng").unwrap()).unwrap();
+        let indexes: Vec<u32> = reader.map(|packet| packet.unwrap().header.interface_index).collect();
+        assert_eq!(indexes, vec![7, 3, 7]);
+
+        fs::remove_file("pcapng_lhs_test.pcap").unwrap();
+        fs::remove_file("pcapng_out_test.pcapng").unwrap();
+    }
+
+    #[test]
+    fn context_processor_test() {
+        write_capture("context_in_test.pcap", &[&[1, 2, 3], &[4, 5, 6], &[7]]);
+
+        let env = PcapTester::new("context_in_test.pcap");
+        assert!(env.process_and_save::<Packet>("context_out_test.pcap", &mut DelayProcessor).unwrap());
+
+        let reader = PcapReader::<_, Packet>::new(File::open("context_out_test.pcap").unwrap()).unwrap();
+        let packets: Vec<_> = reader.map(|packet| packet.unwrap()).collect();
+        assert_eq!(packets.len(), 2);
+        assert_eq!((packets[0].get_header().ts_sec(), packets[0].get_data().as_ref()), (10, &[1, 2, 3, 0][..]));
+        assert_eq!((packets[1].get_header().ts_sec(), packets[1].get_data().as_ref()), (12, &[7, 2][..]));
+        assert_eq!(packets[1].get_header().orig_len(), 2);
+
+        fs::remove_file("context_in_test.pcap").unwrap();
+        fs::remove_file("context_out_test.pcap").unwrap();
+    }
+
+    // Emits every packet twice, the copy 1 second later
+    struct DuplicateProcessor;
+
+    impl EmittingProcessor for DuplicateProcessor {
+        fn emit_packets(&mut self, context: PacketContext, packet: Vec<u8>) -> Result<Vec<ProcessedPacket>, String> {
+            let mut later = context;
+            later.ts_sec += 1;
+            Ok(vec![ProcessedPacket::new(context, packet.clone()), ProcessedPacket::new(later, packet)])
+        }
+    }
+
+    #[test]
+    fn emitting_processor_test() {
+        write_capture("emitting_in_test.pcap", &[&[1, 2], &[3]]);
+        write_capture("emitting_expected_test.pcap", &[&[1, 2], &[1, 2], &[3], &[3]]);
+
+        let env = PcapTester::new("emitting_in_test.pcap");
+        assert!(env.process_and_save::<Packet>("emitting_out_test.pcap", &mut DuplicateProcessor).unwrap());
+
+        let reader = PcapReader::<_, Packet>::new(File::open("emitting_out_test.pcap").unwrap()).unwrap();
+        let timestamps: Vec<u32> = reader.map(|packet| packet.unwrap().get_header().ts_sec()).collect();
+        assert_eq!(timestamps, vec![0, 1, 1, 2]);
+
+        let env = PcapTester::new("emitting_expected_test.pcap");
+        let report = env.process_and_compare_files::<_, Packet>("emitting_in_test.pcap", &mut DuplicateProcessor).unwrap();
+        assert!(report.is_equal());
+        assert_eq!(report.summary().total, 4);
+
+        fs::remove_file("emitting_in_test.pcap").unwrap();
+        fs::remove_file("emitting_expected_test.pcap").unwrap();
+        fs::remove_file("emitting_out_test.pcap").unwrap();
+    }
+
+}
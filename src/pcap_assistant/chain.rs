@@ -0,0 +1,223 @@
+use crate::pcap_assistant::assistant::{ContextProcessor, PacketContext, PacketProcessor};
+use crate::DataLink;
+
+
+/// `ContextProcessor` applying an ordered list of processors to every packet.
+///
+/// Any `PacketProcessor` or `ContextProcessor` can be chained, e.g. a `DisplayFilter` followed by a `FieldRewriter`.
+/// A packet dropped by a processor isn't passed to the next ones.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use pcap_assistant::DataLink;
+/// use pcap_assistant::pcap::Packet;
+/// use pcap_assistant::filter::DisplayFilter;
+/// use pcap_assistant::pcap_assistant::assistant::{FieldRewrite, FieldRewriter, PcapTester, ProcessorChain, ProcessorExample};
+///
+/// let mut chain = ProcessorChain::new()
+///     .with(DisplayFilter::new("udp").unwrap())
+///     .with(ProcessorExample::new(0, 0, vec![0xAA, 0xBB]))
+///     .with(FieldRewriter::new(DataLink::ETHERNET).with(FieldRewrite::Ttl(1)));
+///
+/// let env = PcapTester::new("netinfo.pcap");
+/// env.process_and_save::<Packet>("new_file.pcap", &mut chain).unwrap();
+/// ```
+#[derive(Default)]
+pub struct ProcessorChain {
+    processors: Vec<Box<dyn ContextProcessor>>
+}
+
+impl ProcessorChain {
+
+    /// Creates an empty `ProcessorChain`, which keeps every packet unchanged.
+    pub fn new() -> ProcessorChain {
+
+        ProcessorChain::default()
+    }
+
+    /// Appends a processor at the end of the chain.
+    pub fn with<P: ContextProcessor + 'static>(mut self, processor: P) -> ProcessorChain {
+
+        self.processors.push(Box::new(processor));
+        self
+    }
+
+    /// Appends a boxed processor at the end of the chain.
+    pub fn push(&mut self, processor: Box<dyn ContextProcessor>) {
+        self.processors.push(processor);
+    }
+
+    pub fn len(&self) -> usize {
+        self.processors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.processors.is_empty()
+    }
+
+    /// `PacketProcessor` running the chain, for the callers which don't have the context of the packets.
+    ///
+    /// The chain itself can't be a `PacketProcessor`: every `PacketProcessor` is a `ContextProcessor` ignoring the
+    /// context, its processors would lose the context of the packets. See `ChainPacketProcessor` for the context given to them.
+    pub fn as_packet_processor(&mut self, datalink: DataLink) -> ChainPacketProcessor<'_> {
+
+        ChainPacketProcessor { chain: self, datalink, index: 0 }
+    }
+}
+
+/// Stops at the first processor which drops the packet or fails,
+/// errors are prefixed with the position of the processor in the chain.
+impl ContextProcessor for ProcessorChain {
+
+    fn process_with_context(&mut self, context: &mut PacketContext, packet: &mut Vec<u8>) -> Result<bool, String> {
+
+        for (position, processor) in self.processors.iter_mut().enumerate() {
+            let keep = processor.process_with_context(context, packet)
+                .map_err(|message| format!("Processor {} of the chain: {}", position, message))?;
+            if !keep {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+/// `PacketProcessor` running a `ProcessorChain`, created by `ProcessorChain::as_packet_processor`.
+///
+/// The processors get the context of a packet at the count of processed packets, with the `DataLink` of the
+/// chain, a zero timestamp, the length of the data as captured and original length and no interface index.
+pub struct ChainPacketProcessor<'a> {
+    chain: &'a mut ProcessorChain,
+    datalink: DataLink,
+    index: usize
+}
+
+impl PacketProcessor for ChainPacketProcessor<'_> {
+
+    /// Panics if a processor of the chain fails, see `try_process_packet`.
+    fn process_packet(&mut self, packet: &mut Vec<u8>) -> bool {
+
+        self.try_process_packet(packet).unwrap_or_else(|message| panic!("{}", message))
+    }
+
+    fn try_process_packet(&mut self, packet: &mut Vec<u8>) -> Result<bool, String> {
+
+        let len = packet.len() as u32;
+        let mut context = PacketContext {
+            index: self.index,
+            ts_sec: 0,
+            ts_nsec: 0,
+            incl_len: len,
+            orig_len: len,
+            datalink: self.datalink,
+            interface_index: None
+        };
+        self.index += 1;
+
+        self.chain.process_with_context(&mut context, packet)
+    }
+}
+
+impl<P: PacketProcessor + ?Sized> PacketProcessor for Box<P> {
+
+    fn process_packet(&mut self, packet: &mut Vec<u8>) -> bool {
+        (**self).process_packet(packet)
+    }
+
+    fn try_process_packet(&mut self, packet: &mut Vec<u8>) -> Result<bool, String> {
+        (**self).try_process_packet(packet)
+    }
+}
+
+impl<P: PacketProcessor + ?Sized> PacketProcessor for &mut P {
+
+    fn process_packet(&mut self, packet: &mut Vec<u8>) -> bool {
+        (**self).process_packet(packet)
+    }
+
+    fn try_process_packet(&mut self, packet: &mut Vec<u8>) -> Result<bool, String> {
+        (**self).try_process_packet(packet)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pcap::{PacketHeader, SomePacketHeader};
+    use crate::pcap_assistant::assistant::ProcessorExample;
+    use crate::DataLink;
+
+    // Drops the packets starting with the given byte
+    struct DropFirstByte(u8);
+
+    impl PacketProcessor for DropFirstByte {
+        fn process_packet(&mut self, packet: &mut Vec<u8>) -> bool {
+            packet.first() != Some(&self.0)
+        }
+    }
+
+    // Drops the packets of odd index, and appends the captured length of the other ones
+    struct EvenIndexes;
+
+    impl ContextProcessor for EvenIndexes {
+        fn process_with_context(&mut self, context: &mut PacketContext, packet: &mut Vec<u8>) -> Result<bool, String> {
+            packet.push(context.incl_len as u8);
+            Ok(context.index.is_multiple_of(2))
+        }
+    }
+
+    fn context(index: usize) -> PacketContext {
+        PacketContext::new(index, DataLink::ETHERNET, &PacketHeader::new(0, 0, 3, 3))
+    }
+
+    #[test]
+    fn processor_chain_test() {
+        let mut chain = ProcessorChain::new()
+            .with(ProcessorExample::new(0, 0, vec![0xAA]))
+            .with(DropFirstByte(0xAA))
+            .with(ProcessorExample::new(0, 0, vec![0xBB]));
+        assert_eq!(chain.len(), 3);
+
+        // Short-circuits: the last processor isn't applied to the dropped packet
+        let mut packet = vec![1, 2, 3];
+        assert_eq!(chain.process_with_context(&mut context(0), &mut packet), Ok(false));
+        assert_eq!(packet, vec![0xAA, 1, 2, 3]);
+
+        let mut chain = ProcessorChain::new()
+            .with(DropFirstByte(0xAA))
+            .with(EvenIndexes)
+            .with(ProcessorExample::new(0, 0, vec![0xBB]));
+        chain.push(Box::new(ProcessorExample::new(10, 12, vec![0])));
+
+        let mut packet = vec![1, 2, 3];
+        assert_eq!(chain.process_with_context(&mut context(1), &mut packet), Ok(false));
+        assert_eq!(packet, vec![1, 2, 3, 3]);
+
+        let mut packet = vec![1, 2, 3];
+        assert_eq!(chain.process_with_context(&mut context(2), &mut packet), Err("Processor 3 of the chain: Range is out of packet bounds".to_string()));
+        assert_eq!(packet, vec![0xBB, 1, 2, 3, 3]);
+
+        assert_eq!(ProcessorChain::new().process_with_context(&mut context(0), &mut packet), Ok(true));
+    }
+
+    #[test]
+    fn chain_packet_processor_test() {
+        let mut chain = ProcessorChain::new()
+            .with(EvenIndexes)
+            .with(ProcessorExample::new(0, 0, vec![0xBB]));
+        let mut processor = chain.as_packet_processor(DataLink::ETHERNET);
+        let processor: &mut dyn PacketProcessor = &mut processor;
+
+        // Indexes count the processed packets, the lengths are the ones of the data
+        let mut packet = vec![1, 2];
+        assert!(processor.process_packet(&mut packet));
+        assert_eq!(packet, vec![0xBB, 1, 2, 2]);
+        assert_eq!(processor.try_process_packet(&mut vec![1]), Ok(false));
+        assert_eq!(processor.try_process_packet(&mut vec![1]), Ok(true));
+
+        let mut chain = ProcessorChain::new().with(ProcessorExample::new(10, 12, vec![0]));
+        assert!(chain.as_packet_processor(DataLink::ETHERNET).try_process_packet(&mut vec![1]).is_err());
+    }
+}
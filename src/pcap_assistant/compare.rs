@@ -0,0 +1,651 @@
+use crate::dissect::*;
+use crate::pcap::SomePacketHeader;
+use crate::pcap_assistant::matching::MatchStrategy;
+use crate::pcap_assistant::report::{HeaderDifference, HeaderField, PacketComparison, PacketOutcome};
+use crate::{DataLink, PcapError, ResultParsing};
+use serde::de::Error;
+use serde::{Deserialize, Deserializer};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::time::Duration;
+
+
+// Protocol whose layers hold an ignorable field
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Protocol {
+    Ethernet,
+    Vlan,
+    Ipv4,
+    Ipv6,
+    Tcp,
+    Udp,
+    Icmp,
+    Icmpv6
+}
+
+impl Protocol {
+
+    fn matches(&self, layer: &Layer) -> bool {
+
+        matches!(
+            (self, layer),
+            (Protocol::Ethernet, Layer::Ethernet(_)) | (Protocol::Vlan, Layer::Vlan(_)) |
+            (Protocol::Ipv4, Layer::Ipv4(_)) | (Protocol::Ipv6, Layer::Ipv6(_)) |
+            (Protocol::Tcp, Layer::Tcp(_)) | (Protocol::Udp, Layer::Udp(_)) |
+            (Protocol::Icmp, Layer::Icmp(_)) | (Protocol::Icmpv6, Layer::Icmpv6(_))
+        )
+    }
+}
+
+// Name, protocol, offset in the layer and mask of the bits of every field which can be ignored
+const FIELDS: &[(&str, Protocol, usize, &[u8])] = &[
+    ("eth.dst", Protocol::Ethernet, 0, &[0xFF; 6]),
+    ("eth.src", Protocol::Ethernet, 6, &[0xFF; 6]),
+    ("eth.type", Protocol::Ethernet, 12, &[0xFF; 2]),
+    ("vlan.priority", Protocol::Vlan, 0, &[0xE0]),
+    ("vlan.id", Protocol::Vlan, 0, &[0x0F, 0xFF]),
+    ("ip.dsfield", Protocol::Ipv4, 1, &[0xFF]),
+    ("ip.dsfield.dscp", Protocol::Ipv4, 1, &[0xFC]),
+    ("ip.dsfield.ecn", Protocol::Ipv4, 1, &[0x03]),
+    ("ip.len", Protocol::Ipv4, 2, &[0xFF; 2]),
+    ("ip.id", Protocol::Ipv4, 4, &[0xFF; 2]),
+    ("ip.flags", Protocol::Ipv4, 6, &[0xE0]),
+    ("ip.frag_offset", Protocol::Ipv4, 6, &[0x1F, 0xFF]),
+    ("ip.ttl", Protocol::Ipv4, 8, &[0xFF]),
+    ("ip.proto", Protocol::Ipv4, 9, &[0xFF]),
+    ("ip.checksum", Protocol::Ipv4, 10, &[0xFF; 2]),
+    ("ip.src", Protocol::Ipv4, 12, &[0xFF; 4]),
+    ("ip.dst", Protocol::Ipv4, 16, &[0xFF; 4]),
+    ("ipv6.tclass", Protocol::Ipv6, 0, &[0x0F, 0xF0]),
+    ("ipv6.flow", Protocol::Ipv6, 1, &[0x0F, 0xFF, 0xFF]),
+    ("ipv6.plen", Protocol::Ipv6, 4, &[0xFF; 2]),
+    ("ipv6.nxt", Protocol::Ipv6, 6, &[0xFF]),
+    ("ipv6.hlim", Protocol::Ipv6, 7, &[0xFF]),
+    ("ipv6.src", Protocol::Ipv6, 8, &[0xFF; 16]),
+    ("ipv6.dst", Protocol::Ipv6, 24, &[0xFF; 16]),
+    ("tcp.srcport", Protocol::Tcp, 0, &[0xFF; 2]),
+    ("tcp.dstport", Protocol::Tcp, 2, &[0xFF; 2]),
+    ("tcp.seq", Protocol::Tcp, 4, &[0xFF; 4]),
+    ("tcp.ack", Protocol::Tcp, 8, &[0xFF; 4]),
+    ("tcp.flags", Protocol::Tcp, 12, &[0x01, 0xFF]),
+    ("tcp.window_size", Protocol::Tcp, 14, &[0xFF; 2]),
+    ("tcp.checksum", Protocol::Tcp, 16, &[0xFF; 2]),
+    ("tcp.urgent_pointer", Protocol::Tcp, 18, &[0xFF; 2]),
+    ("udp.srcport", Protocol::Udp, 0, &[0xFF; 2]),
+    ("udp.dstport", Protocol::Udp, 2, &[0xFF; 2]),
+    ("udp.length", Protocol::Udp, 4, &[0xFF; 2]),
+    ("udp.checksum", Protocol::Udp, 6, &[0xFF; 2]),
+    ("icmp.type", Protocol::Icmp, 0, &[0xFF]),
+    ("icmp.code", Protocol::Icmp, 1, &[0xFF]),
+    ("icmp.checksum", Protocol::Icmp, 2, &[0xFF; 2]),
+    ("icmpv6.type", Protocol::Icmpv6, 0, &[0xFF]),
+    ("icmpv6.code", Protocol::Icmpv6, 1, &[0xFF]),
+    ("icmpv6.checksum", Protocol::Icmpv6, 2, &[0xFF; 2])
+];
+
+/// Part of the packets left out of the comparison of a `PacketComparator`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum IgnoreRule {
+
+    /// Header field named like the Wireshark display filter fields, ignored in every layer
+    /// of its protocol, e.g. in the inner and outer IPv4 headers of a tunnel
+    Field(&'static str),
+
+    /// Bits of the packet data set in `mask`, starting at the byte `offset`
+    Mask {
+        offset: usize,
+        mask: Vec<u8>
+    }
+}
+
+/// Options of a comparison as the users write them, the `[compare]` table of a `Scenario` or the
+/// options of the `compare` command, which build the `HeaderComparison` and the `PacketComparator`.
+///
+/// The timestamps are checked when they have a tolerance or are relative, and the interface
+/// indexes when they're mapped.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use std::time::Duration;
+/// use pcap_assistant::DataLink;
+/// use pcap_assistant::pcap_assistant::assistant::{ComparisonOptions, IgnoreRule};
+///
+/// let options = ComparisonOptions {
+///     ignore: vec![IgnoreRule::parse("ip.ttl").unwrap()],
+///     timestamp_tolerance: Some(Duration::from_millis(1)),
+///     interface_map: vec![(1, 2)],
+///     ..Default::default()
+/// };
+/// let comparator = options.comparator(DataLink::ETHERNET);
+/// ```
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ComparisonOptions {
+
+    /// Ignored fields and byte masks, see `IgnoreRule::parse`
+    #[serde(deserialize_with = "deserialize_ignore")]
+    pub ignore: Vec<IgnoreRule>,
+
+    #[serde(rename = "match", deserialize_with = "deserialize_strategy")]
+    pub strategy: MatchStrategy,
+
+    /// Every header field is checked, see `HeaderComparison::exact`
+    pub check_headers: bool,
+
+    /// The timestamps are checked with this tolerance, in seconds in a scenario
+    #[serde(deserialize_with = "deserialize_tolerance")]
+    pub timestamp_tolerance: Option<Duration>,
+
+    /// The timestamps are checked from the first packet of each file
+    pub relative_timestamps: bool,
+
+    /// The interface indexes are checked, the left index mapped to the right one, written
+    /// `<lhs>=<rhs>` in a scenario
+    #[serde(rename = "map_interface", deserialize_with = "deserialize_interface_map")]
+    pub interface_map: Vec<(u32, u32)>
+}
+
+impl ComparisonOptions {
+
+    /// Header comparison of the options.
+    pub fn header_comparison(&self) -> HeaderComparison {
+
+        let mut headers = match self.check_headers {
+            true => HeaderComparison::exact(),
+            false => HeaderComparison::default()
+        };
+        let mut check = |field: HeaderField| {
+            if !headers.checked.contains(&field) {
+                headers.checked.push(field);
+            }
+        };
+
+        if self.timestamp_tolerance.is_some() || self.relative_timestamps {
+            check(HeaderField::TsSec);
+            check(HeaderField::TsNsec);
+        }
+        if !self.interface_map.is_empty() {
+            check(HeaderField::InterfaceIndex);
+        }
+
+        headers.timestamp_tolerance = self.timestamp_tolerance.unwrap_or_default();
+        headers.relative_timestamps = self.relative_timestamps;
+        headers.interface_map.extend(self.interface_map.iter().copied());
+        headers
+    }
+
+    /// Comparator of the options for captures of the given `DataLink`.
+    pub fn comparator(&self, datalink: DataLink) -> PacketComparator {
+
+        self.ignore.iter().cloned()
+            .fold(PacketComparator::new(datalink), PacketComparator::with)
+            .matching(self.strategy)
+            .headers(self.header_comparison())
+    }
+}
+
+/// Parse a number of seconds, e.g. `0.5` for 500 ms.
+///
+/// # Errors
+///
+/// Returns `PcapError::InvalidField` if it isn't a positive number.
+pub fn parse_seconds(seconds: &str) -> ResultParsing<Duration> {
+
+    seconds.parse::<f64>().ok().and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+        .ok_or(PcapError::InvalidField("Duration invalid number of seconds"))
+}
+
+/// Parse an interface mapping written `<lhs>=<rhs>`, e.g. `1=2` for the left interface 1 captured as 2 on the right.
+///
+/// # Errors
+///
+/// Returns `PcapError::InvalidField` if it isn't two interface indexes.
+pub fn parse_interface_mapping(mapping: &str) -> ResultParsing<(u32, u32)> {
+
+    mapping.split_once('=').and_then(|(lhs, rhs)| Some((lhs.parse().ok()?, rhs.parse().ok()?)))
+        .ok_or(PcapError::InvalidField("HeaderComparison interface mapping isn't <lhs>=<rhs>"))
+}
+
+// Ignore rules of an array of strings
+fn deserialize_ignore<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<IgnoreRule>, D::Error> {
+
+    Vec::<String>::deserialize(deserializer)?.iter()
+        .map(|rule| IgnoreRule::parse(rule).map_err(|err| D::Error::custom(format!("ignore '{}': {}", rule, err))))
+        .collect()
+}
+
+fn deserialize_strategy<'de, D: Deserializer<'de>>(deserializer: D) -> Result<MatchStrategy, D::Error> {
+
+    let strategy = String::deserialize(deserializer)?;
+    strategy.parse().map_err(|err| D::Error::custom(format!("match '{}': {}", strategy, err)))
+}
+
+// Tolerance of an integer or a float number of seconds
+fn deserialize_tolerance<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Duration>, D::Error> {
+
+    let seconds = f64::deserialize(deserializer)?;
+    Duration::try_from_secs_f64(seconds).map(Some)
+        .map_err(|_| D::Error::custom("timestamp_tolerance must be a positive number of seconds"))
+}
+
+// Interface mappings of an array of `<lhs>=<rhs>` strings
+fn deserialize_interface_map<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<(u32, u32)>, D::Error> {
+
+    Vec::<String>::deserialize(deserializer)?.iter()
+        .map(|mapping| parse_interface_mapping(mapping).map_err(|err| D::Error::custom(format!("map_interface '{}': {}", mapping, err))))
+        .collect()
+}
+
+impl IgnoreRule {
+
+    /// Parse a field name, e.g. `ip.ttl`, or a byte mask written `<offset>:<hex mask>`, e.g. `22:ffff`
+    /// to ignore the bytes 22 and 23 or `15:03` to ignore the ECN bits of an IPv4 header after Ethernet.
+    ///
+    /// Supported fields are the addresses, ports, lengths, checksums and other fixed fields of the
+    /// `eth`, `vlan`, `ip`, `ipv6`, `tcp`, `udp`, `icmp` and `icmpv6` headers, e.g. `eth.src`, `vlan.id`,
+    /// `ip.id`, `ip.ttl`, `ip.checksum`, `ip.dsfield.ecn`, `ipv6.hlim`, `ipv6.flow`, `tcp.seq` or `tcp.checksum`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `PcapError::InvalidField` if the field is unknown or if the mask can't be parsed.
+    pub fn parse(rule: &str) -> ResultParsing<IgnoreRule> {
+
+        if let Some((name, ..)) = FIELDS.iter().find(|(name, ..)| *name == rule) {
+            return Ok(IgnoreRule::Field(name));
+        }
+
+        let (offset, mask) = rule.split_once(':').ok_or(PcapError::InvalidField("IgnoreRule unknown field"))?;
+        let offset = offset.parse().map_err(|_| PcapError::InvalidField("IgnoreRule invalid mask offset"))?;
+        let mask = hex::decode(mask).map_err(|_| PcapError::InvalidField("IgnoreRule invalid mask"))?;
+
+        Ok(IgnoreRule::Mask { offset, mask })
+    }
+
+    // Set the bits ignored in the dissected packet in `ignored`, a mask as long as the packet
+    fn apply(&self, dissection: &Dissection, ignored: &mut [u8]) {
+
+        match self {
+            IgnoreRule::Field(name) => {
+                let Some((_, protocol, offset, mask)) = FIELDS.iter().find(|(field, ..)| field == name) else {
+                    return;
+                };
+
+                for (start, _) in dissection.layers.iter().filter(|(_, layer)| protocol.matches(layer)) {
+                    add_mask(ignored, start + offset, mask);
+                }
+            },
+            IgnoreRule::Mask { offset, mask } => add_mask(ignored, *offset, mask)
+        }
+    }
+}
+
+// Bits of `mask` set in `ignored` from `offset`, cut at the end of the packet
+fn add_mask(ignored: &mut [u8], offset: usize, mask: &[u8]) {
+
+    for (bits, mask) in ignored.iter_mut().skip(offset).zip(mask) {
+        *bits |= mask;
+    }
+}
+
+/// Comparison of the packet headers of a `PacketComparator`.
+///
+/// Every header difference is reported, but only the ones of the `checked` fields make the packets
+/// different, see `HeaderDifference::checked`. By default no field is checked, the timestamps are
+/// compared exactly and the interface indexes are expected to be the same.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use std::time::Duration;
+/// use pcap_assistant::pcap_assistant::assistant::{HeaderComparison, HeaderField};
+///
+/// // Same interface mapping and pacing as the expected capture, within 100 µs
+/// let headers = HeaderComparison {
+///     checked: vec![HeaderField::TsSec, HeaderField::TsNsec, HeaderField::InterfaceIndex],
+///     timestamp_tolerance: Duration::from_micros(100),
+///     relative_timestamps: true,
+///     interface_map: [(1, 2), (2, 1)].into_iter().collect()
+/// };
+/// ```
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct HeaderComparison {
+
+    /// Fields whose difference makes the packets different
+    pub checked: Vec<HeaderField>,
+
+    /// Largest difference between the timestamps of two packets which isn't reported
+    pub timestamp_tolerance: Duration,
+
+    /// Timestamps are compared from the first packet of each file, to compare the pacing
+    /// of captures taken at different times
+    pub relative_timestamps: bool,
+
+    /// Interface index expected for the right packet, by interface index of the left packet,
+    /// when it isn't the same
+    pub interface_map: HashMap<u32, u32>
+}
+
+impl HeaderComparison {
+
+    /// Every field checked, with exact timestamps.
+    pub fn exact() -> HeaderComparison {
+
+        let checked = vec![HeaderField::TsSec, HeaderField::TsNsec, HeaderField::InclLen, HeaderField::OrigLen, HeaderField::InterfaceIndex];
+        HeaderComparison { checked, ..Default::default() }
+    }
+
+    /// Differences between two headers, `origins` are the timestamps of the first packets of the left
+    /// and right files, used when the timestamps are relative.
+    ///
+    /// Timestamp differences are the relative timestamps when they're relative, and the interface
+    /// difference has the expected interface index on the left. The interface index is only
+    /// compared when both formats record it.
+    pub fn differences<L: SomePacketHeader, R: SomePacketHeader>(&self, origins: (Duration, Duration), lhs: &L, rhs: &R) -> Vec<HeaderDifference> {
+
+        let (mut lhs_time, mut rhs_time) = (lhs.timestamp(), rhs.timestamp());
+        if self.relative_timestamps {
+            (lhs_time, rhs_time) = (lhs_time.saturating_sub(origins.0), rhs_time.saturating_sub(origins.1));
+        }
+
+        let mut fields = Vec::new();
+        if lhs_time.abs_diff(rhs_time) > self.timestamp_tolerance {
+            fields.push((HeaderField::TsSec, lhs_time.as_secs() as u32, rhs_time.as_secs() as u32));
+            fields.push((HeaderField::TsNsec, lhs_time.subsec_nanos(), rhs_time.subsec_nanos()));
+        }
+        fields.push((HeaderField::InclLen, lhs.incl_len(), rhs.incl_len()));
+        fields.push((HeaderField::OrigLen, lhs.orig_len(), rhs.orig_len()));
+
+        if let (Some(lhs_index), Some(rhs_index)) = (lhs.interface_index(), rhs.interface_index()) {
+            let expected = self.interface_map.get(&lhs_index).copied().unwrap_or(lhs_index);
+            fields.push((HeaderField::InterfaceIndex, expected, rhs_index));
+        }
+
+        fields.into_iter()
+            .filter(|(_, lhs, rhs)| lhs != rhs)
+            .map(|(field, lhs, rhs)| HeaderDifference { field, lhs, rhs, checked: self.checked.contains(&field) })
+            .collect()
+    }
+}
+
+/// Comparison of packets leaving out the fields which are expected to differ, e.g. the TTL
+/// decremented or the checksums rewritten by a router.
+///
+/// Both packets are dissected and the bits ignored in either of them are cleared in both before
+/// they're compared, so the first mismatch of the `PacketOutcome` is in another field, which reports
+/// the original data of the packets. Without rules, the packets data are compared byte for byte.
+///
+/// Packets of the compared files are paired by index, or by another `MatchStrategy`. Their headers
+/// are compared as set by the `HeaderComparison`.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use pcap_assistant::DataLink;
+/// use pcap_assistant::pcap_assistant::assistant::{HeaderComparison, IgnoreRule, MatchStrategy, PacketComparator, PcapTester};
+/// use pcap_assistant::pcap::Packet;
+///
+/// let comparator = PacketComparator::new(DataLink::ETHERNET)
+///     .ignore("ip.ttl").unwrap()
+///     .ignore("ip.checksum").unwrap()
+///     .with(IgnoreRule::Mask { offset: 0, mask: vec![0xFF; 6] })
+///     .matching(MatchStrategy::Alignment)
+///     .headers(HeaderComparison::exact());
+///
+/// let env = PcapTester::new("input.pcap");
+/// let report = env.compare_files_with::<Packet, Packet>("routed.pcap", &comparator).unwrap();
+/// assert!(report.is_equal());
+/// ```
+#[derive(Clone, Debug)]
+pub struct PacketComparator {
+    datalink: DataLink,
+    rules: Vec<IgnoreRule>,
+    strategy: MatchStrategy,
+    headers: HeaderComparison
+}
+
+impl PacketComparator {
+
+    /// Creates a `PacketComparator` without rules for packets captured with the given `DataLink`.
+    pub fn new(datalink: DataLink) -> PacketComparator {
+
+        PacketComparator { datalink, rules: Vec::new(), strategy: MatchStrategy::Index, headers: HeaderComparison::default() }
+    }
+
+    /// Adds an ignore rule.
+    pub fn with(mut self, rule: IgnoreRule) -> PacketComparator {
+
+        self.rules.push(rule);
+        self
+    }
+
+    /// Adds an ignore rule by field name or byte mask, see `IgnoreRule::parse`.
+    pub fn ignore(self, rule: &str) -> ResultParsing<PacketComparator> {
+
+        Ok(self.with(IgnoreRule::parse(rule)?))
+    }
+
+    /// Sets how the packets of the compared files are paired.
+    pub fn matching(mut self, strategy: MatchStrategy) -> PacketComparator {
+
+        self.strategy = strategy;
+        self
+    }
+
+    /// Sets how the packet headers are compared.
+    pub fn headers(mut self, headers: HeaderComparison) -> PacketComparator {
+
+        self.headers = headers;
+        self
+    }
+
+    pub fn datalink(&self) -> DataLink {
+        self.datalink
+    }
+
+    pub fn rules(&self) -> &[IgnoreRule] {
+        &self.rules
+    }
+
+    pub fn strategy(&self) -> MatchStrategy {
+        self.strategy
+    }
+
+    pub fn header_comparison(&self) -> &HeaderComparison {
+        &self.headers
+    }
+
+    /// Compare the headers and data of two packets, leaving out the ignored bits.
+    ///
+    /// Relative timestamps are compared from 0, see `compare_from` for the packets of two files.
+    pub fn compare<L: SomePacketHeader, R: SomePacketHeader>(&self, index: usize, lhs_header: &L, lhs: &[u8], rhs_header: &R, rhs: &[u8]) -> PacketComparison {
+        self.compare_from((Duration::ZERO, Duration::ZERO), index, lhs_header, lhs, rhs_header, rhs)
+    }
+
+    /// Compare two packets like `compare`, `origins` are the timestamps of the first packets of the left
+    /// and right files, see `HeaderComparison::differences`.
+    pub fn compare_from<L: SomePacketHeader, R: SomePacketHeader>(&self, origins: (Duration, Duration), index: usize, lhs_header: &L, lhs: &[u8], rhs_header: &R, rhs: &[u8]) -> PacketComparison {
+
+        let mut comparison = match self.rules.is_empty() {
+            true => PacketComparison::new(index, lhs_header, lhs, rhs_header, rhs),
+            false => {
+                // Each packet is dissected with its own layout, and the bits ignored in one are ignored in both
+                let (mut ignored, rhs_ignored) = (self.ignored_bits(lhs), self.ignored_bits(rhs));
+                ignored.resize(lhs.len().max(rhs.len()), 0);
+                for (bits, rhs_bits) in ignored.iter_mut().zip(rhs_ignored) {
+                    *bits |= rhs_bits;
+                }
+
+                // The cleared copies are only compared, the outcome reports the original data
+                let mut comparison = PacketComparison::new(index, lhs_header, &clear_bits(lhs, &ignored), rhs_header, &clear_bits(rhs, &ignored));
+                if let PacketOutcome::Different { lhs: cleared_lhs, rhs: cleared_rhs, .. } = &mut comparison.outcome {
+                    (*cleared_lhs, *cleared_rhs) = (lhs.to_vec(), rhs.to_vec());
+                }
+                comparison
+            }
+        };
+
+        comparison.header_differences = self.headers.differences(origins, lhs_header, rhs_header);
+        comparison
+    }
+
+    /// Hash of the data of a packet without its ignored bits, to find the packets which may compare equal.
+    pub fn content_hash(&self, data: &[u8]) -> u64 {
+
+        let mut hasher = DefaultHasher::new();
+        match self.rules.is_empty() {
+            true => data.hash(&mut hasher),
+            false => clear_bits(data, &self.ignored_bits(data)).hash(&mut hasher)
+        }
+
+        hasher.finish()
+    }
+
+    /// Mask of the bits of `data` left out of the comparison, as long as `data`.
+    pub fn ignored_bits(&self, data: &[u8]) -> Vec<u8> {
+
+        let dissection = dissect(self.datalink, data);
+        let mut ignored = vec![0; data.len()];
+
+        for rule in &self.rules {
+            rule.apply(&dissection, &mut ignored);
+        }
+
+        ignored
+    }
+}
+
+// Data without the bits set in `ignored`
+fn clear_bits(data: &[u8], ignored: &[u8]) -> Vec<u8> {
+    data.iter().enumerate().map(|(offset, byte)| byte & !ignored.get(offset).copied().unwrap_or(0)).collect()
+}
+
+impl Default for PacketComparator {
+
+    /// Byte for byte comparison, without rules.
+    fn default() -> PacketComparator {
+        PacketComparator::new(DataLink::ETHERNET)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pcap::{PacketHeader, VppPacketHeader};
+
+    // Ethernet, IPv4 and UDP headers followed by 4 bytes of payload
+    fn packet(ttl: u8, checksum: u16, payload: u8) -> Vec<u8> {
+        let mut data = vec![0x02, 0, 0, 0, 0, 0x01, 0x02, 0, 0, 0, 0, 0x02, 0x08, 0x00];
+        data.extend_from_slice(&[0x45, 0, 0, 32, 0, 1, 0, 0, ttl, 17]);
+        data.extend_from_slice(&checksum.to_be_bytes());
+        data.extend_from_slice(&[10, 0, 0, 1, 10, 0, 0, 2]);
+        data.extend_from_slice(&[0x30, 0x39, 0x00, 0x35, 0, 12, 0, 0, payload, 0, 0, 0]);
+        data
+    }
+
+    fn compare(comparator: &PacketComparator, lhs: &[u8], rhs: &[u8]) -> PacketOutcome {
+        let header = PacketHeader::new(0, 0, lhs.len() as u32, lhs.len() as u32);
+        comparator.compare(0, &header, lhs, &header, rhs).outcome
+    }
+
+    #[test]
+    fn packet_comparator_test() {
+        let (lhs, routed) = (packet(64, 0x1234, 1), packet(63, 0x1334, 1));
+
+        let strict = PacketComparator::new(DataLink::ETHERNET);
+        assert!(matches!(compare(&strict, &lhs, &routed), PacketOutcome::Different { first_mismatch: 22, .. }));
+
+        let comparator = PacketComparator::new(DataLink::ETHERNET).ignore("ip.ttl").unwrap().ignore("ip.checksum").unwrap();
+        assert_eq!(compare(&comparator, &lhs, &routed), PacketOutcome::Equal);
+
+        // Differences in other fields are still reported, with the original data
+        match compare(&comparator, &lhs, &packet(63, 0x1334, 2)) {
+            PacketOutcome::Different { first_mismatch, lhs, rhs } => {
+                assert_eq!(first_mismatch, 42);
+                assert_eq!((lhs[22], rhs[22], &lhs[24..26], &rhs[24..26]), (64, 63, &[0x12, 0x34][..], &[0x13, 0x34][..]));
+            },
+            outcome => panic!("{:?}", outcome)
+        }
+
+        // Both packets are dissected with their own layout, the longer right packet has a trailer
+        let tagged = |vid: u8, ttl: u8, checksum: u16| {
+            let mut data = packet(ttl, checksum, 1);
+            data.splice(12..12, [0x81, 0x00, 0x00, vid]);
+            data
+        };
+        let mut trailer = tagged(6, 62, 0x1434);
+        trailer.extend_from_slice(&[0xEE, 0xEE]);
+        let comparator = PacketComparator::new(DataLink::ETHERNET).ignore("vlan.id").unwrap().ignore("ip.ttl").unwrap().ignore("ip.checksum").unwrap();
+        assert_eq!(compare(&comparator, &tagged(5, 64, 0x1234), &tagged(6, 62, 0x1434)), PacketOutcome::Equal);
+        assert_eq!(compare(&comparator, &tagged(5, 64, 0x1234), &trailer), PacketOutcome::Different { first_mismatch: 50, lhs: tagged(5, 64, 0x1234), rhs: trailer });
+
+        // Byte mask of the TTL and field of the source MAC
+        let mut rewritten = packet(63, 0x1234, 1);
+        rewritten[6..12].copy_from_slice(&[0x02, 0xAA, 0xBB, 0xCC, 0xDD, 0xEE]);
+        let comparator = PacketComparator::new(DataLink::ETHERNET).ignore("22:ff").unwrap().ignore("eth.src").unwrap();
+        assert_eq!(compare(&comparator, &lhs, &rewritten), PacketOutcome::Equal);
+        assert_eq!(comparator.ignored_bits(&lhs)[6..12], [0xFF; 6]);
+
+        // Fields of a layer which isn't in the packet are compared
+        let comparator = PacketComparator::new(DataLink::ETHERNET).ignore("tcp.checksum").unwrap();
+        let mut udp_checksum = lhs.clone();
+        udp_checksum[40] = 0xFF;
+        assert!(matches!(compare(&comparator, &lhs, &udp_checksum), PacketOutcome::Different { first_mismatch: 40, .. }));
+
+        assert_eq!(IgnoreRule::parse("ip.id").unwrap(), IgnoreRule::Field("ip.id"));
+        assert_eq!(IgnoreRule::parse("15:03").unwrap(), IgnoreRule::Mask { offset: 15, mask: vec![0x03] });
+        assert!(matches!(IgnoreRule::parse("ip.foo"), Err(PcapError::InvalidField("IgnoreRule unknown field"))));
+        assert!(IgnoreRule::parse("15:zz").is_err());
+    }
+
+    #[test]
+    fn header_comparison_test() {
+        let vpp_header = |ts_sec: u32, ts_nsec: u32, interface_index: u32| {
+            let mut header = VppPacketHeader::new(ts_sec, ts_nsec, 4, 4);
+            header.interface_index = interface_index;
+            header
+        };
+        let (lhs, rhs) = (vpp_header(10, 500, 1), vpp_header(20, 700, 2));
+        let origins = (Duration::from_secs(9), Duration::from_secs(19));
+
+        // Differences are informative by default
+        let comparison = PacketComparator::default().compare(0, &lhs, &[1], &rhs, &[1]);
+        assert_eq!(comparison.header_differences.len(), 3);
+        assert!(comparison.is_equal());
+
+        let comparator = PacketComparator::default().headers(HeaderComparison::exact());
+        let comparison = comparator.compare(0, &lhs, &[1], &rhs, &[1]);
+        assert!(!comparison.is_equal() && comparison.has_header_mismatch());
+        assert_eq!(comparison.outcome, PacketOutcome::Equal);
+
+        let pacing = HeaderComparison {
+            checked: vec![HeaderField::TsSec, HeaderField::TsNsec, HeaderField::InterfaceIndex],
+            timestamp_tolerance: Duration::from_nanos(200),
+            relative_timestamps: true,
+            interface_map: [(1, 2)].into_iter().collect()
+        };
+        assert_eq!(pacing.differences(origins, &lhs, &rhs), vec![]);
+        assert_eq!(pacing.differences(origins, &lhs, &vpp_header(20, 701, 1)), vec![
+            HeaderDifference { field: HeaderField::TsNsec, lhs: 500, rhs: 701, checked: true },
+            HeaderDifference { field: HeaderField::InterfaceIndex, lhs: 2, rhs: 1, checked: true }
+        ]);
+
+        // Lengths aren't checked, and the interface index is only compared when both formats record it
+        let packet_header = PacketHeader::new(1, 700, 8, 8);
+        let differences = pacing.differences((Duration::from_secs(1), Duration::ZERO), &packet_header, &rhs);
+        assert_eq!(differences.iter().map(|difference| (difference.field, difference.checked)).collect::<Vec<_>>(), vec![
+            (HeaderField::TsSec, true), (HeaderField::InclLen, false), (HeaderField::OrigLen, false)
+        ]);
+        // Same header comparison from the options of the users
+        let options = ComparisonOptions {
+            timestamp_tolerance: Some(parse_seconds("0.0000002").unwrap()),
+            relative_timestamps: true,
+            interface_map: vec![parse_interface_mapping("1=2").unwrap()],
+            ..Default::default()
+        };
+        assert_eq!(options.header_comparison(), pacing);
+        assert_eq!(ComparisonOptions { check_headers: true, ..options }.header_comparison().checked.len(), 5);
+        assert!(parse_interface_mapping("1-2").is_err() && parse_seconds("-1").is_err());
+    }
+}
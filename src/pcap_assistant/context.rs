@@ -0,0 +1,160 @@
+use crate::pcap::SomePacketHeader;
+use crate::pcap_assistant::assistant::PacketProcessor;
+use crate::DataLink;
+
+
+/// Header fields and position of the packet given to a `ContextProcessor`.
+///
+/// Changes made by the processor are saved in the header of the processed packet.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct PacketContext {
+
+    /// Index of the packet in the processed file
+    pub index: usize,
+    pub ts_sec: u32,
+    pub ts_nsec: u32,
+
+    /// Captured length of the packet given to the processor, the header of the processed packet has the processed data length
+    pub incl_len: u32,
+
+    /// Original length of the packet, the processed data length is saved if it is left unchanged
+    pub orig_len: u32,
+
+    /// Link type of the processed file
+    pub datalink: DataLink,
+
+    /// VPP interface index, `None` for classic packets
+    pub interface_index: Option<u32>
+}
+
+impl PacketContext {
+
+    /// Creates the context of the packet at `index` with the given header.
+    pub fn new<H: SomePacketHeader>(index: usize, datalink: DataLink, header: &H) -> PacketContext {
+
+        PacketContext {
+            index,
+            ts_sec: header.ts_sec(),
+            ts_nsec: header.ts_nsec(),
+            incl_len: header.incl_len(),
+            orig_len: header.orig_len(),
+            datalink,
+            interface_index: header.interface_index()
+        }
+    }
+
+    /// Creates the header of the processed packet, `initial` is the context given to the processor.
+    pub(crate) fn to_header<H: SomePacketHeader>(self, initial: &PacketContext, data_len: usize) -> H {
+
+        let orig_len = if self.orig_len == initial.orig_len { data_len as u32 } else { self.orig_len };
+        let mut header = H::new(self.ts_sec, self.ts_nsec, data_len as u32, orig_len);
+
+        if let Some(interface_index) = self.interface_index {
+            header.set_interface_index(interface_index);
+        }
+
+        header
+    }
+}
+
+/// Trait for packet processors which need the context of the packet.
+///
+/// Every `PacketProcessor` is a `ContextProcessor` ignoring the context.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use pcap_assistant::pcap::VppPacket;
+/// use pcap_assistant::pcap_assistant::assistant::{ContextProcessor, PacketContext, PcapTester};
+///
+/// // Keep the packets received on the interface 3 during the first 10 seconds
+/// struct Interface3;
+///
+/// impl ContextProcessor for Interface3 {
+///     fn process_with_context(&mut self, context: &mut PacketContext, _: &mut Vec<u8>) -> Result<bool, String> {
+///         Ok(context.interface_index == Some(3) && context.ts_sec < 10)
+///     }
+/// }
+///
+/// let env = PcapTester::new("vpp_netinfo.pcap");
+/// env.process_and_save::<VppPacket>("interface3.pcap", &mut Interface3).unwrap();
+/// ```
+pub trait ContextProcessor {
+
+    /// Processes the packet (`Vec<u8>`) and its context, returns false if the packet must be dropped and true otherwise.
+    ///
+    /// Returns an error message when the packet can't be processed.
+    fn process_with_context(&mut self, context: &mut PacketContext, packet: &mut Vec<u8>) -> Result<bool, String>;
+}
+
+impl<T: PacketProcessor + ?Sized> ContextProcessor for T {
+
+    fn process_with_context(&mut self, _context: &mut PacketContext, packet: &mut Vec<u8>) -> Result<bool, String> {
+        self.try_process_packet(packet)
+    }
+}
+
+/// Packet emitted by an `EmittingProcessor`, with its own context.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ProcessedPacket {
+    pub context: PacketContext,
+    pub data: Vec<u8>
+}
+
+impl ProcessedPacket {
+
+    pub fn new(context: PacketContext, data: Vec<u8>) -> ProcessedPacket {
+
+        ProcessedPacket { context, data }
+    }
+}
+
+/// Trait for packet processors which emit zero, one or many packets per processed packet
+/// (fragmentation, duplication, segmentation, tunnel splitting...).
+///
+/// Every `ContextProcessor` is an `EmittingProcessor` emitting the processed packet unless it is dropped.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use pcap_assistant::pcap::Packet;
+/// use pcap_assistant::pcap_assistant::assistant::{EmittingProcessor, PacketContext, PcapTester, ProcessedPacket};
+///
+/// // Split the packets in chunks of 64 bytes, 1 microsecond apart
+/// struct Splitter;
+///
+/// impl EmittingProcessor for Splitter {
+///     fn emit_packets(&mut self, context: PacketContext, packet: Vec<u8>) -> Result<Vec<ProcessedPacket>, String> {
+///         Ok(packet.chunks(64).enumerate().map(|(i, chunk)| {
+///             let mut context = context;
+///             context.ts_nsec += i as u32 * 1000;
+///             ProcessedPacket::new(context, chunk.to_vec())
+///         }).collect())
+///     }
+/// }
+///
+/// let env = PcapTester::new("netinfo.pcap");
+/// env.process_and_save::<Packet>("split.pcap", &mut Splitter).unwrap();
+/// ```
+pub trait EmittingProcessor {
+
+    /// Processes the packet (`Vec<u8>`) and its context, returns the packets to emit in order,
+    /// none if the packet must be dropped.
+    ///
+    /// The header of each emitted packet is made from its context, as for `ContextProcessor`.
+    /// Returns an error message when the packet can't be processed.
+    fn emit_packets(&mut self, context: PacketContext, packet: Vec<u8>) -> Result<Vec<ProcessedPacket>, String>;
+}
+
+impl<T: ContextProcessor + ?Sized> EmittingProcessor for T {
+
+    fn emit_packets(&mut self, mut context: PacketContext, mut packet: Vec<u8>) -> Result<Vec<ProcessedPacket>, String> {
+
+        if self.process_with_context(&mut context, &mut packet)? {
+            Ok(vec![ProcessedPacket::new(context, packet)])
+        }
+        else {
+            Ok(Vec::new())
+        }
+    }
+}
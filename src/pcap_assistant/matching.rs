@@ -0,0 +1,338 @@
+use crate::pcap::{SomePacket, SomePacketHeader};
+use crate::pcap_assistant::compare::{parse_seconds, PacketComparator};
+use crate::pcap_assistant::report::{ComparisonReport, PacketComparison};
+use crate::PcapError;
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use std::time::Duration;
+
+
+/// How the packets of two compared files are paired, see `PacketComparator::matching`.
+///
+/// Whatever the strategy, the paired packets are compared by the `PacketComparator`, the unpaired
+/// packets of the left file are `PacketOutcome::MissingRight` and the ones of the right file
+/// `PacketOutcome::MissingLeft`.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum MatchStrategy {
+
+    /// Packets at the same index are paired, so a dropped packet makes every later packet differ
+    #[default]
+    Index,
+
+    /// Packets are aligned on their longest common subsequence, as an edit script: between two
+    /// common packets, the remaining packets are paired in order as modified packets and the others
+    /// are deleted (only left) or inserted (only right)
+    Alignment,
+
+    /// Every left packet is paired with the first unpaired right packet of the same content whose timestamp
+    /// is within `window` of its own, or anywhere in the file without window, so the packets may be reordered
+    Multiset {
+        window: Option<Duration>
+    }
+}
+
+/// Parse `index`, `alignment`, `multiset` or `multiset:<seconds>`, e.g. `multiset:0.5` for a window of 500 ms.
+impl FromStr for MatchStrategy {
+
+    type Err = PcapError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+
+        match s.split_once(':') {
+            None if s == "index" => Ok(MatchStrategy::Index),
+            None if s == "alignment" => Ok(MatchStrategy::Alignment),
+            None if s == "multiset" => Ok(MatchStrategy::Multiset { window: None }),
+            Some(("multiset", seconds)) => {
+                let window = parse_seconds(seconds).map_err(|_| PcapError::InvalidField("MatchStrategy invalid window"))?;
+                Ok(MatchStrategy::Multiset { window: Some(window) })
+            },
+            _ => Err(PcapError::InvalidField("MatchStrategy unknown strategy"))
+        }
+    }
+}
+
+/// Pair the packets of both files with the strategy of `comparator` and compare the pairs.
+pub(crate) fn match_packets<'l, 'r, LP, RP>(lhs: &[LP], rhs: &[RP], comparator: &PacketComparator) -> ComparisonReport
+    where LP: SomePacket<'l>,
+          RP: SomePacket<'r>
+{
+    let origins = match (lhs.first(), rhs.first()) {
+        (Some(first_lhs), Some(first_rhs)) => (first_lhs.get_header().timestamp(), first_rhs.get_header().timestamp()),
+        _ => (Duration::ZERO, Duration::ZERO)
+    };
+
+    let compare = |index: usize, rhs_index: usize| {
+        let (packet_lhs, packet_rhs) = (&lhs[index], &rhs[rhs_index]);
+        let mut comparison = comparator.compare_from(origins, index, &packet_lhs.get_header(), packet_lhs.get_data(), &packet_rhs.get_header(), packet_rhs.get_data());
+        comparison.rhs_index = Some(rhs_index);
+        comparison
+    };
+
+    let mut report = ComparisonReport::default();
+    let packets = &mut report.packets;
+
+    match comparator.strategy() {
+        MatchStrategy::Index => {
+            for index in 0..lhs.len().max(rhs.len()) {
+                packets.push(match (index < lhs.len(), index < rhs.len()) {
+                    (true, true) => comparator.compare_from(origins, index, &lhs[index].get_header(), lhs[index].get_data(), &rhs[index].get_header(), rhs[index].get_data()),
+                    (true, false) => PacketComparison::missing_right(index),
+                    _ => PacketComparison::missing_left(index)
+                });
+            }
+        },
+        MatchStrategy::Alignment => {
+            let lhs_hashes: Vec<u64> = lhs.iter().map(|packet| comparator.content_hash(packet.get_data())).collect();
+            let rhs_hashes: Vec<u64> = rhs.iter().map(|packet| comparator.content_hash(packet.get_data())).collect();
+            let (mut index, mut rhs_index) = (0, 0);
+
+            // The end of both files closes the last gap
+            for (common, rhs_common) in common_subsequence(&lhs_hashes, &rhs_hashes).into_iter().chain([(lhs.len(), rhs.len())]) {
+                let modified = (common - index).min(rhs_common - rhs_index);
+
+                packets.extend((0..modified).map(|offset| compare(index + offset, rhs_index + offset)));
+                packets.extend((index + modified..common).map(PacketComparison::missing_right));
+                packets.extend((rhs_index + modified..rhs_common).map(PacketComparison::missing_left));
+
+                if common < lhs.len() {
+                    packets.push(compare(common, rhs_common));
+                }
+                (index, rhs_index) = (common + 1, rhs_common + 1);
+            }
+        },
+        MatchStrategy::Multiset { window } => {
+            let mut candidates: HashMap<u64, Vec<usize>> = HashMap::new();
+            for (rhs_index, packet) in rhs.iter().enumerate() {
+                candidates.entry(comparator.content_hash(packet.get_data())).or_default().push(rhs_index);
+            }
+
+            let mut paired = vec![false; rhs.len()];
+            for (index, packet) in lhs.iter().enumerate() {
+                let time = packet.get_header().timestamp();
+                let rhs_index = candidates.get_mut(&comparator.content_hash(packet.get_data())).and_then(|candidates| {
+                    let position = candidates.iter().position(|rhs_index| {
+                        window.is_none_or(|window| time.abs_diff(rhs[*rhs_index].get_header().timestamp()) <= window)
+                    })?;
+                    Some(candidates.remove(position))
+                });
+
+                match rhs_index {
+                    Some(rhs_index) => {
+                        paired[rhs_index] = true;
+                        packets.push(compare(index, rhs_index));
+                    },
+                    None => packets.push(PacketComparison::missing_right(index))
+                }
+            }
+
+            packets.extend((0..rhs.len()).filter(|rhs_index| !paired[*rhs_index]).map(PacketComparison::missing_left));
+        }
+    }
+
+    report
+}
+
+/// Pairs of indexes of a longest common subsequence of `lhs` and `rhs`, in order.
+///
+/// Myers' algorithm with the linear space refinement, in O((N + M) D) time and O(N + M) memory
+/// for D differences, so it's fast for files which mostly match and bounded for files which don't.
+/// The values missing from the other side are removed first, so fully different files are linear.
+fn common_subsequence(lhs: &[u64], rhs: &[u64]) -> Vec<(usize, usize)> {
+
+    let (lhs_values, rhs_values): (HashSet<u64>, HashSet<u64>) = (lhs.iter().copied().collect(), rhs.iter().copied().collect());
+    let lhs_indexes: Vec<usize> = (0..lhs.len()).filter(|x| rhs_values.contains(&lhs[*x])).collect();
+    let rhs_indexes: Vec<usize> = (0..rhs.len()).filter(|y| lhs_values.contains(&rhs[*y])).collect();
+    let lhs_common: Vec<u64> = lhs_indexes.iter().map(|x| lhs[*x]).collect();
+    let rhs_common: Vec<u64> = rhs_indexes.iter().map(|y| rhs[*y]).collect();
+
+    let mut pairs = Vec::new();
+    align(&lhs_common, &rhs_common, (0, 0), &mut pairs);
+    pairs.into_iter().map(|(x, y)| (lhs_indexes[x], rhs_indexes[y])).collect()
+}
+
+// Append the pairs of a longest common subsequence of `lhs` and `rhs`, which start at `origin` in the whole sequences
+fn align(lhs: &[u64], rhs: &[u64], origin: (usize, usize), pairs: &mut Vec<(usize, usize)>) {
+
+    let prefix = lhs.iter().zip(rhs).take_while(|(x, y)| x == y).count();
+    pairs.extend((0..prefix).map(|offset| (origin.0 + offset, origin.1 + offset)));
+
+    let (lhs, rhs) = (&lhs[prefix..], &rhs[prefix..]);
+    let origin = (origin.0 + prefix, origin.1 + prefix);
+    let suffix = lhs.iter().rev().zip(rhs.iter().rev()).take_while(|(x, y)| x == y).count();
+    let (lhs, rhs) = (&lhs[..lhs.len() - suffix], &rhs[..rhs.len() - suffix]);
+
+    if !lhs.is_empty() && !rhs.is_empty() {
+        let ((x, y), (u, v)) = middle_snake(lhs, rhs);
+
+        align(&lhs[..x], &rhs[..y], origin, pairs);
+        pairs.extend((0..u - x).map(|offset| (origin.0 + x + offset, origin.1 + y + offset)));
+        align(&lhs[u..], &rhs[v..], (origin.0 + u, origin.1 + v), pairs);
+    }
+
+    pairs.extend((0..suffix).map(|offset| (origin.0 + lhs.len() + offset, origin.1 + rhs.len() + offset)));
+}
+
+// Start and end of the snake in the middle of a shortest edit script of `lhs` and `rhs`, found
+// by searching from both ends until the forward and backward paths overlap
+fn middle_snake(lhs: &[u64], rhs: &[u64]) -> ((usize, usize), (usize, usize)) {
+
+    let (n, m) = (lhs.len() as isize, rhs.len() as isize);
+    let delta = n - m;
+    let max = (n + m + 1) / 2;
+    // Furthest x reached on each diagonal k = x - y, at index k + offset, from the start and from the end
+    let offset = max + 1;
+    let mut forward = vec![0_isize; 2 * offset as usize + 1];
+    let mut backward = vec![0_isize; 2 * offset as usize + 1];
+
+    // Next x on the diagonal k at step d, from the furthest x of the neighbour diagonals
+    let next = |furthest: &[isize], d: isize, k: isize| {
+        let at = |k: isize| furthest[(k + offset) as usize];
+        match k == -d || (k != d && at(k - 1) < at(k + 1)) {
+            true => at(k + 1),
+            false => at(k - 1) + 1
+        }
+    };
+
+    for d in 0..=max {
+        for k in (-d..=d).step_by(2) {
+            let (start_x, start_y) = (next(&forward, d, k), next(&forward, d, k) - k);
+            let (mut x, mut y) = (start_x, start_y);
+            while x < n && y < m && lhs[x as usize] == rhs[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            forward[(k + offset) as usize] = x;
+
+            let reverse_k = delta - k;
+            if delta % 2 != 0 && reverse_k.abs() < d && x + backward[(reverse_k + offset) as usize] >= n {
+                return ((start_x as usize, start_y as usize), (x as usize, y as usize));
+            }
+        }
+
+        // Same search on the reversed sequences, whose x is the distance from the end
+        for k in (-d..=d).step_by(2) {
+            let (start_x, start_y) = (next(&backward, d, k), next(&backward, d, k) - k);
+            let (mut x, mut y) = (start_x, start_y);
+            while x < n && y < m && lhs[(n - 1 - x) as usize] == rhs[(m - 1 - y) as usize] {
+                x += 1;
+                y += 1;
+            }
+            backward[(k + offset) as usize] = x;
+
+            let forward_k = delta - k;
+            if delta % 2 == 0 && forward_k.abs() <= d && x + forward[(forward_k + offset) as usize] >= n {
+                return (((n - x) as usize, (m - y) as usize), ((n - start_x) as usize, (m - start_y) as usize));
+            }
+        }
+    }
+
+    unreachable!("the forward and backward paths overlap after (N + M + 1) / 2 steps")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pcap::Packet;
+    use crate::pcap_assistant::report::PacketOutcome;
+
+    fn packets(contents: &[(u8, u32)]) -> Vec<Packet<'static>> {
+        contents.iter().map(|(byte, ts_sec)| Packet::new_owned(*ts_sec, 0, vec![*byte; 4], 4)).collect()
+    }
+
+    // Index, right index and outcome of every comparison, E for equal, D for different, L and R for missing left and right
+    fn outcomes(report: &ComparisonReport) -> Vec<(usize, Option<usize>, char)> {
+        report.packets.iter().map(|packet| {
+            let outcome = match packet.outcome {
+                PacketOutcome::Equal => 'E',
+                PacketOutcome::Different { .. } => 'D',
+                PacketOutcome::MissingLeft => 'L',
+                PacketOutcome::MissingRight => 'R'
+            };
+            (packet.index, packet.rhs_index, outcome)
+        }).collect()
+    }
+
+    #[test]
+    fn common_subsequence_test() {
+        assert_eq!(common_subsequence(&[1, 2, 3], &[1, 2, 3]), vec![(0, 0), (1, 1), (2, 2)]);
+        assert_eq!(common_subsequence(&[1, 2, 3, 4], &[1, 3, 4]), vec![(0, 0), (2, 1), (3, 2)]);
+        assert_eq!(common_subsequence(&[1, 3], &[0, 1, 2, 3, 5]), vec![(0, 1), (1, 3)]);
+        assert_eq!(common_subsequence(&[], &[1]), vec![]);
+        assert_eq!(common_subsequence(&[7, 8], &[]), vec![]);
+
+        let (lhs, rhs) = ([1, 2, 3, 4, 5, 6, 7], [2, 9, 4, 5, 7, 1]);
+        let pairs = common_subsequence(&lhs, &rhs);
+        assert_eq!(pairs.len(), 4);
+        assert!(pairs.iter().all(|(x, y)| lhs[*x] == rhs[*y]));
+        assert!(pairs.windows(2).all(|pair| pair[0].0 < pair[1].0 && pair[0].1 < pair[1].1));
+
+        // Pseudo-random sequences over a small alphabet, checked against the dynamic programming length
+        let mut seed = 7_u64;
+        let mut random = |len: usize| -> Vec<u64> {
+            (0..len).map(|_| {
+                seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                (seed >> 60) % 4
+            }).collect()
+        };
+        for len in 0..40 {
+            let (lhs, rhs) = (random(len), random(40 - len / 2));
+            let mut lengths = vec![vec![0; rhs.len() + 1]; lhs.len() + 1];
+            for x in 0..lhs.len() {
+                for y in 0..rhs.len() {
+                    lengths[x + 1][y + 1] = if lhs[x] == rhs[y] { lengths[x][y] + 1 } else { lengths[x][y + 1].max(lengths[x + 1][y]) };
+                }
+            }
+
+            let pairs = common_subsequence(&lhs, &rhs);
+            assert_eq!(pairs.len(), lengths[lhs.len()][rhs.len()]);
+            assert!(pairs.iter().all(|(x, y)| lhs[*x] == rhs[*y]));
+            assert!(pairs.windows(2).all(|pair| pair[0].0 < pair[1].0 && pair[0].1 < pair[1].1));
+        }
+    }
+
+    #[test]
+    fn different_sequences_test() {
+        // Every packet differs, the edit script has D = N + M differences
+        let lhs: Vec<u64> = (0..20_000).collect();
+        let rhs: Vec<u64> = (20_000..40_000).collect();
+        assert_eq!(common_subsequence(&lhs, &rhs), vec![]);
+
+        // Same values in reverse order, the search from both ends keeps the memory linear
+        let lhs: Vec<u64> = (0..2_000).collect();
+        let rhs: Vec<u64> = (0..2_000).rev().collect();
+        assert_eq!(common_subsequence(&lhs, &rhs).len(), 1);
+    }
+
+    #[test]
+    fn match_packets_test() {
+        let lhs = packets(&[(0, 0), (1, 1), (2, 2), (3, 3), (4, 4)]);
+        // Packet 1 dropped, packet 3 modified and a packet inserted at the end
+        let rhs = packets(&[(0, 0), (2, 2), (9, 3), (4, 4), (5, 5)]);
+
+        let index = match_packets(&lhs, &rhs, &PacketComparator::default());
+        assert_eq!(index.summary().equal, 1);
+
+        let alignment = match_packets(&lhs, &rhs, &PacketComparator::default().matching(MatchStrategy::Alignment));
+        assert_eq!(outcomes(&alignment), vec![
+            (0, Some(0), 'E'), (1, None, 'R'), (2, Some(1), 'E'), (3, Some(2), 'D'), (4, Some(3), 'E'), (4, None, 'L')
+        ]);
+
+        // Reordered packets, and a duplicate too far in time
+        let rhs = packets(&[(2, 2), (1, 1), (0, 0), (4, 4), (3, 3), (0, 60)]);
+        let multiset = PacketComparator::default().matching(MatchStrategy::Multiset { window: None });
+        assert_eq!(outcomes(&match_packets(&lhs, &rhs, &multiset)), vec![
+            (0, Some(2), 'E'), (1, Some(1), 'E'), (2, Some(0), 'E'), (3, Some(4), 'E'), (4, Some(3), 'E'), (5, None, 'L')
+        ]);
+
+        let lhs = packets(&[(0, 60), (1, 1)]);
+        let multiset = PacketComparator::default().matching(MatchStrategy::Multiset { window: Some(Duration::from_secs(5)) });
+        assert_eq!(outcomes(&match_packets(&lhs, &rhs, &multiset)), vec![
+            (0, Some(5), 'E'), (1, Some(1), 'E'), (0, None, 'L'), (2, None, 'L'), (3, None, 'L'), (4, None, 'L')
+        ]);
+
+        assert_eq!("alignment".parse::<MatchStrategy>().unwrap(), MatchStrategy::Alignment);
+        assert_eq!("multiset:0.5".parse::<MatchStrategy>().unwrap(), MatchStrategy::Multiset { window: Some(Duration::from_millis(500)) });
+        assert!("multiset:-1".parse::<MatchStrategy>().is_err());
+        assert!("lcs".parse::<MatchStrategy>().is_err());
+    }
+}
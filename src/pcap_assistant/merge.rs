@@ -0,0 +1,200 @@
+use crate::pcap::*;
+use crate::{AssistantError, AssistantResult, TsResolution};
+use std::io::Read;
+
+
+/// Packet yielded by a `CaptureMerger`, with the position of its input.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MergedPacket<I> {
+    pub input: usize,
+    pub packet: I
+}
+
+impl<I: SomePacket<'static>> MergedPacket<I> {
+
+    /// Copy the packet to a `VppPacket` whose interface index is the position of its input.
+    pub fn tagged(&self) -> VppPacket<'static> {
+
+        let header = self.packet.get_header();
+        let mut vpp_header = VppPacketHeader::new(header.ts_sec(), header.ts_nsec(), header.incl_len(), header.orig_len());
+        vpp_header.interface_index = self.input as u32;
+
+        VppPacket::from_parts(vpp_header, self.packet.get_data().to_vec())
+    }
+}
+
+// Reader of an input and its next packet
+struct MergeInput<T: Read, P: SomePacket<'static>> {
+    reader: PcapReader<T, P>,
+    next: Option<P::Item>,
+    index: usize,
+    done: bool
+}
+
+/// Iterator merging the packets of several `PcapReader`s in timestamp order.
+///
+/// Packets with the same timestamp are yielded in the order of the inputs, and the packets
+/// of each input keep their order. A parsing error is yielded once and ends its input,
+/// the other inputs are still merged.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use std::fs::File;
+/// use pcap_assistant::pcap::{PcapReader, PcapWriter, PacketWriter, VppPacket};
+/// use pcap_assistant::pcap_assistant::assistant::CaptureMerger;
+///
+/// let readers = ["node1.pcap", "node2.pcap"].iter()
+///     .map(|path| PcapReader::<_, VppPacket>::new(File::open(path).unwrap()).unwrap())
+///     .collect();
+///
+/// let merger = CaptureMerger::new(readers).unwrap();
+/// let mut writer = PcapWriter::with_header(*merger.header(), File::create("merged.pcap").unwrap()).unwrap();
+///
+/// for merged in merger {
+///     writer.write_packet(merged.unwrap().packet).unwrap();
+/// }
+/// ```
+pub struct CaptureMerger<T: Read, P: SomePacket<'static>> {
+    inputs: Vec<MergeInput<T, P>>,
+    header: PcapHeader
+}
+
+impl<T: Read, P: SomePacket<'static>> CaptureMerger<T, P> {
+
+    /// Creates a `CaptureMerger` of the given readers.
+    ///
+    /// # Errors
+    ///
+    /// Returns `AssistantError::DataLinkMismatch` if the inputs don't have the same `DataLink`.
+    pub fn new(readers: Vec<PcapReader<T, P>>) -> AssistantResult<CaptureMerger<T, P>> {
+
+        let header = merged_header(readers.iter().map(|reader| &reader.header))?;
+        let inputs = readers.into_iter()
+            .map(|reader| MergeInput { reader, next: None, index: 0, done: false })
+            .collect();
+
+        Ok(CaptureMerger { inputs, header })
+    }
+
+    /// Header to write the merged packets with.
+    ///
+    /// It has the endianness of the first input, the nanosecond resolution if an input has it,
+    /// and the largest snaplen of the inputs.
+    pub fn header(&self) -> &PcapHeader {
+        &self.header
+    }
+}
+
+impl<T: Read, P: SomePacket<'static>> Iterator for CaptureMerger<T, P>
+    where P::Item: SomePacket<'static>
+{
+    type Item = AssistantResult<MergedPacket<P::Item>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+
+        for (position, input) in self.inputs.iter_mut().enumerate() {
+            if input.next.is_some() || input.done {
+                continue;
+            }
+
+            let offset = input.reader.position();
+            match input.reader.next() {
+                Some(Ok(packet)) => input.next = Some(packet),
+                Some(Err(source)) => {
+                    input.done = true;
+                    return Some(Err(AssistantError::InputParse { input: position, index: input.index, offset, source }));
+                },
+                None => input.done = true
+            }
+        }
+
+        // The first input wins the ties
+        let (position, input) = self.inputs.iter_mut().enumerate()
+            .filter(|(_, input)| input.next.is_some())
+            .min_by_key(|(position, input)| (input.next.as_ref().map(|packet| packet.get_header().timestamp()), *position))?;
+
+        input.index += 1;
+
+        Some(Ok(MergedPacket { input: position, packet: input.next.take()? }))
+    }
+}
+
+// Header compatible with the headers of all the inputs
+fn merged_header<'h, I: IntoIterator<Item = &'h PcapHeader>>(headers: I) -> AssistantResult<PcapHeader> {
+
+    let mut headers = headers.into_iter();
+    let mut merged = match headers.next() {
+        Some(first) => PcapHeader { ts_correction: 0, ts_accuracy: 0, ..*first },
+        None => return Ok(PcapHeader::default())
+    };
+
+    for (position, header) in headers.enumerate() {
+        if header.datalink != merged.datalink {
+            return Err(AssistantError::DataLinkMismatch { input: position + 1, expected: merged.datalink, found: header.datalink });
+        }
+
+        if header.ts_resolution() == TsResolution::NanoSecond {
+            merged.set_ts_resolution(TsResolution::NanoSecond);
+        }
+
+        merged.snaplen = merged.snaplen.max(header.snaplen);
+    }
+
+    Ok(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures;
+    use crate::{DataLink, Endianness};
+    use std::io::Cursor;
+
+    fn capture(header: PcapHeader, timestamps: &[(u32, u32)]) -> PcapReader<Cursor<Vec<u8>>, Packet<'static>> {
+        let packets = timestamps.iter().enumerate().map(|(index, (ts_sec, ts_nsec))| Packet::new_owned(*ts_sec, *ts_nsec, vec![index as u8], 1));
+        PcapReader::new(Cursor::new(test_fixtures::capture(header, packets))).unwrap()
+    }
+
+    #[test]
+    fn capture_merger_test() {
+        let mut big_nano = PcapHeader { snaplen: 1500, ..Default::default() };
+        big_nano.set_endianness(Endianness::Big);
+        big_nano.set_ts_resolution(TsResolution::NanoSecond);
+        let mut little_micro = PcapHeader { snaplen: 9000, ..Default::default() };
+        little_micro.set_endianness(Endianness::Little);
+
+        let readers = vec![
+            capture(big_nano, &[(1, 500), (2, 0), (2, 0)]),
+            capture(little_micro, &[(1, 0), (2, 0), (3, 0)])
+        ];
+        let merger = CaptureMerger::new(readers).unwrap();
+
+        let header = *merger.header();
+        assert_eq!((header.endianness(), header.ts_resolution(), header.snaplen), (Endianness::Big, TsResolution::NanoSecond, 9000));
+
+        let merged: Vec<(usize, u8)> = merger.map(|merged| {
+            let merged = merged.unwrap();
+            (merged.input, merged.packet.data[0])
+        }).collect();
+        assert_eq!(merged, vec![(1, 0), (0, 0), (0, 1), (0, 2), (1, 1), (1, 2)]);
+
+        let tagged = MergedPacket { input: 3, packet: Packet::new_owned(1, 2, vec![7], 10) }.tagged();
+        assert_eq!((tagged.header.interface_index, tagged.header.orig_len, tagged.data.as_ref()), (3, 10, &[7][..]));
+
+        let raw = PcapHeader { datalink: DataLink::RAW, ..Default::default() };
+        let readers = vec![capture(PcapHeader::default(), &[]), capture(raw, &[])];
+        assert!(matches!(CaptureMerger::new(readers), Err(AssistantError::DataLinkMismatch { input: 1, .. })));
+
+        // Truncated second input
+        let mut truncated = capture(PcapHeader::default(), &[(0, 0), (5, 0)]).into_reader().into_inner();
+        truncated.truncate(truncated.len() - 2);
+        let readers = vec![capture(PcapHeader::default(), &[(1, 0), (6, 0)]), PcapReader::new(Cursor::new(truncated)).unwrap()];
+
+        let merged: Vec<_> = CaptureMerger::new(readers).unwrap().collect();
+        assert_eq!(merged.len(), 4);
+        assert_eq!(merged[0].as_ref().unwrap().input, 1);
+        assert!(matches!(merged[1], Err(AssistantError::InputParse { input: 1, index: 1, offset: 41, .. })));
+        assert_eq!(merged[3].as_ref().unwrap().packet.header.ts_sec, 6);
+    }
+}
@@ -0,0 +1,234 @@
+use crate::AssistantResult;
+use std::{
+    collections::BTreeMap,
+    num::NonZeroUsize,
+    panic::{self, AssertUnwindSafe},
+    sync::{mpsc, Mutex},
+    thread
+};
+
+
+/// Threads and chunk size used by the parallel methods of `PcapTester`,
+/// e.g. `process_and_save_parallel` and `compare_files_parallel`.
+///
+/// Packets are read in chunks of `chunk_size`, the chunks are handled by `threads` workers
+/// and their results are written or reported in the order of the input.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ParallelOptions {
+    pub threads: usize,
+    pub chunk_size: usize
+}
+
+impl ParallelOptions {
+
+    /// Creates `ParallelOptions`, a zero value is replaced by one.
+    pub fn new(threads: usize, chunk_size: usize) -> ParallelOptions {
+        ParallelOptions { threads: threads.max(1), chunk_size: chunk_size.max(1) }
+    }
+}
+
+impl Default for ParallelOptions {
+
+    /// One thread per available CPU and chunks of 1024 packets.
+    fn default() -> ParallelOptions {
+
+        let threads = thread::available_parallelism().map_or(1, NonZeroUsize::get);
+        ParallelOptions::new(threads, 1024)
+    }
+}
+
+/// Run `work` on the chunks of `chunks` with a worker per state of `states`, passing
+/// the results to `sink` in the order of the chunks.
+///
+/// `work` gets the state of its worker and the position of the first item of the chunk.
+/// At most two chunks per worker are in flight. The first error, in the order of the chunks,
+/// stops the run after the results of the previous chunks are passed to `sink`, and a panic of `work`
+/// is resumed on the calling thread.
+pub(crate) fn run_ordered<I, O, S, C, W, K>(states: Vec<S>, chunks: C, work: W, mut sink: K) -> AssistantResult<()>
+    where I: Send,
+          O: Send,
+          S: Send,
+          C: IntoIterator<Item = AssistantResult<Vec<I>>>,
+          W: Fn(&mut S, usize, Vec<I>) -> AssistantResult<O> + Sync,
+          K: FnMut(O) -> AssistantResult<()>
+{
+    let max_in_flight = 2 * states.len().max(1);
+
+    let (job_sender, job_receiver) = mpsc::channel::<(usize, usize, Vec<I>)>();
+    let job_receiver = Mutex::new(job_receiver);
+
+    thread::scope(|scope| {
+        let (result_sender, result_receiver) = mpsc::channel();
+
+        for mut state in states {
+            let result_sender = result_sender.clone();
+            let (job_receiver, work) = (&job_receiver, &work);
+
+            scope.spawn(move || {
+                loop {
+                    let job = job_receiver.lock().map_err(drop).and_then(|receiver| receiver.recv().map_err(drop));
+                    let Ok((sequence, start, items)) = job else {
+                        break;
+                    };
+                    let result = panic::catch_unwind(AssertUnwindSafe(|| work(&mut state, start, items)));
+                    if result_sender.send((sequence, result)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(result_sender);
+
+        let mut ordered = Ordered { pending: BTreeMap::new(), next: 0, receiver: result_receiver };
+        let mut sent = 0;
+        let mut start = 0;
+        let mut read_error = None;
+
+        for chunk in chunks {
+            while sent - ordered.next >= max_in_flight {
+                ordered.receive(&mut sink)?;
+            }
+
+            match chunk {
+                Ok(items) => {
+                    let len = items.len();
+                    job_sender.send((sent, start, items)).expect("Parallel workers stopped");
+                    sent += 1;
+                    start += len;
+                },
+                Err(err) => {
+                    read_error = Some(err);
+                    break;
+                }
+            }
+        }
+
+        drop(job_sender);
+        while ordered.next < sent {
+            ordered.receive(&mut sink)?;
+        }
+
+        read_error.map_or(Ok(()), Err)
+    })
+}
+
+// Results of the workers, passed to the sink in the order of the chunks
+struct Ordered<O> {
+    pending: BTreeMap<usize, thread::Result<AssistantResult<O>>>,
+    next: usize,
+    receiver: mpsc::Receiver<(usize, thread::Result<AssistantResult<O>>)>
+}
+
+impl<O> Ordered<O> {
+
+    // Wait for a result, then pass the results which are next in order to the sink
+    fn receive<K: FnMut(O) -> AssistantResult<()>>(&mut self, sink: &mut K) -> AssistantResult<()> {
+
+        let (sequence, result) = self.receiver.recv().expect("Parallel worker stopped");
+        self.pending.insert(sequence, result);
+
+        while let Some(result) = self.pending.remove(&self.next) {
+            self.next += 1;
+            let result = result.unwrap_or_else(|payload| panic::resume_unwind(payload));
+            sink(result?)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Split `items` in chunks of `chunk_size`, the first error ends the chunks
+/// after the chunk of the items read before it.
+pub(crate) fn chunks<T, I>(mut items: I, chunk_size: usize) -> impl Iterator<Item = AssistantResult<Vec<T>>>
+    where I: Iterator<Item = AssistantResult<T>>
+{
+    let mut error = None;
+    let mut failed = false;
+
+    std::iter::from_fn(move || {
+        if failed {
+            return None;
+        }
+        if let Some(err) = error.take() {
+            failed = true;
+            return Some(Err(err));
+        }
+
+        let mut chunk = Vec::with_capacity(chunk_size);
+        for item in items.by_ref().take(chunk_size) {
+            match item {
+                Ok(item) => chunk.push(item),
+                Err(err) => {
+                    error = Some(err);
+                    break;
+                }
+            }
+        }
+
+        match chunk.is_empty() {
+            true => error.take().map(|err| {
+                failed = true;
+                Err(err)
+            }),
+            false => Some(Ok(chunk))
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AssistantError, PcapError};
+    use std::panic;
+
+    fn parse_error(index: usize) -> AssistantError {
+        AssistantError::PacketParse { index, offset: 0, source: PcapError::InvalidField("test") }
+    }
+
+    #[test]
+    fn run_ordered_test() {
+        let items = (0..100).map(Ok::<usize, AssistantError>);
+        let mut output = Vec::new();
+
+        run_ordered(vec![0_usize; 4], chunks(items, 7), |handled, start, chunk| {
+            // Later chunks finish first
+            thread::sleep(std::time::Duration::from_micros(50 * (100 - start) as u64));
+            *handled += chunk.len();
+            Ok(chunk.into_iter().map(|item| (start, item * 2)).collect::<Vec<_>>())
+        }, |doubled| {
+            output.extend(doubled);
+            Ok(())
+        }).unwrap();
+
+        assert_eq!(output.iter().map(|(_, item)| *item).collect::<Vec<_>>(), (0..100).map(|item| item * 2).collect::<Vec<_>>());
+        assert!(output.iter().all(|(start, item)| start % 7 == 0 && (*start..start + 7).contains(&(item / 2))));
+
+        // Items before a read error are still handled
+        let items = (0..10).map(|item| if item == 8 { Err(parse_error(item)) } else { Ok(item) });
+        let mut output = Vec::new();
+        let result = run_ordered(vec![(); 2], chunks(items, 3), |_, _, chunk| Ok(chunk), |chunk| {
+            output.extend(chunk);
+            Ok(())
+        });
+        assert!(matches!(result, Err(AssistantError::PacketParse { index: 8, .. })));
+        assert_eq!(output, (0..8).collect::<Vec<_>>());
+
+        // First error in order
+        let result = run_ordered(vec![(); 3], chunks((0..50).map(Ok), 5), |_, start, _| {
+            match start {
+                10 | 30 => Err(parse_error(start)),
+                _ => Ok(())
+            }
+        }, |_| Ok(()));
+        assert!(matches!(result, Err(AssistantError::PacketParse { index: 10, .. })));
+
+        let result = panic::catch_unwind(|| {
+            run_ordered(vec![(); 2], chunks((0..10).map(Ok::<_, AssistantError>), 2), |_, start, _| {
+                assert!(start != 4, "Worker panic");
+                Ok(())
+            }, |_| Ok(()))
+        });
+        assert!(result.is_err());
+        assert_eq!(ParallelOptions::new(0, 0), ParallelOptions { threads: 1, chunk_size: 1 });
+    }
+}
@@ -0,0 +1,244 @@
+use crate::pcap::SomePacketHeader;
+use crate::pcap_assistant::assistant::PcapTester;
+use crate::pcap_assistant::compare::HeaderComparison;
+use colored::Colorize;
+use std::fmt::{self, Write};
+use std::time::Duration;
+
+
+/// Header field compared between two packets.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum HeaderField {
+    TsSec,
+    TsNsec,
+    InclLen,
+    OrigLen,
+    InterfaceIndex
+}
+
+/// Difference of one header field between the left and the right packet.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct HeaderDifference {
+    pub field: HeaderField,
+    pub lhs: u32,
+    pub rhs: u32,
+
+    /// The field is checked by the `HeaderComparison`, so the packets aren't equal
+    pub checked: bool
+}
+
+/// Outcome of the comparison of the packets at a given index.
+///
+/// The left side is the original file of the `PcapTester`, the right side the compared file.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum PacketOutcome {
+
+    /// Both packets have the same data
+    Equal,
+
+    /// Packets data differ, starting at the byte `first_mismatch` of the payload
+    Different {
+        first_mismatch: usize,
+        lhs: Vec<u8>,
+        rhs: Vec<u8>
+    },
+
+    /// The packet exists only in the right file
+    MissingLeft,
+
+    /// The packet exists only in the left file
+    MissingRight
+}
+
+/// Comparison of the packets at a given index.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PacketComparison {
+
+    /// Index of the packet, starting at 0: in the left file, or in the right file for `PacketOutcome::MissingLeft`
+    pub index: usize,
+
+    /// Index of the right packet compared with the left one, when they aren't paired by index, see `MatchStrategy`
+    pub rhs_index: Option<usize>,
+
+    /// Outcome of the comparison of the packets data
+    pub outcome: PacketOutcome,
+
+    /// Header fields which differ, informative only unless they're checked
+    pub header_differences: Vec<HeaderDifference>
+}
+
+impl PacketComparison {
+
+    /// Compare the headers and data of two packets, no header field is checked.
+    pub fn new<L: SomePacketHeader, R: SomePacketHeader>(index: usize, lhs_header: &L, lhs: &[u8], rhs_header: &R, rhs: &[u8]) -> PacketComparison {
+
+        let outcome = match first_mismatch(lhs, rhs) {
+            None => PacketOutcome::Equal,
+            Some(first_mismatch) => PacketOutcome::Different {
+                first_mismatch,
+                lhs: lhs.to_vec(),
+                rhs: rhs.to_vec()
+            }
+        };
+
+        PacketComparison {
+            index,
+            rhs_index: None,
+            outcome,
+            header_differences: HeaderComparison::default().differences((Duration::ZERO, Duration::ZERO), lhs_header, rhs_header)
+        }
+    }
+
+    /// Packet only present in the right file.
+    pub fn missing_left(index: usize) -> PacketComparison {
+        PacketComparison { index, rhs_index: None, outcome: PacketOutcome::MissingLeft, header_differences: Vec::new() }
+    }
+
+    /// Packet only present in the left file.
+    pub fn missing_right(index: usize) -> PacketComparison {
+        PacketComparison { index, rhs_index: None, outcome: PacketOutcome::MissingRight, header_differences: Vec::new() }
+    }
+
+    /// Return true if the packets have the same data and no checked header difference.
+    pub fn is_equal(&self) -> bool {
+        self.outcome == PacketOutcome::Equal && !self.has_header_mismatch()
+    }
+
+    /// Return true if a checked header field differs.
+    pub fn has_header_mismatch(&self) -> bool {
+        self.header_differences.iter().any(|difference| difference.checked)
+    }
+}
+
+/// Counts of each outcome in a `ComparisonReport`.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct ComparisonSummary {
+    pub total: usize,
+    pub equal: usize,
+
+    /// Packets whose data or checked header fields differ
+    pub different: usize,
+    pub missing_left: usize,
+    pub missing_right: usize
+}
+
+/// Result of the comparison of two captures, packet by packet.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use pcap_assistant::pcap::Packet;
+/// use pcap_assistant::pcap_assistant::assistant::PcapTester;
+///
+/// let env = PcapTester::new("file.pcap");
+/// let report = env.compare_files::<Packet, Packet>("file2.pcap").unwrap();
+///
+/// report.print();
+/// assert!(report.is_equal(), "{:?}", report.summary());
+/// ```
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ComparisonReport {
+
+    /// Comparison of every packet, in index order
+    pub packets: Vec<PacketComparison>
+}
+
+impl ComparisonReport {
+
+    /// Return true if both captures have the same packets.
+    pub fn is_equal(&self) -> bool {
+        self.packets.iter().all(PacketComparison::is_equal)
+    }
+
+    /// Count the packets of each outcome.
+    pub fn summary(&self) -> ComparisonSummary {
+
+        let mut summary = ComparisonSummary { total: self.packets.len(), ..Default::default() };
+
+        for packet in &self.packets {
+            match packet.outcome {
+                PacketOutcome::Equal if packet.has_header_mismatch() => summary.different += 1,
+                PacketOutcome::Equal => summary.equal += 1,
+                PacketOutcome::Different { .. } => summary.different += 1,
+                PacketOutcome::MissingLeft => summary.missing_left += 1,
+                PacketOutcome::MissingRight => summary.missing_right += 1
+            }
+        }
+
+        summary
+    }
+
+    /// Packets which are not equal.
+    pub fn failures(&self) -> impl Iterator<Item = &PacketComparison> {
+        self.packets.iter().filter(|packet| !packet.is_equal())
+    }
+
+    /// Render the report, one line per packet followed by its header differences, the checked ones
+    /// highlighted, and by the summary.
+    ///
+    /// Packets paired with a right packet of another index show both indexes. Different packets are followed by their hex data with the mismatching digits highlighted.
+    pub fn render<W: Write>(&self, out: &mut W) -> fmt::Result {
+        self.render_packets(out, self.packets.iter())
+    }
+
+    /// Render the packets which are not equal like `render`, followed by the summary of all the packets.
+    pub fn render_failures<W: Write>(&self, out: &mut W) -> fmt::Result {
+        self.render_packets(out, self.failures())
+    }
+
+    // Lines of the given packets and the summary of the report
+    fn render_packets<'a, W: Write>(&self, out: &mut W, packets: impl Iterator<Item = &'a PacketComparison>) -> fmt::Result {
+
+        for packet in packets {
+            let index = match packet.rhs_index {
+                Some(rhs_index) if rhs_index != packet.index => format!("{} (right {})", packet.index, rhs_index),
+                _ => packet.index.to_string()
+            };
+
+            match &packet.outcome {
+                PacketOutcome::Equal if packet.has_header_mismatch() => writeln!(out, "Packet {}: {} in header", index, "FAIL".red().bold())?,
+                PacketOutcome::Equal => writeln!(out, "Packet {}: {}", index, "OK".green().bold())?,
+                PacketOutcome::Different { first_mismatch, lhs, rhs } => {
+                    writeln!(out, "Packet {}: {} at byte {} \n {}", index, "FAIL".red().bold(), first_mismatch, PcapTester::compare_pakets_data(lhs, rhs).0)?
+                },
+                PacketOutcome::MissingLeft => writeln!(out, "Packet {}: {} only in right file", packet.index, "FAIL".red().bold())?,
+                PacketOutcome::MissingRight => writeln!(out, "Packet {}: {} only in left file", packet.index, "FAIL".red().bold())?
+            }
+
+            for difference in &packet.header_differences {
+                let line = format!("    {:?}: {} != {}", difference.field, difference.lhs, difference.rhs);
+                match difference.checked {
+                    true => writeln!(out, "{}", line.red())?,
+                    false => writeln!(out, "{}", line)?
+                }
+            }
+        }
+
+        let summary = self.summary();
+        writeln!(
+            out, "{} packets: {} equal, {} different, {} missing left, {} missing right",
+            summary.total, summary.equal, summary.different, summary.missing_left, summary.missing_right
+        )
+    }
+
+    /// Print the report to stdout.
+    pub fn print(&self) {
+        print!("{}", self);
+    }
+}
+
+impl fmt::Display for ComparisonReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.render(f)
+    }
+}
+
+/// Offset of the first byte which differs, or the length of the shortest slice if one is a prefix of the other.
+fn first_mismatch(lhs: &[u8], rhs: &[u8]) -> Option<usize> {
+
+    match lhs.iter().zip(rhs).position(|(l, r)| l != r) {
+        Some(offset) => Some(offset),
+        None if lhs.len() != rhs.len() => Some(lhs.len().min(rhs.len())),
+        None => None
+    }
+}
@@ -0,0 +1,365 @@
+use crate::dissect::*;
+use crate::pcap_assistant::assistant::PacketProcessor;
+use crate::{DataLink, PcapError, ResultParsing};
+use byteorder::{BigEndian, ByteOrder};
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+
+/// Header field to rewrite, with its new value.
+///
+/// Fields are named like the Wireshark display filter fields, see `FieldRewrite::parse`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum FieldRewrite {
+    EthSrc(MacAddr),
+    EthDst(MacAddr),
+    Ipv4Src(Ipv4Addr),
+    Ipv4Dst(Ipv4Addr),
+    Ipv6Src(Ipv6Addr),
+    Ipv6Dst(Ipv6Addr),
+    TcpSrcPort(u16),
+    TcpDstPort(u16),
+    UdpSrcPort(u16),
+    UdpDstPort(u16),
+    Ttl(u8),
+    HopLimit(u8),
+
+    /// DSCP of the IPv4 header (6 bits), the ECN bits are kept
+    Ipv4Dscp(u8),
+
+    /// DSCP of the IPv6 traffic class (6 bits), the ECN bits are kept
+    Ipv6Dscp(u8),
+
+    /// Identifier (12 bits) of the outer VLAN tag, the priority is kept
+    VlanId(u16)
+}
+
+impl FieldRewrite {
+
+    /// Name of the rewritten field.
+    pub fn field_name(&self) -> &'static str {
+
+        match self {
+            FieldRewrite::EthSrc(_) => "eth.src",
+            FieldRewrite::EthDst(_) => "eth.dst",
+            FieldRewrite::Ipv4Src(_) => "ip.src",
+            FieldRewrite::Ipv4Dst(_) => "ip.dst",
+            FieldRewrite::Ipv6Src(_) => "ipv6.src",
+            FieldRewrite::Ipv6Dst(_) => "ipv6.dst",
+            FieldRewrite::TcpSrcPort(_) => "tcp.srcport",
+            FieldRewrite::TcpDstPort(_) => "tcp.dstport",
+            FieldRewrite::UdpSrcPort(_) => "udp.srcport",
+            FieldRewrite::UdpDstPort(_) => "udp.dstport",
+            FieldRewrite::Ttl(_) => "ip.ttl",
+            FieldRewrite::HopLimit(_) => "ipv6.hlim",
+            FieldRewrite::Ipv4Dscp(_) => "ip.dsfield.dscp",
+            FieldRewrite::Ipv6Dscp(_) => "ipv6.tclass.dscp",
+            FieldRewrite::VlanId(_) => "vlan.id"
+        }
+    }
+
+    /// Parse a rewrite from a field name and its new value.
+    ///
+    /// Supported fields are `eth.src`, `eth.dst`, `ip.src`, `ip.dst`, `ipv6.src`, `ipv6.dst`,
+    /// `tcp.srcport`, `tcp.dstport`, `udp.srcport`, `udp.dstport`, `ip.ttl`, `ipv6.hlim`,
+    /// `ip.dsfield.dscp`, `ipv6.tclass.dscp` and `vlan.id`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `PcapError::InvalidField` if the field is unknown or if the value can't be parsed
+    /// or is out of the field range.
+    pub fn parse(field: &str, value: &str) -> ResultParsing<FieldRewrite> {
+
+        let rewrite = match field {
+            "eth.src" => FieldRewrite::EthSrc(value.parse()?),
+            "eth.dst" => FieldRewrite::EthDst(value.parse()?),
+            "ip.src" => FieldRewrite::Ipv4Src(parse_value(value)?),
+            "ip.dst" => FieldRewrite::Ipv4Dst(parse_value(value)?),
+            "ipv6.src" => FieldRewrite::Ipv6Src(parse_value(value)?),
+            "ipv6.dst" => FieldRewrite::Ipv6Dst(parse_value(value)?),
+            "tcp.srcport" => FieldRewrite::TcpSrcPort(parse_value(value)?),
+            "tcp.dstport" => FieldRewrite::TcpDstPort(parse_value(value)?),
+            "udp.srcport" => FieldRewrite::UdpSrcPort(parse_value(value)?),
+            "udp.dstport" => FieldRewrite::UdpDstPort(parse_value(value)?),
+            "ip.ttl" => FieldRewrite::Ttl(parse_value(value)?),
+            "ipv6.hlim" => FieldRewrite::HopLimit(parse_value(value)?),
+            "ip.dsfield.dscp" => FieldRewrite::Ipv4Dscp(parse_value(value)?),
+            "ipv6.tclass.dscp" => FieldRewrite::Ipv6Dscp(parse_value(value)?),
+            "vlan.id" => FieldRewrite::VlanId(parse_value(value)?),
+            _ => return Err(PcapError::InvalidField("FieldRewrite unknown field"))
+        };
+
+        match rewrite {
+            FieldRewrite::Ipv4Dscp(dscp) | FieldRewrite::Ipv6Dscp(dscp) if dscp > 0x3F => {
+                Err(PcapError::InvalidField("FieldRewrite dscp > 63"))
+            },
+            FieldRewrite::VlanId(vid) if vid > 0x0FFF => Err(PcapError::InvalidField("FieldRewrite vlan.id > 4095")),
+            _ => Ok(rewrite)
+        }
+    }
+
+    // Write the new value in the packet, returns false if the packet doesn't have the field
+    fn apply(&self, layers: &LayerOffsets, data: &mut [u8]) -> bool {
+
+        let (offset, range, bytes): (Option<usize>, _, Vec<u8>) = match *self {
+            FieldRewrite::EthSrc(mac) => (layers.ethernet, 6..12, mac.0.to_vec()),
+            FieldRewrite::EthDst(mac) => (layers.ethernet, 0..6, mac.0.to_vec()),
+            FieldRewrite::Ipv4Src(ip) => (layers.ipv4, 12..16, ip.octets().to_vec()),
+            FieldRewrite::Ipv4Dst(ip) => (layers.ipv4, 16..20, ip.octets().to_vec()),
+            FieldRewrite::Ipv6Src(ip) => (layers.ipv6, 8..24, ip.octets().to_vec()),
+            FieldRewrite::Ipv6Dst(ip) => (layers.ipv6, 24..40, ip.octets().to_vec()),
+            FieldRewrite::TcpSrcPort(port) => (layers.tcp, 0..2, port.to_be_bytes().to_vec()),
+            FieldRewrite::TcpDstPort(port) => (layers.tcp, 2..4, port.to_be_bytes().to_vec()),
+            FieldRewrite::UdpSrcPort(port) => (layers.udp, 0..2, port.to_be_bytes().to_vec()),
+            FieldRewrite::UdpDstPort(port) => (layers.udp, 2..4, port.to_be_bytes().to_vec()),
+            FieldRewrite::Ttl(ttl) => (layers.ipv4, 8..9, vec![ttl]),
+            FieldRewrite::HopLimit(hop_limit) => (layers.ipv6, 7..8, vec![hop_limit]),
+            FieldRewrite::Ipv4Dscp(dscp) => match layers.ipv4 {
+                Some(offset) => (Some(offset), 1..2, vec![(dscp << 2) | (data[offset + 1] & 0x03)]),
+                None => (None, 0..0, vec![])
+            },
+            FieldRewrite::Ipv6Dscp(dscp) => match layers.ipv6 {
+                Some(offset) => {
+                    // Traffic class is between the version and the flow label
+                    let word = BigEndian::read_u16(&data[offset..offset + 2]);
+                    let word = (word & 0xF03F) | (((dscp & 0x3F) as u16) << 6);
+                    (Some(offset), 0..2, word.to_be_bytes().to_vec())
+                },
+                None => (None, 0..0, vec![])
+            },
+            FieldRewrite::VlanId(vid) => match layers.vlan {
+                Some(offset) => {
+                    let tci = (BigEndian::read_u16(&data[offset..offset + 2]) & 0xF000) | (vid & 0x0FFF);
+                    (Some(offset), 0..2, tci.to_be_bytes().to_vec())
+                },
+                None => (None, 0..0, vec![])
+            }
+        };
+
+        match offset {
+            Some(offset) => {
+                data[offset + range.start..offset + range.end].copy_from_slice(&bytes);
+                true
+            },
+            None => false
+        }
+    }
+}
+
+fn parse_value<T: std::str::FromStr>(value: &str) -> ResultParsing<T> {
+    value.parse().map_err(|_| PcapError::InvalidField("FieldRewrite invalid value"))
+}
+
+// Offsets of the outermost layers which can be rewritten
+struct LayerOffsets {
+    ethernet: Option<usize>,
+    vlan: Option<usize>,
+    ipv4: Option<usize>,
+    ipv6: Option<usize>,
+    tcp: Option<usize>,
+    udp: Option<usize>
+}
+
+impl LayerOffsets {
+
+    fn new(datalink: DataLink, data: &[u8]) -> LayerOffsets {
+
+        let dissection = dissect(datalink, data);
+
+        LayerOffsets {
+            ethernet: dissection.offset_of(|layer| matches!(layer, Layer::Ethernet(_))),
+            vlan: dissection.offset_of(|layer| matches!(layer, Layer::Vlan(_))),
+            ipv4: dissection.offset_of(|layer| matches!(layer, Layer::Ipv4(_))),
+            ipv6: dissection.offset_of(|layer| matches!(layer, Layer::Ipv6(_))),
+            tcp: dissection.offset_of(|layer| matches!(layer, Layer::Tcp(_))),
+            udp: dissection.offset_of(|layer| matches!(layer, Layer::Udp(_)))
+        }
+    }
+}
+
+/// `PacketProcessor` rewriting header fields and fixing the checksums of the rewritten packets.
+///
+/// Packets without the rewritten fields (e.g. ARP packets for `ip.src`) are kept unchanged.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use pcap_assistant::DataLink;
+/// use pcap_assistant::pcap_assistant::assistant::{FieldRewrite, FieldRewriter, PcapTester};
+/// use pcap_assistant::pcap::Packet;
+///
+/// let mut rewriter = FieldRewriter::new(DataLink::ETHERNET)
+///     .with(FieldRewrite::Ttl(32))
+///     .set("ip.dst", "10.0.0.2").unwrap();
+///
+/// let env = PcapTester::new("netinfo.pcap");
+/// let report = env.process_and_compare_files::<_, Packet>("rewritten.pcap", &mut rewriter).unwrap();
+/// assert!(report.is_equal());
+/// ```
+#[derive(Clone, Debug)]
+pub struct FieldRewriter {
+    datalink: DataLink,
+    rewrites: Vec<FieldRewrite>
+}
+
+impl FieldRewriter {
+
+    /// Creates a `FieldRewriter` without rewrites for packets captured with the given `DataLink`.
+    pub fn new(datalink: DataLink) -> FieldRewriter {
+
+        FieldRewriter { datalink, rewrites: Vec::new() }
+    }
+
+    /// Adds a rewrite, applied after the previous ones.
+    pub fn with(mut self, rewrite: FieldRewrite) -> FieldRewriter {
+
+        self.rewrites.push(rewrite);
+        self
+    }
+
+    /// Adds a rewrite by field name, see `FieldRewrite::parse`.
+    pub fn set(self, field: &str, value: &str) -> ResultParsing<FieldRewriter> {
+
+        Ok(self.with(FieldRewrite::parse(field, value)?))
+    }
+
+    pub fn rewrites(&self) -> &[FieldRewrite] {
+        &self.rewrites
+    }
+}
+
+impl PacketProcessor for FieldRewriter {
+
+    fn process_packet(&mut self, packet: &mut Vec<u8>) -> bool {
+
+        let layers = LayerOffsets::new(self.datalink, packet);
+        let mut rewritten = false;
+
+        for rewrite in &self.rewrites {
+            rewritten |= rewrite.apply(&layers, packet);
+        }
+
+        if rewritten {
+            fix_checksums(self.datalink, packet);
+        }
+
+        true
+    }
+}
+
+/// `PacketProcessor` recomputing the IPv4 header, TCP, UDP, ICMP and ICMPv6 checksums,
+/// to be chained after processors which change the packet data.
+#[derive(Copy, Clone, Debug)]
+pub struct ChecksumFixer {
+    datalink: DataLink
+}
+
+impl ChecksumFixer {
+
+    pub fn new(datalink: DataLink) -> ChecksumFixer {
+
+        ChecksumFixer { datalink }
+    }
+}
+
+impl PacketProcessor for ChecksumFixer {
+
+    fn process_packet(&mut self, packet: &mut Vec<u8>) -> bool {
+
+        fix_checksums(self.datalink, packet);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ethernet_vlan_ipv4_udp() -> Vec<u8> {
+        let mut frame = vec![0xFF; 6];
+        frame.extend_from_slice(&[0x02, 0, 0, 0, 0, 1, 0x81, 0x00, 0x20, 0x64, 0x08, 0x00]);
+        frame.extend_from_slice(&[
+            0x45, 0x00, 0x00, 0x20, 0x12, 0x34, 0x40, 0x00, 0x40, 0x11, 0x00, 0x00,
+            10, 0, 0, 1, 10, 0, 0, 2,
+            0x30, 0x39, 0x12, 0xB5, 0x00, 0x0C, 0xAB, 0xCD
+        ]);
+        frame.extend_from_slice(b"ping");
+        frame
+    }
+
+    // True if the one's complement sum over the covered data, checksum included, is all ones
+    fn udp_checksum_valid(frame: &[u8]) -> bool {
+        let dissection = dissect(DataLink::ETHERNET, frame);
+        let ipv4 = dissection.ipv4().unwrap();
+        let mut pseudo = ipv4.header()[12..20].to_vec();
+        pseudo.extend_from_slice(&[0, IP_PROTO_UDP, 0, ipv4.payload().len() as u8]);
+        pseudo.extend_from_slice(ipv4.payload());
+        internet_checksum(&pseudo) == 0
+    }
+
+    #[test]
+    fn field_rewriter_test() {
+        let mut frame = ethernet_vlan_ipv4_udp();
+
+        let mut rewriter = FieldRewriter::new(DataLink::ETHERNET)
+            .with(FieldRewrite::Ttl(32))
+            .set("ip.src", "192.168.1.1").unwrap()
+            .set("udp.dstport", "53").unwrap()
+            .set("eth.dst", "02:00:00:00:00:02").unwrap()
+            .set("vlan.id", "200").unwrap()
+            .set("ip.dsfield.dscp", "46").unwrap();
+
+        assert!(rewriter.process_packet(&mut frame));
+
+        let dissection = dissect(DataLink::ETHERNET, &frame);
+        let ipv4 = dissection.ipv4().unwrap();
+        assert_eq!((ipv4.ttl(), ipv4.src(), ipv4.dscp()), (32, Ipv4Addr::new(192, 168, 1, 1), 46));
+        assert_eq!(dissection.udp().unwrap().dst_port(), 53);
+        assert_eq!(dissection.ethernet().unwrap().dst().to_string(), "02:00:00:00:00:02");
+        assert_eq!((dissection.vlans()[0].vid(), dissection.vlans()[0].pcp()), (200, 1));
+        assert_eq!(internet_checksum(ipv4.header()), 0);
+        assert!(udp_checksum_valid(&frame));
+
+        // Absent layers are skipped, the packet is left unchanged
+        let mut frame = ethernet_vlan_ipv4_udp();
+        let mut rewriter = FieldRewriter::new(DataLink::ETHERNET).set("tcp.dstport", "80").unwrap();
+        assert!(rewriter.process_packet(&mut frame));
+        assert_eq!(frame, ethernet_vlan_ipv4_udp());
+
+        assert!(FieldRewrite::parse("ip.foo", "1").is_err());
+        assert!(FieldRewrite::parse("ip.ttl", "256").is_err());
+        assert!(FieldRewrite::parse("vlan.id", "4096").is_err());
+        assert_eq!(FieldRewrite::parse("ipv6.hlim", "1").unwrap().field_name(), "ipv6.hlim");
+    }
+
+    #[test]
+    fn checksum_fixer_test() {
+        // IPv4 header with a known checksum of 0xB861
+        let mut header = vec![
+            0x45, 0x00, 0x00, 0x73, 0x00, 0x00, 0x40, 0x00, 0x40, 0x11, 0x00, 0x00,
+            0xC0, 0xA8, 0x00, 0x01, 0xC0, 0xA8, 0x00, 0xC7
+        ];
+        assert_eq!(internet_checksum(&header), 0xB861);
+
+        // IPv6 ICMPv6 echo request, checksum with the pseudo-header
+        let mut ipv6 = vec![0x60, 0, 0, 0, 0, 8, IP_PROTO_ICMPV6, 64];
+        ipv6.extend_from_slice(&Ipv6Addr::LOCALHOST.octets());
+        ipv6.extend_from_slice(&Ipv6Addr::LOCALHOST.octets());
+        ipv6.extend_from_slice(&[128, 0, 0, 0, 0, 1, 0, 1]);
+
+        let mut fixer = ChecksumFixer::new(DataLink::RAW);
+        assert!(fixer.process_packet(&mut ipv6));
+
+        let mut pseudo = ipv6[8..40].to_vec();
+        pseudo.extend_from_slice(&[0, 0, 0, 8, 0, 0, 0, IP_PROTO_ICMPV6]);
+        pseudo.extend_from_slice(&ipv6[40..]);
+        assert_ne!(&ipv6[42..44], &[0, 0]);
+        assert_eq!(internet_checksum(&pseudo), 0);
+
+        // Truncated IPv4 header checksum is fixed, not the UDP one
+        header.truncate(20);
+        header.extend_from_slice(&[0x30, 0x39, 0x12, 0xB5, 0x00, 0x5F, 0xAB, 0xCD]);
+        fixer.process_packet(&mut header);
+        assert_eq!(BigEndian::read_u16(&header[10..12]), 0xB861);
+        assert_eq!(&header[26..28], &[0xAB, 0xCD]);
+    }
+}
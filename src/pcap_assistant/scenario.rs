@@ -0,0 +1,441 @@
+use crate::filter::{BpfFilter, DisplayFilter, PacketSource};
+use crate::pcap::{Packet, VppPacket};
+use crate::pcap_assistant::assistant::*;
+use crate::{AssistantError, AssistantResult, DataLink};
+use colored::Colorize;
+use serde::de::{Deserializer, Error};
+use serde::Deserialize;
+use std::fmt::{self, Write};
+use std::fs;
+use std::path::Path;
+
+
+/// Processor of a `Scenario`, in the order of its `[[processor]]` tables.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ProcessorSpec {
+
+    /// `type = "rewrite"` with `set = ["<field>=<value>", ...]`, see `FieldRewrite::parse`
+    Rewrite(Vec<FieldRewrite>),
+
+    /// `type = "fix-checksums"`, see `ChecksumFixer`
+    FixChecksums,
+
+    /// `type = "filter"` with a pcap filter `expression`, see `BpfFilter`
+    Filter(String),
+
+    /// `type = "display-filter"` with a display filter `expression`, see `DisplayFilter`
+    DisplayFilter(String),
+
+    /// `type = "example"` with `start`, `end` and hex `data`, see `ProcessorExample`
+    Example {
+        start: usize,
+        end: usize,
+        data: Vec<u8>
+    }
+}
+
+/// Test case described in a file: an input capture, processors, an expected capture
+/// and the options of the comparison.
+///
+/// Scenarios are written in TOML, the paths are relative to the scenario file.
+///
+/// ```toml
+/// name = "Router decrements the TTL"
+/// input = "input.pcap"
+/// expected = "routed.pcap"
+/// vpp = false
+///
+/// [[processor]]
+/// type = "display-filter"
+/// expression = "udp"
+///
+/// [[processor]]
+/// type = "rewrite"
+/// set = ["ip.ttl=63"]
+///
+/// [compare]
+/// ignore = ["ip.checksum", "udp.checksum"]
+/// match = "alignment"
+/// check_headers = false
+/// timestamp_tolerance = 0.001
+/// relative_timestamps = true
+/// map_interface = ["1=2"]
+/// ```
+///
+/// The `[compare]` keys are the `ComparisonOptions`: ignore rules (see `IgnoreRule::parse`),
+/// `MatchStrategy` and header checks.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use pcap_assistant::pcap_assistant::assistant::Scenario;
+///
+/// let scenario = Scenario::load("scenarios/ttl.toml").unwrap();
+/// assert!(scenario.run().unwrap().is_equal());
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Scenario {
+
+    /// Name of the scenario, the file name without extension by default
+    pub name: String,
+    pub input: String,
+    pub expected: String,
+
+    /// Captures are in the VPP format
+    pub vpp: bool,
+    pub processors: Vec<ProcessorSpec>,
+    pub compare: ComparisonOptions
+}
+
+impl Scenario {
+
+    /// Read and parse the scenario file at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `AssistantError::FileOpen` if the file can't be read, or `AssistantError::ScenarioParse`.
+    pub fn load(path: &str) -> AssistantResult<Scenario> {
+
+        let text = fs::read_to_string(path).map_err(|source| AssistantError::FileOpen { path: path.to_string(), source })?;
+        Scenario::parse(&text, path)
+    }
+
+    /// Parse a scenario read from `path`, whose directory is the base of the relative paths.
+    ///
+    /// # Errors
+    ///
+    /// Returns `AssistantError::ScenarioParse` if the text isn't valid TOML, has unknown keys or
+    /// invalid values, or misses the `input` or `expected` capture.
+    pub fn parse(text: &str, path: &str) -> AssistantResult<Scenario> {
+
+        let file: ScenarioFile = toml::from_str(text).map_err(|err| AssistantError::ScenarioParse {
+            path: path.to_string(),
+            line: err.span().map_or(1, |span| text[..span.start].matches('\n').count() + 1),
+            message: err.message().to_string()
+        })?;
+
+        let base = Path::new(path).parent().unwrap_or(Path::new(""));
+        let relative = |file: String| base.join(file).to_string_lossy().into_owned();
+
+        Ok(Scenario {
+            name: file.name.unwrap_or_else(|| Path::new(path).file_stem().map_or(String::new(), |stem| stem.to_string_lossy().into_owned())),
+            input: relative(file.input),
+            expected: relative(file.expected),
+            vpp: file.vpp,
+            processors: file.processors.into_iter().map(ProcessorSpec::from).collect(),
+            compare: file.compare
+        })
+    }
+
+    /// Comparator of the `[compare]` options for captures of the given `DataLink`.
+    pub fn comparator(&self, datalink: DataLink) -> PacketComparator {
+        self.compare.comparator(datalink)
+    }
+
+    /// Process the input with the processors and compare the processed packets with the expected capture,
+    /// see `PcapTester::process_and_compare_files_with`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a capture can't be read, if a filter is invalid for the `DataLink` of the input
+    /// or if a processor fails.
+    pub fn run(&self) -> AssistantResult<ComparisonReport> {
+
+        let datalink = open_capture::<Packet>(&self.input)?.current_datalink();
+        let comparator = self.comparator(datalink);
+        let mut chain = ProcessorChain::new();
+
+        for processor in &self.processors {
+            chain.push(match processor {
+                ProcessorSpec::Rewrite(rewrites) => Box::new(rewrites.iter().fold(FieldRewriter::new(datalink), |rewriter, rewrite| rewriter.with(*rewrite))),
+                ProcessorSpec::FixChecksums => Box::new(ChecksumFixer::new(datalink)),
+                ProcessorSpec::Filter(expression) => Box::new(BpfFilter::new(expression, datalink).map_err(invalid_filter(expression))?),
+                ProcessorSpec::DisplayFilter(expression) => Box::new(DisplayFilter::new(expression).map_err(invalid_filter(expression))?),
+                ProcessorSpec::Example { start, end, data } => Box::new(ProcessorExample::new(*start, *end, data.clone()))
+            });
+        }
+
+        let env = PcapTester::new(&self.expected);
+        match self.vpp {
+            true => env.process_and_compare_files_with::<_, VppPacket>(&self.input, &mut chain, &comparator),
+            false => env.process_and_compare_files_with::<_, Packet>(&self.input, &mut chain, &comparator)
+        }
+    }
+}
+
+fn invalid_filter(expression: &str) -> impl Fn(crate::FilterError) -> AssistantError + '_ {
+    move |source| AssistantError::InvalidFilter { expression: expression.to_string(), source }
+}
+
+// Scenario file as written, before the paths are resolved
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ScenarioFile {
+    name: Option<String>,
+    input: String,
+    expected: String,
+    #[serde(default)]
+    vpp: bool,
+    #[serde(default, rename = "processor")]
+    processors: Vec<ProcessorTable>,
+    #[serde(default)]
+    compare: ComparisonOptions
+}
+
+// [[processor]] table of a scenario file
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case", deny_unknown_fields)]
+enum ProcessorTable {
+    Rewrite {
+        #[serde(default, deserialize_with = "deserialize_rewrites")]
+        set: Vec<FieldRewrite>
+    },
+    FixChecksums,
+    Filter {
+        expression: String
+    },
+    DisplayFilter {
+        expression: String
+    },
+    Example {
+        #[serde(default)]
+        start: usize,
+        #[serde(default)]
+        end: usize,
+        #[serde(default, deserialize_with = "deserialize_hex")]
+        data: Vec<u8>
+    }
+}
+
+impl From<ProcessorTable> for ProcessorSpec {
+
+    fn from(table: ProcessorTable) -> ProcessorSpec {
+
+        match table {
+            ProcessorTable::Rewrite { set } => ProcessorSpec::Rewrite(set),
+            ProcessorTable::FixChecksums => ProcessorSpec::FixChecksums,
+            ProcessorTable::Filter { expression } => ProcessorSpec::Filter(expression),
+            ProcessorTable::DisplayFilter { expression } => ProcessorSpec::DisplayFilter(expression),
+            ProcessorTable::Example { start, end, data } => ProcessorSpec::Example { start, end, data }
+        }
+    }
+}
+
+// Field rewrites of an array of `<field>=<value>` strings
+fn deserialize_rewrites<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<FieldRewrite>, D::Error> {
+
+    Vec::<String>::deserialize(deserializer)?.iter().map(|assignment| {
+        let (field, value) = assignment.split_once('=')
+            .ok_or_else(|| D::Error::custom(format!("set expects '<field>=<value>', found '{}'", assignment)))?;
+        FieldRewrite::parse(field, value).map_err(|err| D::Error::custom(format!("set '{}': {}", assignment, err)))
+    }).collect()
+}
+
+fn deserialize_hex<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+    hex::decode(String::deserialize(deserializer)?).map_err(|_| D::Error::custom("data must be a hex string"))
+}
+
+/// Result of a scenario run by `run_scenarios`.
+#[derive(Debug)]
+pub struct ScenarioOutcome {
+
+    /// Path of the scenario file
+    pub path: String,
+
+    /// Name of the scenario, its path if it can't be parsed
+    pub name: String,
+    pub result: AssistantResult<ComparisonReport>
+}
+
+impl ScenarioOutcome {
+
+    /// Return true if the processed packets are equal to the expected ones.
+    pub fn passed(&self) -> bool {
+        matches!(&self.result, Ok(report) if report.is_equal())
+    }
+}
+
+/// Results of the scenarios of `run_scenarios`, in the order of their paths.
+#[derive(Debug, Default)]
+pub struct ScenarioReport {
+    pub outcomes: Vec<ScenarioOutcome>
+}
+
+impl ScenarioReport {
+
+    /// Return true if every scenario passed.
+    pub fn passed(&self) -> bool {
+        self.outcomes.iter().all(ScenarioOutcome::passed)
+    }
+
+    /// Render one line per scenario followed by the summary, the comparison reports of the failed scenarios
+    /// are rendered after their line if `verbose`.
+    pub fn render<W: Write>(&self, out: &mut W, verbose: bool) -> fmt::Result {
+
+        for outcome in &self.outcomes {
+            match &outcome.result {
+                Ok(report) if report.is_equal() => writeln!(out, "Scenario {}: {}", outcome.name, "PASS".green().bold())?,
+                Ok(report) => {
+                    let summary = report.summary();
+                    writeln!(
+                        out, "Scenario {}: {} ({} different, {} missing left, {} missing right)",
+                        outcome.name, "FAIL".red().bold(), summary.different, summary.missing_left, summary.missing_right
+                    )?;
+                    if verbose {
+                        report.render(out)?;
+                    }
+                },
+                Err(err) => {
+                    write!(out, "Scenario {}: {} {}", outcome.name, "ERROR".red().bold(), err)?;
+                    let mut source = std::error::Error::source(err);
+                    while let Some(err) = source {
+                        write!(out, ": {}", err)?;
+                        source = err.source();
+                    }
+                    writeln!(out)?;
+                }
+            }
+        }
+
+        let passed = self.outcomes.iter().filter(|outcome| outcome.passed()).count();
+        writeln!(out, "{} scenarios: {} passed, {} failed", self.outcomes.len(), passed, self.outcomes.len() - passed)
+    }
+
+    /// Print the report to stdout.
+    pub fn print(&self, verbose: bool) {
+
+        let mut out = String::new();
+        let _ = self.render(&mut out, verbose);
+        print!("{}", out);
+    }
+}
+
+impl fmt::Display for ScenarioReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.render(f, false)
+    }
+}
+
+/// Run the scenario files at `paths`, the `.toml` files of a directory are run in the order of their names.
+///
+/// A scenario which can't be parsed or run is reported as an error, the other scenarios still run.
+///
+/// # Errors
+///
+/// Returns `AssistantError::FileOpen` if a path can't be read.
+pub fn run_scenarios<S: AsRef<str>>(paths: &[S]) -> AssistantResult<ScenarioReport> {
+
+    let mut files = Vec::new();
+
+    for path in paths.iter().map(AsRef::as_ref) {
+        let open_error = |source| AssistantError::FileOpen { path: path.to_string(), source };
+
+        if Path::new(path).is_dir() {
+            let mut entries = Vec::new();
+            for entry in fs::read_dir(path).map_err(open_error)? {
+                let entry = entry.map_err(open_error)?.path();
+                if entry.extension().is_some_and(|extension| extension == "toml") {
+                    entries.push(entry.to_string_lossy().into_owned());
+                }
+            }
+            entries.sort();
+            files.extend(entries);
+        }
+        else {
+            files.push(path.to_string());
+        }
+    }
+
+    let outcomes = files.into_iter().map(|path| {
+        match Scenario::load(&path) {
+            Ok(scenario) => ScenarioOutcome { name: scenario.name.clone(), result: scenario.run(), path },
+            Err(err) => ScenarioOutcome { name: path.clone(), result: Err(err), path }
+        }
+    }).collect();
+
+    Ok(ScenarioReport { outcomes })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::write_capture;
+    use std::time::Duration;
+
+    #[test]
+    fn scenario_parse_test() {
+        let text = r#"
+            # Scenario of the test
+            input = "captures/input.pcap"
+            expected = 'captures/expected.pcap'
+
+            [[processor]]
+            type = "rewrite"
+            set = [
+                "ip.ttl=63",  # decremented
+                "ip.dst=10.0.0.2",
+            ]
+
+            [[processor]]
+            type = "example"
+            start = 2
+            data = "aabb"
+
+            [compare]
+            ignore = ["ip.checksum", "22:ff"]
+            match = "multiset:0.5"
+            relative_timestamps = true
+            map_interface = ["1=2"]
+        "#;
+
+        let scenario = Scenario::parse(text, "tests/ttl.toml").unwrap();
+        assert_eq!(scenario.name, "ttl");
+        assert_eq!((scenario.input.as_str(), scenario.expected.as_str(), scenario.vpp), ("tests/captures/input.pcap", "tests/captures/expected.pcap", false));
+        assert_eq!(scenario.processors, vec![
+            ProcessorSpec::Rewrite(vec![FieldRewrite::Ttl(63), FieldRewrite::Ipv4Dst([10, 0, 0, 2].into())]),
+            ProcessorSpec::Example { start: 2, end: 0, data: vec![0xAA, 0xBB] }
+        ]);
+        assert_eq!(scenario.compare.ignore, vec![IgnoreRule::Field("ip.checksum"), IgnoreRule::Mask { offset: 22, mask: vec![0xFF] }]);
+        assert_eq!(scenario.compare.strategy, MatchStrategy::Multiset { window: Some(Duration::from_millis(500)) });
+        let headers = scenario.compare.header_comparison();
+        assert!(headers.relative_timestamps && headers.checked.contains(&HeaderField::InterfaceIndex));
+        assert_eq!(headers.interface_map.get(&1), Some(&2));
+
+        let error_line = |text: &str| match Scenario::parse(text, "error.toml") {
+            Err(AssistantError::ScenarioParse { line, .. }) => line,
+            result => panic!("{:?}", result)
+        };
+        assert_eq!(error_line("input = \"a.pcap\"\nexpected = \"b.pcap\"\n\n[[processor]]\ntype = \"sort\""), 5);
+        assert_eq!(error_line("input = \"a.pcap\"\nexpected = \"b.pcap\"\nspeed = 2"), 3);
+        assert_eq!(error_line("input = \"a.pcap\"\nexpected = \"b.pcap\n"), 2);
+        assert_eq!(error_line("input = \"a.pcap\"\n[compare]\nignore = [\"ip.tll\"]"), 3);
+        assert_eq!(error_line("input = \"a.pcap\"\n\n[compare]\nmatch = \"index\""), 1);
+        assert_eq!(error_line("input = \"a.pcap\"\nexpected = \"b.pcap\"\n[compare]\nignore = [\"ip.foo\"]"), 4);
+    }
+
+    #[test]
+    fn run_scenarios_test() {
+        let dir = "run_scenarios_test";
+        fs::create_dir_all(dir).unwrap();
+
+        let write = |name: &str, packets: &[&[u8]]| write_capture(&format!("{}/{}", dir, name), packets);
+        write("input.pcap", &[&[1, 2, 3], &[4, 5, 6]]);
+        write("expected.pcap", &[&[0xAA, 1, 2, 3], &[0xAA, 4, 5, 6]]);
+
+        let scenario = "input = \"input.pcap\"\nexpected = \"expected.pcap\"\n[[processor]]\ntype = \"example\"\ndata = \"aa\"\n";
+        fs::write(format!("{}/a_pass.toml", dir), scenario).unwrap();
+        fs::write(format!("{}/b_fail.toml", dir), scenario.replace("\"aa\"", "\"bb\"")).unwrap();
+        fs::write(format!("{}/c_error.toml", dir), "input = \"missing.pcap\"\nexpected = \"expected.pcap\"\n").unwrap();
+        fs::write(format!("{}/notes.txt", dir), "not a scenario").unwrap();
+
+        let report = run_scenarios(&[dir]).unwrap();
+        fs::remove_dir_all(dir).unwrap();
+
+        assert_eq!(report.outcomes.iter().map(|outcome| (outcome.name.as_str(), outcome.passed())).collect::<Vec<_>>(), vec![
+            ("a_pass", true), ("b_fail", false), ("c_error", false)
+        ]);
+        assert!(matches!(report.outcomes[2].result, Err(AssistantError::FileOpen { .. })));
+        assert!(!report.passed());
+        assert!(report.to_string().ends_with("3 scenarios: 1 passed, 2 failed\n"));
+    }
+}
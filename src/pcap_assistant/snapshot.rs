@@ -0,0 +1,293 @@
+use crate::pcap::*;
+use crate::pcap_assistant::assistant::*;
+use crate::{AssistantError, AssistantResult, TsResolution};
+use std::path::Path;
+use std::{env, fs};
+
+
+/// Environment variable rewriting the golden captures of `assert_pcap_snapshot!` instead of failing,
+/// when it's set to a value other than `0`.
+pub const UPDATE_SNAPSHOTS_ENV: &str = "PCAP_ASSISTANT_UPDATE_SNAPSHOTS";
+
+/// Return true if `UPDATE_SNAPSHOTS_ENV` is set to a value other than `0`.
+pub fn update_snapshots() -> bool {
+    env::var_os(UPDATE_SNAPSHOTS_ENV).is_some_and(|value| !value.is_empty() && value != "0")
+}
+
+/// Result of the comparison of packets with a golden capture.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SnapshotOutcome {
+
+    /// The packets are equal to the golden capture
+    Matched,
+
+    /// The packets differ from the golden capture, which is left unchanged
+    Mismatched(ComparisonReport),
+
+    /// The golden capture was rewritten with the packets, with the report of the previous
+    /// golden capture or `None` if it was missing
+    Updated(Option<ComparisonReport>)
+}
+
+/// Packets which can be compared with a golden capture, see `assert_pcap_snapshot!`.
+///
+/// Implemented for the path of a produced capture (`str` or `String`), whose packets are read as `Packet`s,
+/// and for in-memory `Packet`s and `VppPacket`s. Use `check_file_snapshot` for captures in the VPP format.
+pub trait Snapshot {
+
+    /// Compare the packets with the `golden` capture, the golden capture is on the left side of the report.
+    ///
+    /// If `update`, a missing, unreadable or different golden capture is rewritten with the packets, in-memory packets
+    /// are saved with the `DataLink` of `comparator` and nanosecond timestamps.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a capture can't be read or the golden capture can't be written.
+    fn check_snapshot(&self, golden: &str, comparator: &PacketComparator, update: bool) -> AssistantResult<SnapshotOutcome>;
+}
+
+impl Snapshot for str {
+
+    fn check_snapshot(&self, golden: &str, comparator: &PacketComparator, update: bool) -> AssistantResult<SnapshotOutcome> {
+        check_file_snapshot::<Packet>(self, golden, comparator, update)
+    }
+}
+
+impl Snapshot for String {
+
+    fn check_snapshot(&self, golden: &str, comparator: &PacketComparator, update: bool) -> AssistantResult<SnapshotOutcome> {
+        self.as_str().check_snapshot(golden, comparator, update)
+    }
+}
+
+impl<'a> Snapshot for [Packet<'a>] {
+
+    fn check_snapshot(&self, golden: &str, comparator: &PacketComparator, update: bool) -> AssistantResult<SnapshotOutcome> {
+        check_packets::<Packet<'a>, Packet<'static>>(self, golden, comparator, update)
+    }
+}
+
+impl<'a> Snapshot for [VppPacket<'a>] {
+
+    fn check_snapshot(&self, golden: &str, comparator: &PacketComparator, update: bool) -> AssistantResult<SnapshotOutcome> {
+        check_packets::<VppPacket<'a>, VppPacket<'static>>(self, golden, comparator, update)
+    }
+}
+
+impl<P> Snapshot for Vec<P> where [P]: Snapshot {
+
+    fn check_snapshot(&self, golden: &str, comparator: &PacketComparator, update: bool) -> AssistantResult<SnapshotOutcome> {
+        self.as_slice().check_snapshot(golden, comparator, update)
+    }
+}
+
+impl<T: Snapshot + ?Sized> Snapshot for &T {
+
+    fn check_snapshot(&self, golden: &str, comparator: &PacketComparator, update: bool) -> AssistantResult<SnapshotOutcome> {
+        (**self).check_snapshot(golden, comparator, update)
+    }
+}
+
+/// Compare the `produced` capture with the `golden` capture like `Snapshot::check_snapshot`, both read as `P` packets.
+///
+/// If `update`, a missing, unreadable or different golden capture is replaced by a copy of the produced capture.
+///
+/// # Errors
+///
+/// Returns an error if a capture can't be read or the golden capture can't be written.
+pub fn check_file_snapshot<P: SomePacket<'static>>(produced: &str, golden: &str, comparator: &PacketComparator, update: bool) -> AssistantResult<SnapshotOutcome>
+    where P::Item: SomePacket<'static>
+{
+    check(
+        golden,
+        update,
+        || read_capture::<P>(golden),
+        || PcapTester::new(golden).compare_files_with::<P, P>(produced, comparator),
+        || {
+            read_capture::<P>(produced)?;
+            fs::copy(produced, golden).map(|_| ()).map_err(|source| AssistantError::FileCreate { path: golden.to_string(), source })
+        }
+    )
+}
+
+/// Check the `produced` packets against the `golden` capture, see `Snapshot`.
+///
+/// The comparison report is printed on a mismatch, and the golden capture is rewritten instead
+/// of failing if `update_snapshots()`.
+///
+/// # Panics
+///
+/// If the packets differ from the golden capture, or if a capture can't be read or written.
+#[track_caller]
+pub fn assert_snapshot<S: Snapshot + ?Sized>(produced: &S, golden: &str, comparator: &PacketComparator) {
+
+    let mut diff = String::new();
+
+    match produced.check_snapshot(golden, comparator, update_snapshots()) {
+        Ok(SnapshotOutcome::Matched) => {},
+        Ok(SnapshotOutcome::Updated(report)) => {
+            if let Some(report) = report {
+                let _ = report.render_failures(&mut diff);
+            }
+            eprint!("Updated snapshot {}\n{}", golden, diff);
+        },
+        Ok(SnapshotOutcome::Mismatched(report)) => {
+            let _ = report.render_failures(&mut diff);
+            panic!("Snapshot {} doesn't match the packets (left is the snapshot), set {}=1 to update it\n{}", golden, UPDATE_SNAPSHOTS_ENV, diff);
+        },
+        Err(err) => panic!("Snapshot {} can't be checked: {}, set {}=1 to create it", golden, err, UPDATE_SNAPSHOTS_ENV)
+    }
+}
+
+/// Assert that packets are equal to a golden capture, or rewrite the golden capture if the
+/// `PCAP_ASSISTANT_UPDATE_SNAPSHOTS` environment variable is set.
+///
+/// The packets are the path of a produced capture or in-memory `Packet`s or `VppPacket`s, see `Snapshot`.
+/// An optional `&PacketComparator` sets the ignore rules, the `MatchStrategy`, the `HeaderComparison`
+/// and the `DataLink` of the in-memory packets, Ethernet by default.
+///
+/// # Panics
+///
+/// If the packets differ from the golden capture, the packets which differ are printed in the panic message.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use pcap_assistant::assert_pcap_snapshot;
+/// use pcap_assistant::pcap::{Packet, SomePacket};
+/// use pcap_assistant::pcap_assistant::assistant::{PacketComparator, PcapTester, ProcessorExample};
+///
+/// let env = PcapTester::new("netinfo.pcap");
+/// env.process_and_save::<Packet>("processed.pcap", &mut ProcessorExample::new(0, 2, vec![0xAA, 0xBB])).unwrap();
+/// assert_pcap_snapshot!("processed.pcap", "snapshots/processed.pcap");
+///
+/// let comparator = PacketComparator::default().ignore("ip.checksum").unwrap();
+/// let packets = vec![Packet::new(0, 0, &[0xAA, 0xBB], 2)];
+/// assert_pcap_snapshot!(packets, "snapshots/packets.pcap", &comparator);
+/// ```
+#[macro_export]
+macro_rules! assert_pcap_snapshot {
+    ($produced:expr, $golden:expr $(,)?) => {
+        $crate::assert_pcap_snapshot!($produced, $golden, &$crate::pcap_assistant::assistant::PacketComparator::default())
+    };
+    ($produced:expr, $golden:expr, $comparator:expr $(,)?) => {
+        $crate::pcap_assistant::assistant::assert_snapshot(&$produced, $golden, $comparator)
+    };
+}
+
+// Compare in-memory packets with the golden capture read as G packets
+fn check_packets<'a, P, G>(packets: &[P], golden: &str, comparator: &PacketComparator, update: bool) -> AssistantResult<SnapshotOutcome>
+    where P: SomePacket<'a, Item = P> + Clone,
+          G: SomePacket<'static>,
+          G::Item: SomePacket<'static>,
+          PcapWriter<fs::File>: PacketWriter<P>
+{
+    let compare = || compare_packets(open_capture::<G>(golden)?, packets.iter().cloned().map(Ok), comparator);
+
+    let write = || {
+        let mut header = PcapHeader { datalink: comparator.datalink(), ..Default::default() };
+        header.set_ts_resolution(TsResolution::NanoSecond);
+
+        let mut writer = PcapWriter::with_header(header, create_file(golden)?).map_err(write_failure(golden))?;
+        for packet in packets {
+            writer.write_packet(packet.clone()).map_err(write_failure(golden))?;
+        }
+        Ok(())
+    };
+
+    check(golden, update, || read_capture::<G>(golden), compare, write)
+}
+
+// Read every packet of the capture, returns the first error
+fn read_capture<P: SomePacket<'static>>(path: &str) -> AssistantResult<()>
+    where P::Item: SomePacket<'static>
+{
+    open_capture::<P>(path)?.try_for_each(|packet| packet.map(|_| ()))
+}
+
+// Compare with the golden capture, and rewrite it with `write` if it's missing, unreadable or different when updating.
+// Only the errors of `read_golden` lead to a rewrite, the ones of the produced packets are returned.
+fn check<R, C, W>(golden: &str, update: bool, read_golden: R, compare: C, write: W) -> AssistantResult<SnapshotOutcome>
+    where R: FnOnce() -> AssistantResult<()>,
+          C: FnOnce() -> AssistantResult<ComparisonReport>,
+          W: FnOnce() -> AssistantResult<()>
+{
+    let create_parent = || match Path::new(golden).parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => fs::create_dir_all(parent)
+            .map_err(|source| AssistantError::FileCreate { path: golden.to_string(), source }),
+        _ => Ok(())
+    };
+
+    if update && !Path::new(golden).exists() {
+        create_parent()?;
+        write()?;
+        return Ok(SnapshotOutcome::Updated(None));
+    }
+
+    if update && read_golden().is_err() {
+        write()?;
+        return Ok(SnapshotOutcome::Updated(None));
+    }
+
+    let report = compare()?;
+
+    match (report.is_equal(), update) {
+        (true, _) => Ok(SnapshotOutcome::Matched),
+        (false, false) => Ok(SnapshotOutcome::Mismatched(report)),
+        (false, true) => {
+            write()?;
+            Ok(SnapshotOutcome::Updated(Some(report)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshot_test() {
+        let golden = "snapshot_test/golden.pcap";
+        let data: [&[u8]; 2] = [&[1, 2, 3], &[4, 5, 6]];
+        let packets: Vec<Packet> = data.iter().enumerate().map(|(index, data)| Packet::new(index as u32, 7000, data, data.len() as u32)).collect();
+        let comparator = PacketComparator::default();
+
+        assert!(matches!(packets.check_snapshot(golden, &comparator, false), Err(AssistantError::FileOpen { .. })));
+        assert_eq!(packets.check_snapshot(golden, &comparator, true).unwrap(), SnapshotOutcome::Updated(None));
+        assert_eq!(packets.check_snapshot(golden, &comparator, false).unwrap(), SnapshotOutcome::Matched);
+        assert_pcap_snapshot!(packets, golden);
+
+        let mut changed = packets.clone();
+        changed[1] = Packet::new(1, 7000, &[4, 9, 6], 3);
+        let report = match changed.check_snapshot(golden, &comparator, false).unwrap() {
+            SnapshotOutcome::Mismatched(report) => report,
+            outcome => panic!("{:?}", outcome)
+        };
+        assert_eq!((report.summary().equal, report.summary().different), (1, 1));
+
+        let produced = "snapshot_test/produced.pcap";
+        PcapTester::save_packets_to_new_pcap(produced, PcapHeader::default(), changed.clone()).unwrap();
+        assert_eq!(produced.check_snapshot(golden, &comparator, true).unwrap(), SnapshotOutcome::Updated(Some(report)));
+        assert_eq!(fs::read(produced).unwrap(), fs::read(golden).unwrap());
+        assert_pcap_snapshot!(produced.to_string(), golden, &comparator);
+
+        assert!(matches!(packets.check_snapshot(golden, &comparator, false).unwrap(), SnapshotOutcome::Mismatched(_)));
+
+        fs::write(golden, [0xDE, 0xAD]).unwrap();
+        assert!(packets.check_snapshot(golden, &comparator, false).is_err());
+        assert_eq!(packets.check_snapshot(golden, &comparator, true).unwrap(), SnapshotOutcome::Updated(None));
+        assert_eq!(packets.check_snapshot(golden, &comparator, false).unwrap(), SnapshotOutcome::Matched);
+
+        // Errors of the produced capture leave the golden capture unchanged
+        let missing = "snapshot_test/missing.pcap";
+        assert!(matches!(missing.check_snapshot(golden, &comparator, true), Err(AssistantError::FileOpen { path, .. }) if path == missing));
+        fs::write(produced, [0xDE, 0xAD]).unwrap();
+        assert!(matches!(produced.check_snapshot(golden, &comparator, true), Err(AssistantError::HeaderParse { .. })));
+        fs::write(golden, [0xDE, 0xAD]).unwrap();
+        assert!(matches!(produced.check_snapshot(golden, &comparator, true), Err(AssistantError::HeaderParse { .. })));
+        assert_eq!(fs::read(golden).unwrap(), [0xDE, 0xAD]);
+        assert!(produced.check_snapshot("snapshot_test/new.pcap", &comparator, true).is_err());
+        assert!(!Path::new("snapshot_test/new.pcap").exists());
+        fs::remove_dir_all("snapshot_test").unwrap();
+    }
+}
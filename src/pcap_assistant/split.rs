@@ -0,0 +1,350 @@
+use crate::dissect::{Dissection, IP_PROTO_ICMP, IP_PROTO_ICMPV6, IP_PROTO_TCP, IP_PROTO_UDP};
+use crate::pcap::*;
+use crate::pcap_assistant::assistant::{create_file, write_failure};
+use crate::{AssistantError, AssistantResult, Endianness};
+use byteorder::{BigEndian, LittleEndian};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Read};
+use std::net::IpAddr;
+use std::time::Duration;
+
+
+/// Number of files a `CaptureSplitter` keeps open by default, see `CaptureSplitter::max_open_files`.
+pub const DEFAULT_MAX_OPEN_FILES: usize = 64;
+
+/// When a `CaptureSplitter` starts a new output file.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SplitPolicy {
+
+    /// Every N packets
+    Packets(usize),
+
+    /// Every interval of capture time, starting at the first packet
+    Duration(Duration),
+
+    /// When the file would exceed this size in bytes, a larger packet is written alone
+    Bytes(u64),
+
+    /// One file per flow: IP protocol, addresses and ports, both directions in the same file
+    Flow,
+
+    /// One file per `VppPacketHeader::interface_index`
+    Interface
+}
+
+/// Splits the packets of a `PcapReader` in several files, according to a `SplitPolicy`.
+///
+/// The files are named after a template where `{index}` is replaced with the number of the file,
+/// from 0 in creation order, and `{key}` with the flow (`udp_10.0.0.1_4789_10.0.0.2_12345`), the
+/// interface index or the number of the file for the other policies. Without any of them `_{index}`
+/// is added before the extension. Every file has the header of the split capture.
+///
+/// With `SplitPolicy::Flow` and `SplitPolicy::Interface`, at most `max_open_files` files are open at once:
+/// the least recently written one is closed, and reopened in append mode for its next packet.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use std::fs::File;
+/// use pcap_assistant::pcap::{PcapReader, VppPacket};
+/// use pcap_assistant::pcap_assistant::assistant::{CaptureSplitter, SplitPolicy};
+///
+/// let reader = PcapReader::<_, VppPacket>::new(File::open("vpp_netinfo.pcap").unwrap()).unwrap();
+///
+/// let splitter = CaptureSplitter::new(SplitPolicy::Interface, "interface_{key}.pcap");
+/// let files = splitter.split(reader).unwrap();
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CaptureSplitter {
+    policy: SplitPolicy,
+    template: String,
+    max_open_files: usize
+}
+
+// Output file, whose writer is closed when it isn't one of the most recently written files
+struct Output {
+    writer: Option<PcapWriter<BufWriter<File>>>,
+    path: String,
+    packets: usize,
+    bytes: u64,
+    last_write: usize
+}
+
+impl Output {
+
+    // Create the file, or reopen it in append mode after its first packet
+    fn open(&mut self, header: PcapHeader) -> AssistantResult<&mut PcapWriter<BufWriter<File>>> {
+
+        let writer = match self.packets {
+            0 => PcapWriter::with_header(header, BufWriter::new(create_file(&self.path)?)).map_err(write_failure(&self.path))?,
+            _ => {
+                let file = OpenOptions::new().append(true).open(&self.path)
+                    .map_err(|source| AssistantError::FileOpen { path: self.path.clone(), source })?;
+                PcapWriter::without_header(header, BufWriter::new(file))
+            }
+        };
+
+        Ok(self.writer.insert(writer))
+    }
+
+    // Flush and close the file
+    fn close(&mut self) -> AssistantResult<()> {
+
+        if let Some(writer) = self.writer.take() {
+            writer.into_writer().into_inner()
+                .map_err(|error| AssistantError::WriteFailure { path: self.path.clone(), source: error.into_error().into() })?;
+        }
+
+        Ok(())
+    }
+}
+
+impl CaptureSplitter {
+
+    pub fn new(policy: SplitPolicy, template: &str) -> CaptureSplitter {
+
+        CaptureSplitter { policy, template: template.to_string(), max_open_files: DEFAULT_MAX_OPEN_FILES }
+    }
+
+    /// Keep at most `max_open_files` files open, at least 1, `DEFAULT_MAX_OPEN_FILES` by default.
+    pub fn max_open_files(mut self, max_open_files: usize) -> CaptureSplitter {
+        self.max_open_files = max_open_files.max(1);
+        self
+    }
+
+    pub fn policy(&self) -> SplitPolicy {
+        self.policy
+    }
+
+    /// Name of the file `index`, with the given key.
+    pub fn file_name(&self, index: usize, key: &str) -> String {
+
+        if !self.template.contains("{index}") && !self.template.contains("{key}") {
+            let extension = self.template.rfind('.').filter(|dot| !self.template[*dot..].contains('/'));
+            let (stem, extension) = self.template.split_at(extension.unwrap_or(self.template.len()));
+            return format!("{}_{}{}", stem, index, extension);
+        }
+
+        self.template.replace("{index}", &index.to_string()).replace("{key}", key)
+    }
+
+    /// Write the packets of the reader to the split files, returns their paths in creation order.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a packet can't be read or a file can't be written.
+    pub fn split<T: Read, P: SomePacket<'static>>(&self, mut reader: PcapReader<T, P>) -> AssistantResult<Vec<String>>
+        where P::Item: SomePacket<'static>,
+              PcapWriter<BufWriter<File>>: PacketWriter<P::Item>
+    {
+        let header = reader.header;
+        let mut outputs: HashMap<String, Output> = HashMap::new();
+        // Keys of the open files by their last write
+        let mut open: BTreeMap<usize, String> = BTreeMap::new();
+        let mut paths = Vec::new();
+        // Key of the rolling file, and the start of its interval for `SplitPolicy::Duration`
+        let mut current: Option<(String, Duration)> = None;
+        let mut record_header_len = None;
+        let mut index = 0;
+
+        loop {
+            let offset = reader.position();
+            let packet = match reader.next() {
+                Some(packet) => packet.map_err(|source| AssistantError::PacketParse { index, offset, source })?,
+                None => break
+            };
+            index += 1;
+
+            let packet_header = packet.get_header();
+            let record_header_len = *record_header_len.get_or_insert_with(|| written_len(&header, &packet_header));
+            let record_len = (record_header_len + packet.get_data().len()) as u64;
+
+            let key = match self.policy {
+                SplitPolicy::Flow => flow_key(&packet.dissect(header.datalink)),
+                SplitPolicy::Interface => packet_header.interface_index().map_or("none".to_string(), |index| index.to_string()),
+                _ => {
+                    let timestamp = packet_header.timestamp();
+                    let roll = match (&current, self.policy) {
+                        (None, _) => true,
+                        (Some((key, _)), SplitPolicy::Packets(count)) => outputs[key].packets >= count.max(1),
+                        (Some((key, _)), SplitPolicy::Bytes(bytes)) => outputs[key].packets > 0 && outputs[key].bytes + record_len > bytes,
+                        (Some((_, start)), SplitPolicy::Duration(interval)) => !interval.is_zero() && timestamp >= *start + interval,
+                        _ => false
+                    };
+
+                    if roll {
+                        let start = match (&current, self.policy) {
+                            // Intervals stay aligned on the first packet, the empty ones are skipped
+                            (Some((_, start)), SplitPolicy::Duration(interval)) if !interval.is_zero() => {
+                                let elapsed = (timestamp - *start).as_nanos() / interval.as_nanos();
+                                *start + Duration::from_nanos((interval.as_nanos() * elapsed) as u64)
+                            },
+                            _ => timestamp
+                        };
+                        current = Some((paths.len().to_string(), start));
+                    }
+
+                    current.as_ref().map(|(key, _)| key.clone()).unwrap_or_default()
+                }
+            };
+
+            if !outputs.contains_key(&key) {
+                // The rolling files are complete once the next one is started
+                if !matches!(self.policy, SplitPolicy::Flow | SplitPolicy::Interface) {
+                    finish(outputs.drain())?;
+                    open.clear();
+                }
+
+                let path = self.file_name(paths.len(), &key);
+                paths.push(path.clone());
+                outputs.insert(key.clone(), Output { writer: None, path, packets: 0, bytes: 24, last_write: 0 });
+            }
+
+            let last_write = std::mem::replace(&mut outputs.get_mut(&key).expect("output created above").last_write, index);
+            open.remove(&last_write);
+            open.insert(index, key.clone());
+
+            // Close the least recently written file before opening another one
+            if outputs[&key].writer.is_none() && open.len() > self.max_open_files {
+                let (_, least_recent) = open.pop_first().expect("more open files than the limit");
+                outputs.get_mut(&least_recent).expect("open output").close()?;
+            }
+
+            let output = outputs.get_mut(&key).expect("output created above");
+            let writer = match output.writer {
+                Some(ref mut writer) => writer,
+                None => output.open(header)?
+            };
+            writer.write_packet(packet).map_err(write_failure(&output.path))?;
+            output.packets += 1;
+            output.bytes += record_len;
+        }
+
+        finish(outputs.drain())?;
+
+        Ok(paths)
+    }
+}
+
+// Flush and close the output files
+fn finish<I: Iterator<Item = (String, Output)>>(outputs: I) -> AssistantResult<()> {
+
+    for (_, mut output) in outputs {
+        output.close()?;
+    }
+
+    Ok(())
+}
+
+// Length of a packet header in the file
+fn written_len<H: SomePacketHeader>(header: &PcapHeader, packet_header: &H) -> usize {
+
+    let mut written = Vec::new();
+    let _ = match header.endianness() {
+        Endianness::Big => packet_header.write_to::<_, BigEndian>(&mut written, header.ts_resolution()),
+        Endianness::Little => packet_header.write_to::<_, LittleEndian>(&mut written, header.ts_resolution())
+    };
+
+    written.len()
+}
+
+// Name of the flow of the packet, the same for both directions
+fn flow_key(dissection: &Dissection) -> String {
+
+    let (src, dst, protocol): (IpAddr, IpAddr, u8) = if let Some(ipv4) = dissection.ipv4() {
+        (ipv4.src().into(), ipv4.dst().into(), ipv4.protocol())
+    }
+    else if let Some(ipv6) = dissection.ipv6() {
+        let protocol = ipv6.upper_layer().map_or(ipv6.next_header(), |(protocol, _, _)| protocol);
+        (ipv6.src().into(), ipv6.dst().into(), protocol)
+    }
+    else {
+        return "other".to_string();
+    };
+
+    let (src_port, dst_port) = match (dissection.tcp(), dissection.udp()) {
+        (Some(tcp), _) => (tcp.src_port(), tcp.dst_port()),
+        (_, Some(udp)) => (udp.src_port(), udp.dst_port()),
+        _ => (0, 0)
+    };
+
+    let name = match protocol {
+        IP_PROTO_TCP => "tcp".to_string(),
+        IP_PROTO_UDP => "udp".to_string(),
+        IP_PROTO_ICMP => "icmp".to_string(),
+        IP_PROTO_ICMPV6 => "icmpv6".to_string(),
+        other => format!("ip{}", other)
+    };
+
+    let (lhs, rhs) = if (src, src_port) <= (dst, dst_port) { ((src, src_port), (dst, dst_port)) } else { ((dst, dst_port), (src, src_port)) };
+
+    // ':' of the IPv6 addresses isn't allowed in every file system
+    format!("{}_{}_{}_{}_{}", name, lhs.0, lhs.1, rhs.0, rhs.1).replace(':', "-")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures;
+    use std::fs;
+    use std::io::Cursor;
+
+    fn udp(src_port: u8, dst_port: u8) -> Vec<u8> {
+        let mut frame = vec![0x02, 0, 0, 0, 0, 2, 0x02, 0, 0, 0, 0, 1, 0x08, 0x00];
+        frame.extend_from_slice(&[0x45, 0, 0, 28, 0, 0, 0, 0, 64, 17, 0, 0, 10, 0, 0, src_port, 10, 0, 0, dst_port]);
+        frame.extend_from_slice(&[0, src_port, 0, dst_port, 0, 8, 0, 0]);
+        frame
+    }
+
+    fn capture(packets: &[(u32, Vec<u8>)]) -> PcapReader<Cursor<Vec<u8>>, VppPacket<'static>> {
+        let packets = packets.iter().enumerate().map(|(index, (ts_sec, data))| {
+            let mut packet = VppPacket::new(*ts_sec, 0, data, data.len() as u32);
+            packet.header.interface_index = index as u32 % 2;
+            packet
+        });
+        PcapReader::new(Cursor::new(test_fixtures::capture(PcapHeader::default(), packets))).unwrap()
+    }
+
+    fn packet_counts(paths: &[String]) -> Vec<usize> {
+        paths.iter().map(|path| {
+            let count = PcapReader::<_, VppPacket>::new(File::open(path).unwrap()).unwrap().count();
+            fs::remove_file(path).unwrap();
+            count
+        }).collect()
+    }
+
+    #[test]
+    fn capture_splitter_test() {
+        let packets = vec![(0, udp(1, 2)), (1, udp(2, 1)), (2, udp(1, 3)), (7, udp(1, 2)), (8, vec![0; 4])];
+
+        let splitter = CaptureSplitter::new(SplitPolicy::Packets(2), "split_test.pcap");
+        let paths = splitter.split(capture(&packets)).unwrap();
+        assert_eq!(paths, vec!["split_test_0.pcap", "split_test_1.pcap", "split_test_2.pcap"]);
+        assert_eq!(packet_counts(&paths), vec![2, 2, 1]);
+
+        let splitter = CaptureSplitter::new(SplitPolicy::Duration(Duration::from_secs(3)), "split_duration_{index}_test.pcap");
+        assert_eq!(packet_counts(&splitter.split(capture(&packets)).unwrap()), vec![3, 2]);
+
+        // 24 bytes of header, 62 bytes per UDP packet
+        let splitter = CaptureSplitter::new(SplitPolicy::Bytes(24 + 62 * 2), "split_bytes_{index}_test.pcap");
+        assert_eq!(packet_counts(&splitter.split(capture(&packets)).unwrap()), vec![2, 2, 1]);
+
+        let splitter = CaptureSplitter::new(SplitPolicy::Flow, "split_{key}_test.pcap");
+        let paths = splitter.split(capture(&packets)).unwrap();
+        assert_eq!(paths, vec!["split_udp_10.0.0.1_1_10.0.0.2_2_test.pcap", "split_udp_10.0.0.1_1_10.0.0.3_3_test.pcap", "split_other_test.pcap"]);
+        assert_eq!(packet_counts(&paths), vec![3, 1, 1]);
+
+        // A single open file, the flows are closed and reopened in append mode in turn
+        let splitter = CaptureSplitter::new(SplitPolicy::Flow, "split_{key}_test.pcap").max_open_files(1);
+        let paths = splitter.split(capture(&packets)).unwrap();
+        let first_flow = PcapReader::<_, VppPacket>::new(File::open(&paths[0]).unwrap()).unwrap();
+        assert_eq!(first_flow.map(|packet| packet.unwrap().header.ts_sec).collect::<Vec<_>>(), vec![0, 1, 7]);
+        assert_eq!(packet_counts(&paths), vec![3, 1, 1]);
+
+        let splitter = CaptureSplitter::new(SplitPolicy::Interface, "split_interface_{key}_test.pcap");
+        let paths = splitter.split(capture(&packets)).unwrap();
+        assert_eq!(paths, vec!["split_interface_0_test.pcap", "split_interface_1_test.pcap"]);
+        assert_eq!(packet_counts(&paths), vec![3, 2]);
+    }
+}
@@ -0,0 +1,7 @@
+mod myblocks;
+mod myreader;
+mod mywriter;
+
+pub use myblocks::*;
+pub use myreader::*;
+pub use mywriter::*;
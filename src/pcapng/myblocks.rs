@@ -0,0 +1,591 @@
+use byteorder::{BigEndian, ByteOrder, LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::{
+    myerrors::*,
+    DataLink,
+    Endianness
+};
+
+use std::{
+    io::Read,
+    io::Write,
+    net::{Ipv4Addr, Ipv6Addr}
+};
+
+/// Block type of a Section Header Block, identical in both endiannesses
+pub const SECTION_HEADER_BLOCK: u32 = 0x0A0D0D0A;
+/// Block type of an Interface Description Block
+pub const INTERFACE_DESCRIPTION_BLOCK: u32 = 0x00000001;
+/// Block type of a Simple Packet Block
+pub const SIMPLE_PACKET_BLOCK: u32 = 0x00000003;
+/// Block type of a Name Resolution Block
+pub const NAME_RESOLUTION_BLOCK: u32 = 0x00000004;
+/// Block type of an Interface Statistics Block
+pub const INTERFACE_STATISTICS_BLOCK: u32 = 0x00000005;
+/// Block type of an Enhanced Packet Block
+pub const ENHANCED_PACKET_BLOCK: u32 = 0x00000006;
+
+/// Byte order magic of the Section Header Block
+pub const BYTE_ORDER_MAGIC: u32 = 0x1A2B3C4D;
+
+/// Interface Description Block option holding the name of the interface
+pub const IF_NAME: u16 = 2;
+/// Interface Description Block option holding the timestamp resolution
+pub const IF_TSRESOL: u16 = 9;
+/// Interface Description Block option holding the timestamp offset in seconds
+pub const IF_TSOFFSET: u16 = 14;
+
+/// Prefix of the `if_name` of the interfaces written for `VppPacket`s, followed by their interface index
+pub const VPP_INTERFACE_PREFIX: &str = "vpp";
+
+/// Generic option (code, value) attached to a block.
+///
+/// The value is kept raw, without its padding.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BlockOption {
+
+    /// Option code
+    pub code: u16,
+
+    /// Raw value of the option
+    pub value: Vec<u8>
+}
+
+/// Section Header Block, starts every section of a pcapng.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SectionHeaderBlock {
+
+    /// Endianness of every block of the section
+    pub endianness: Endianness,
+
+    /// Major version number, should be 1
+    pub major_version: u16,
+
+    /// Minor version number, should be 0
+    pub minor_version: u16,
+
+    /// Length of the section in bytes, -1 if unspecified
+    pub section_length: i64,
+
+    /// Options of the block
+    pub options: Vec<BlockOption>
+}
+
+/// Interface Description Block, describes an interface on which packets are captured.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InterfaceDescriptionBlock {
+
+    /// DataLink type of the interface
+    pub linktype: DataLink,
+
+    /// Max length of captured packet, 0 if unlimited
+    pub snaplen: u32,
+
+    /// Options of the block
+    pub options: Vec<BlockOption>
+}
+
+/// Enhanced Packet Block, a packet captured on a given interface.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct EnhancedPacketBlock {
+
+    /// Index of the interface in the current section
+    pub interface_id: u32,
+
+    /// Timestamp in units of the interface timestamp resolution
+    pub timestamp: u64,
+
+    /// Original length of the packet on the wire
+    pub original_len: u32,
+
+    /// Captured data
+    pub data: Vec<u8>,
+
+    /// Options of the block
+    pub options: Vec<BlockOption>
+}
+
+/// Simple Packet Block, a packet captured on the first interface without timestamp.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SimplePacketBlock {
+
+    /// Original length of the packet on the wire
+    pub original_len: u32,
+
+    /// Captured data
+    pub data: Vec<u8>
+}
+
+/// Record of a Name Resolution Block.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum NameRecord {
+    Ipv4 {
+        addr: Ipv4Addr,
+        names: Vec<String>
+    },
+    Ipv6 {
+        addr: Ipv6Addr,
+        names: Vec<String>
+    },
+    Unknown {
+        record_type: u16,
+        value: Vec<u8>
+    }
+}
+
+/// Name Resolution Block, maps addresses to names.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NameResolutionBlock {
+
+    /// Name records of the block
+    pub records: Vec<NameRecord>,
+
+    /// Options of the block
+    pub options: Vec<BlockOption>
+}
+
+/// Interface Statistics Block, capture statistics of an interface.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InterfaceStatisticsBlock {
+
+    /// Index of the interface in the current section
+    pub interface_id: u32,
+
+    /// Timestamp in units of the interface timestamp resolution
+    pub timestamp: u64,
+
+    /// Options of the block
+    pub options: Vec<BlockOption>
+}
+
+/// A pcapng block.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Block {
+    SectionHeader(SectionHeaderBlock),
+    InterfaceDescription(InterfaceDescriptionBlock),
+    EnhancedPacket(EnhancedPacketBlock),
+    SimplePacket(SimplePacketBlock),
+    NameResolution(NameResolutionBlock),
+    InterfaceStatistics(InterfaceStatisticsBlock),
+
+    /// Block of a type not handled by this crate
+    Unknown {
+        block_type: u32,
+        body: Vec<u8>
+    }
+}
+
+impl Block {
+
+    /// Read the next block from a reader.
+    ///
+    /// `endianness` is the endianness of the current section,
+    /// it is ignored for Section Header Blocks which carry their own.
+    pub fn from_reader<R: Read>(reader: &mut R, endianness: Endianness) -> ResultParsing<Block> {
+
+        let mut raw_type = [0_u8; 4];
+        reader.read_exact(&mut raw_type)?;
+
+        let mut raw_len = [0_u8; 4];
+        reader.read_exact(&mut raw_len)?;
+
+        if u32::from_be_bytes(raw_type) == SECTION_HEADER_BLOCK {
+
+            let mut raw_magic = [0_u8; 4];
+            reader.read_exact(&mut raw_magic)?;
+
+            let endianness = match u32::from_be_bytes(raw_magic) {
+                BYTE_ORDER_MAGIC => Endianness::Big,
+                0x4D3C2B1A => Endianness::Little,
+                _ => return Err(PcapError::InvalidField("SectionHeaderBlock wrong byte order magic"))
+            };
+
+            let block_len = match endianness {
+                Endianness::Big => u32::from_be_bytes(raw_len),
+                Endianness::Little => u32::from_le_bytes(raw_len)
+            };
+
+            // Type, length and magic are already consumed
+            let body = read_body(reader, block_len, 4)?;
+            let shb = match endianness {
+                Endianness::Big => SectionHeaderBlock::from_body::<BigEndian>(&body, endianness)?,
+                Endianness::Little => SectionHeaderBlock::from_body::<LittleEndian>(&body, endianness)?
+            };
+
+            return Ok(Block::SectionHeader(shb));
+        }
+
+        match endianness {
+            Endianness::Big => parse_block::<_, BigEndian>(reader, raw_type, raw_len),
+            Endianness::Little => parse_block::<_, LittleEndian>(reader, raw_type, raw_len)
+        }
+    }
+}
+
+// Parse a non Section Header Block once its type and length are known
+fn parse_block<R: Read, B: ByteOrder>(reader: &mut R, raw_type: [u8; 4], raw_len: [u8; 4]) -> ResultParsing<Block> {
+
+    let block_type = B::read_u32(&raw_type);
+    let block_len = B::read_u32(&raw_len);
+    let body = read_body(reader, block_len, 0)?;
+    let mut slice = &body[..];
+
+    let block = match block_type {
+
+        INTERFACE_DESCRIPTION_BLOCK => {
+            let linktype = DataLink::from(slice.read_u16::<B>()? as u32);
+            let _reserved = slice.read_u16::<B>()?;
+            let snaplen = slice.read_u32::<B>()?;
+
+            Block::InterfaceDescription(InterfaceDescriptionBlock {
+                linktype,
+                snaplen,
+                options: parse_options::<B>(slice)?
+            })
+        },
+
+        ENHANCED_PACKET_BLOCK => {
+            let interface_id = slice.read_u32::<B>()?;
+            let ts_high = slice.read_u32::<B>()? as u64;
+            let ts_low = slice.read_u32::<B>()? as u64;
+            let captured_len = slice.read_u32::<B>()? as usize;
+            let original_len = slice.read_u32::<B>()?;
+
+            let padded_len = padded(captured_len);
+            if slice.len() < padded_len {
+                return Err(PcapError::InvalidField("EnhancedPacketBlock captured_len > block length"));
+            }
+
+            let data = slice[..captured_len].to_vec();
+
+            Block::EnhancedPacket(EnhancedPacketBlock {
+                interface_id,
+                timestamp: (ts_high << 32) | ts_low,
+                original_len,
+                data,
+                options: parse_options::<B>(&slice[padded_len..])?
+            })
+        },
+
+        SIMPLE_PACKET_BLOCK => {
+            let original_len = slice.read_u32::<B>()?;
+
+            // The captured length is the smaller of original_len and the remaining body
+            let captured_len = (original_len as usize).min(slice.len());
+
+            Block::SimplePacket(SimplePacketBlock {
+                original_len,
+                data: slice[..captured_len].to_vec()
+            })
+        },
+
+        NAME_RESOLUTION_BLOCK => {
+            let mut records = Vec::new();
+
+            loop {
+                let record_type = slice.read_u16::<B>()?;
+                let record_len = slice.read_u16::<B>()? as usize;
+
+                if record_type == 0 {
+                    break;
+                }
+
+                if slice.len() < padded(record_len) {
+                    return Err(PcapError::InvalidField("NameResolutionBlock record length > block length"));
+                }
+
+                let value = &slice[..record_len];
+                records.push(parse_name_record(record_type, value)?);
+                slice = &slice[padded(record_len)..];
+            }
+
+            Block::NameResolution(NameResolutionBlock {
+                records,
+                options: parse_options::<B>(slice)?
+            })
+        },
+
+        INTERFACE_STATISTICS_BLOCK => {
+            let interface_id = slice.read_u32::<B>()?;
+            let ts_high = slice.read_u32::<B>()? as u64;
+            let ts_low = slice.read_u32::<B>()? as u64;
+
+            Block::InterfaceStatistics(InterfaceStatisticsBlock {
+                interface_id,
+                timestamp: (ts_high << 32) | ts_low,
+                options: parse_options::<B>(slice)?
+            })
+        },
+
+        _ => Block::Unknown {
+            block_type,
+            body
+        }
+    };
+
+    Ok(block)
+}
+
+impl SectionHeaderBlock {
+
+    /// Write a `SectionHeaderBlock` to a writer, in its own endianness.
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> ResultParsing<()> {
+
+        match self.endianness {
+            Endianness::Big => self.write_body::<_, BigEndian>(writer),
+            Endianness::Little => self.write_body::<_, LittleEndian>(writer)
+        }
+    }
+
+    fn write_body<W: Write, B: ByteOrder>(&self, writer: &mut W) -> ResultParsing<()> {
+
+        let mut body = Vec::new();
+        body.write_u32::<B>(BYTE_ORDER_MAGIC)?;
+        body.write_u16::<B>(self.major_version)?;
+        body.write_u16::<B>(self.minor_version)?;
+        body.write_i64::<B>(self.section_length)?;
+        write_options::<B>(&mut body, &self.options)?;
+
+        write_block::<_, B>(writer, SECTION_HEADER_BLOCK, &body)
+    }
+
+    // Parse the body following the byte order magic
+    fn from_body<B: ByteOrder>(mut slice: &[u8], endianness: Endianness) -> ResultParsing<SectionHeaderBlock> {
+
+        let major_version = slice.read_u16::<B>()?;
+        let minor_version = slice.read_u16::<B>()?;
+        let section_length = slice.read_i64::<B>()?;
+
+        if major_version != 1 {
+            return Err(PcapError::InvalidField("SectionHeaderBlock unsupported major version"));
+        }
+
+        Ok(
+            SectionHeaderBlock {
+                endianness,
+                major_version,
+                minor_version,
+                section_length,
+                options: parse_options::<B>(slice)?
+            }
+        )
+    }
+}
+
+impl Default for SectionHeaderBlock {
+    fn default() -> Self {
+        SectionHeaderBlock {
+            endianness: Endianness::Big,
+            major_version: 1,
+            minor_version: 0,
+            section_length: -1,
+            options: Vec::new()
+        }
+    }
+}
+
+impl InterfaceDescriptionBlock {
+
+    /// Write an `InterfaceDescriptionBlock` to a writer.
+    pub fn write_to<W: Write, B: ByteOrder>(&self, writer: &mut W) -> ResultParsing<()> {
+
+        let mut body = Vec::new();
+        body.write_u16::<B>(u32::from(self.linktype) as u16)?;
+        body.write_u16::<B>(0)?;
+        body.write_u32::<B>(self.snaplen)?;
+        write_options::<B>(&mut body, &self.options)?;
+
+        write_block::<_, B>(writer, INTERFACE_DESCRIPTION_BLOCK, &body)
+    }
+
+    /// Return the value of the `if_name` option, if any.
+    pub fn name(&self) -> Option<String> {
+
+        self.options.iter()
+            .find(|opt| opt.code == IF_NAME)
+            .map(|opt| String::from_utf8_lossy(&opt.value).into_owned())
+    }
+
+    /// Return the VPP interface index of an interface named `vpp<index>`, see `VPP_INTERFACE_PREFIX`.
+    pub fn vpp_interface_index(&self) -> Option<u32> {
+        self.name()?.strip_prefix(VPP_INTERFACE_PREFIX)?.parse().ok()
+    }
+
+    /// Return the number of timestamp units per second, from the `if_tsresol` option.
+    ///
+    /// Defaults to microseconds when the option is absent.
+    pub fn ts_units_per_sec(&self) -> u64 {
+
+        let tsresol = self.options.iter()
+            .find(|opt| opt.code == IF_TSRESOL && !opt.value.is_empty())
+            .map(|opt| opt.value[0])
+            .unwrap_or(6);
+
+        let exponent = (tsresol & 0x7F) as u32;
+
+        if tsresol & 0x80 == 0 {
+            10_u64.checked_pow(exponent).unwrap_or(u64::MAX)
+        }
+        else {
+            1_u64.checked_shl(exponent).unwrap_or(u64::MAX)
+        }
+    }
+
+    /// Return the offset in seconds to add to every timestamp, from the `if_tsoffset` option.
+    ///
+    /// `endianness` is the endianness of the section containing the block.
+    pub fn ts_offset(&self, endianness: Endianness) -> i64 {
+
+        self.options.iter()
+            .find(|opt| opt.code == IF_TSOFFSET && opt.value.len() == 8)
+            .map(|opt| match endianness {
+                Endianness::Big => BigEndian::read_i64(&opt.value),
+                Endianness::Little => LittleEndian::read_i64(&opt.value)
+            })
+            .unwrap_or(0)
+    }
+
+    /// Convert a timestamp of this interface to seconds and nanoseconds.
+    pub fn split_timestamp(&self, timestamp: u64, endianness: Endianness) -> (u32, u32) {
+
+        let units = self.ts_units_per_sec();
+        let secs = (timestamp / units) as i64 + self.ts_offset(endianness);
+        let nsecs = ((timestamp % units) as u128 * 1_000_000_000 / units as u128) as u32;
+
+        (secs as u32, nsecs)
+    }
+}
+
+impl EnhancedPacketBlock {
+
+    /// Write an `EnhancedPacketBlock` to a writer.
+    pub fn write_to<W: Write, B: ByteOrder>(&self, writer: &mut W) -> ResultParsing<()> {
+
+        let mut body = Vec::with_capacity(20 + padded(self.data.len()));
+        body.write_u32::<B>(self.interface_id)?;
+        body.write_u32::<B>((self.timestamp >> 32) as u32)?;
+        body.write_u32::<B>(self.timestamp as u32)?;
+        body.write_u32::<B>(self.data.len() as u32)?;
+        body.write_u32::<B>(self.original_len)?;
+        body.extend_from_slice(&self.data);
+        body.resize(padded(body.len()), 0);
+        write_options::<B>(&mut body, &self.options)?;
+
+        write_block::<_, B>(writer, ENHANCED_PACKET_BLOCK, &body)
+    }
+}
+
+// Write a whole block around an already padded body
+fn write_block<W: Write, B: ByteOrder>(writer: &mut W, block_type: u32, body: &[u8]) -> ResultParsing<()> {
+
+    let block_len = (body.len() + 12) as u32;
+
+    writer.write_u32::<B>(block_type)?;
+    writer.write_u32::<B>(block_len)?;
+    writer.write_all(body)?;
+    writer.write_u32::<B>(block_len)?;
+
+    Ok(())
+}
+
+fn write_options<B: ByteOrder>(body: &mut Vec<u8>, options: &[BlockOption]) -> ResultParsing<()> {
+
+    if options.is_empty() {
+        return Ok(());
+    }
+
+    for option in options {
+        body.write_u16::<B>(option.code)?;
+        body.write_u16::<B>(option.value.len() as u16)?;
+        body.extend_from_slice(&option.value);
+        body.resize(padded(body.len()), 0);
+    }
+
+    // opt_endofopt
+    body.write_u32::<B>(0)?;
+
+    Ok(())
+}
+
+// Read a block body, leaving out the trailing total length
+fn read_body<R: Read>(reader: &mut R, block_len: u32, already_read: u32) -> ResultParsing<Vec<u8>> {
+
+    // Type, leading length and trailing length
+    let overhead = 12 + already_read;
+
+    if block_len < overhead || !block_len.is_multiple_of(4) {
+        return Err(PcapError::InvalidField("Block wrong total length"));
+    }
+
+    // Grow the body with the data actually read rather than trusting the declared length
+    let body_len = (block_len - overhead) as usize;
+    let mut body = Vec::new();
+    reader.by_ref().take(body_len as u64).read_to_end(&mut body)?;
+    if body.len() < body_len {
+        return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+    }
+
+    let mut trailing_len = [0_u8; 4];
+    reader.read_exact(&mut trailing_len)?;
+
+    Ok(body)
+}
+
+fn parse_options<B: ByteOrder>(mut slice: &[u8]) -> ResultParsing<Vec<BlockOption>> {
+
+    let mut options = Vec::new();
+
+    while slice.len() >= 4 {
+
+        let code = slice.read_u16::<B>()?;
+        let len = slice.read_u16::<B>()? as usize;
+
+        // opt_endofopt
+        if code == 0 {
+            break;
+        }
+
+        if slice.len() < len {
+            return Err(PcapError::InvalidField("BlockOption length > block length"));
+        }
+
+        options.push(BlockOption {
+            code,
+            value: slice[..len].to_vec()
+        });
+
+        slice = &slice[padded(len).min(slice.len())..];
+    }
+
+    Ok(options)
+}
+
+fn parse_name_record(record_type: u16, value: &[u8]) -> ResultParsing<NameRecord> {
+
+    fn parse_names(raw: &[u8]) -> ResultParsing<Vec<String>> {
+        raw.split(|b| *b == 0)
+            .filter(|name| !name.is_empty())
+            .map(|name| Ok(String::from_utf8(name.to_vec())?))
+            .collect()
+    }
+
+    Ok(
+        match record_type {
+            1 if value.len() >= 4 => {
+                let addr = Ipv4Addr::new(value[0], value[1], value[2], value[3]);
+                NameRecord::Ipv4 { addr, names: parse_names(&value[4..])? }
+            },
+            2 if value.len() >= 16 => {
+                let mut raw = [0_u8; 16];
+                raw.copy_from_slice(&value[..16]);
+                NameRecord::Ipv6 { addr: Ipv6Addr::from(raw), names: parse_names(&value[16..])? }
+            },
+            _ => NameRecord::Unknown { record_type, value: value.to_vec() }
+        }
+    )
+}
+
+/// Length rounded up to the next multiple of 4.
+pub(crate) fn padded(len: usize) -> usize {
+    (len + 3) & !3
+}
@@ -0,0 +1,323 @@
+use crate::{
+    DataLink,
+    Endianness,
+    myerrors::*,
+    pcap::{SomePacket, SomePacketHeader},
+    pcapng::myblocks::*,
+    peek_reader::PeekReader
+};
+
+use std::{io::Read, marker::PhantomData};
+
+
+/// Wraps another reader and uses it to read a PcapNg formated stream.
+///
+/// It implements the Iterator trait in order to read one packet at a time.
+/// Enhanced and Simple Packet Blocks are yielded as packets, the other blocks
+/// update the state of the reader (interfaces, name resolution, statistics).
+///
+/// For `VppPacket`, the interface index of an interface named `vpp<index>` by `PcapNgWriter`
+/// is stored in `VppPacketHeader::interface_index`, otherwise its interface id.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use std::fs::File;
+/// use pcap_assistant::pcap::Packet;
+/// use pcap_assistant::pcapng::PcapNgReader;
+///
+/// let file_in = File::open("test.pcapng").expect("Error opening file");
+/// let pcapng_reader = PcapNgReader::<_, Packet>::new(file_in).unwrap();
+///
+/// // Read test.pcapng
+/// for packet in pcapng_reader {
+///
+///     //Check if there is no error
+///     let packet = packet.unwrap();
+///
+///     //Do something
+/// }
+/// ```
+#[derive(Debug)]
+pub struct PcapNgReader<T: Read, P: SomePacket<'static>> {
+
+    phantom_data: PhantomData<P>,
+
+    /// Section Header Block of the current section
+    pub section: SectionHeaderBlock,
+
+    /// Interfaces described in the current section
+    pub interfaces: Vec<InterfaceDescriptionBlock>,
+
+    /// Name Resolution Blocks read so far
+    pub name_resolutions: Vec<NameResolutionBlock>,
+
+    /// Interface Statistics Blocks read so far
+    pub statistics: Vec<InterfaceStatisticsBlock>,
+
+    /// Interface id of the last packet read in the current section
+    packet_interface: Option<u32>,
+
+    reader: PeekReader<T>
+}
+
+impl<T: Read, P: SomePacket<'static>> PcapNgReader<T, P> {
+
+    /// Create a new PcapNgReader from an existing reader.
+    /// This function reads the first Section Header Block of the stream.
+    ///
+    /// # Errors
+    /// Return an error if the data stream doesn't start with a valid Section Header Block.
+    /// Or if the underlying data are not readable.
+    pub fn new(reader: T) -> ResultParsing<Self> {
+
+        let mut reader = PeekReader::new(reader);
+
+        let section = match Block::from_reader(&mut reader, Endianness::Big)? {
+            Block::SectionHeader(shb) => shb,
+            _ => return Err(PcapError::InvalidField("PcapNg doesn't start with a SectionHeaderBlock"))
+        };
+
+        Ok(
+            Self {
+                phantom_data: Default::default(),
+                section,
+                interfaces: Vec::new(),
+                name_resolutions: Vec::new(),
+                statistics: Vec::new(),
+                packet_interface: None,
+                reader
+            }
+        )
+    }
+
+    /// Read the next block, updating the state of the reader.
+    ///
+    /// Returns `None` at the end of the stream.
+    pub fn next_block(&mut self) -> Option<ResultParsing<Block>> {
+
+        match self.reader.is_empty() {
+            Ok(is_empty) if is_empty => {
+                return None;
+            },
+            Err(err) => return Some(Err(err.into())),
+            _ => {}
+        }
+
+        let block = match Block::from_reader(&mut self.reader, self.section.endianness) {
+            Ok(block) => block,
+            Err(err) => return Some(Err(err))
+        };
+
+        match &block {
+            Block::SectionHeader(shb) => {
+                self.section = shb.clone();
+                self.interfaces.clear();
+                self.packet_interface = None;
+            },
+            Block::InterfaceDescription(idb) => self.interfaces.push(idb.clone()),
+            Block::NameResolution(nrb) => self.name_resolutions.push(nrb.clone()),
+            Block::InterfaceStatistics(isb) => self.statistics.push(isb.clone()),
+            _ => {}
+        }
+
+        Some(Ok(block))
+    }
+
+    /// Byte offset of the next block from the start of the stream.
+    pub fn position(&self) -> u64 {
+        self.reader.position
+    }
+
+    /// DataLink of the first interface of the current section.
+    ///
+    /// Returns `DataLink::ETHERNET` if no interface has been described yet.
+    pub fn datalink(&self) -> DataLink {
+        self.interfaces.first().map(|idb| idb.linktype).unwrap_or(DataLink::ETHERNET)
+    }
+
+    /// DataLink of the interface of the last packet read, interfaces may have different link types.
+    ///
+    /// Returns `datalink()` before the first packet of the section.
+    pub fn packet_datalink(&self) -> DataLink {
+
+        self.packet_interface
+            .and_then(|id| self.interfaces.get(id as usize))
+            .map_or_else(|| self.datalink(), |idb| idb.linktype)
+    }
+
+    /// Consumes the `PcapNgReader`, returning the wrapped reader.
+    pub fn into_reader(self) -> T {
+        self.reader.inner
+    }
+
+    /// Gets a reference to the underlying reader.
+    ///
+    /// It is not advised to directly read from the underlying reader.
+    pub fn get_ref(&self) -> &T {
+        &self.reader.inner
+    }
+
+    /// Gets a mutable reference to the underlying reader.
+    ///
+    /// It is not advised to directly read from the underlying reader.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.reader.inner
+    }
+
+    // Convert a packet block to a packet of type P
+    fn to_packet(&self, interface_id: u32, timestamp: u64, original_len: u32, data: Vec<u8>) -> ResultParsing<P::Item> {
+
+        let interface = self.interfaces.get(interface_id as usize)
+            .ok_or(PcapError::InvalidField("Packet block refers to an undescribed interface"))?;
+
+        let (ts_sec, ts_nsec) = interface.split_timestamp(timestamp, self.section.endianness);
+        let interface_index = interface.vpp_interface_index().unwrap_or(interface_id);
+
+        let mut header = P::Header::new(ts_sec, ts_nsec, data.len() as u32, original_len);
+        header.set_interface_index(interface_index);
+
+        Ok(P::from_parts(header, data))
+    }
+}
+
+
+impl<T: Read, P: SomePacket<'static>> Iterator for PcapNgReader<T, P> {
+
+    type Item = ResultParsing<P::Item>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+
+        loop {
+            let block = match self.next_block()? {
+                Ok(block) => block,
+                Err(err) => return Some(Err(err))
+            };
+
+            match block {
+                Block::EnhancedPacket(epb) => {
+                    self.packet_interface = Some(epb.interface_id);
+                    return Some(self.to_packet(epb.interface_id, epb.timestamp, epb.original_len, epb.data));
+                },
+                Block::SimplePacket(mut spb) => {
+                    // The captured length of a Simple Packet Block is bounded by the snaplen of the first interface
+                    if let Some(snaplen) = self.interfaces.first().map(|idb| idb.snaplen as usize).filter(|len| *len > 0) {
+                        spb.data.truncate(snaplen);
+                    }
+
+                    self.packet_interface = Some(0);
+                    return Some(self.to_packet(0, 0, spb.original_len, spb.data));
+                },
+                _ => continue
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pcap::*;
+    use crate::pcapng::*;
+    use crate::{CaptureReader, DataLink};
+    use std::io::Cursor;
+
+    fn block(block_type: u32, body: &[u8]) -> Vec<u8> {
+        let len = (body.len() + 12) as u32;
+        let mut out = Vec::new();
+        out.extend_from_slice(&block_type.to_le_bytes());
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(body);
+        out.extend_from_slice(&len.to_le_bytes());
+        out
+    }
+
+    fn sample_pcapng() -> Vec<u8> {
+        let mut out = Vec::new();
+
+        let mut shb = Vec::new();
+        shb.extend_from_slice(&BYTE_ORDER_MAGIC.to_le_bytes());
+        shb.extend_from_slice(&1_u16.to_le_bytes());
+        shb.extend_from_slice(&0_u16.to_le_bytes());
+        shb.extend_from_slice(&(-1_i64).to_le_bytes());
+        out.extend(block(SECTION_HEADER_BLOCK, &shb));
+
+        // Two interfaces, the second one of another link type with nanosecond resolution
+        let mut idb = Vec::new();
+        idb.extend_from_slice(&1_u16.to_le_bytes());
+        idb.extend_from_slice(&0_u16.to_le_bytes());
+        idb.extend_from_slice(&0_u32.to_le_bytes());
+        out.extend(block(INTERFACE_DESCRIPTION_BLOCK, &idb));
+        idb[0..2].copy_from_slice(&113_u16.to_le_bytes());
+        idb.extend_from_slice(&IF_TSRESOL.to_le_bytes());
+        idb.extend_from_slice(&1_u16.to_le_bytes());
+        idb.extend_from_slice(&[9, 0, 0, 0]);
+        idb.extend_from_slice(&[0, 0, 0, 0]);
+        out.extend(block(INTERFACE_DESCRIPTION_BLOCK, &idb));
+
+        let mut nrb = Vec::new();
+        nrb.extend_from_slice(&1_u16.to_le_bytes());
+        nrb.extend_from_slice(&9_u16.to_le_bytes());
+        nrb.extend_from_slice(&[10, 0, 0, 1, b'h', b'o', b's', b't', 0, 0, 0, 0]);
+        nrb.extend_from_slice(&[0, 0, 0, 0]);
+        out.extend(block(NAME_RESOLUTION_BLOCK, &nrb));
+
+        let timestamp: u64 = 3_000_000_005;
+        let mut epb = Vec::new();
+        epb.extend_from_slice(&1_u32.to_le_bytes());
+        epb.extend_from_slice(&((timestamp >> 32) as u32).to_le_bytes());
+        epb.extend_from_slice(&(timestamp as u32).to_le_bytes());
+        epb.extend_from_slice(&5_u32.to_le_bytes());
+        epb.extend_from_slice(&60_u32.to_le_bytes());
+        epb.extend_from_slice(&[1, 2, 3, 4, 5, 0, 0, 0]);
+        out.extend(block(ENHANCED_PACKET_BLOCK, &epb));
+
+        let mut isb = Vec::new();
+        isb.extend_from_slice(&0_u32.to_le_bytes());
+        isb.extend_from_slice(&[0; 8]);
+        out.extend(block(INTERFACE_STATISTICS_BLOCK, &isb));
+
+        let mut spb = Vec::new();
+        spb.extend_from_slice(&3_u32.to_le_bytes());
+        spb.extend_from_slice(&[7, 8, 9, 0]);
+        out.extend(block(SIMPLE_PACKET_BLOCK, &spb));
+
+        out
+    }
+
+    #[test]
+    fn pcapng_reader_test() {
+        let mut reader = PcapNgReader::<_, VppPacket>::new(Cursor::new(sample_pcapng())).unwrap();
+
+        let packet = reader.next().unwrap().unwrap();
+        assert_eq!(packet.header.interface_index, 1);
+        assert_eq!((reader.datalink(), reader.packet_datalink()), (DataLink::ETHERNET, DataLink::LINUX_SLL));
+        assert_eq!((packet.header.ts_sec, packet.header.ts_nsec), (3, 5));
+        assert_eq!((packet.header.incl_len, packet.header.orig_len), (5, 60));
+        assert_eq!(packet.data.as_ref(), &[1, 2, 3, 4, 5]);
+
+        let packet = reader.next().unwrap().unwrap();
+        assert_eq!(packet.header.interface_index, 0);
+        assert_eq!(packet.data.as_ref(), &[7, 8, 9]);
+        assert_eq!(reader.packet_datalink(), DataLink::ETHERNET);
+
+        assert!(reader.next().is_none());
+        assert_eq!(reader.interfaces.len(), 2);
+        assert_eq!(reader.statistics.len(), 1);
+        assert_eq!(
+            reader.name_resolutions[0].records,
+            vec![NameRecord::Ipv4 { addr: "10.0.0.1".parse().unwrap(), names: vec!["host".to_string()] }]
+        );
+
+        let packets: Vec<_> = CaptureReader::<_, Packet>::new(Cursor::new(sample_pcapng())).unwrap().collect();
+        assert_eq!(packets.len(), 2);
+
+        // Block declaring about 4 GiB followed by a few bytes
+        let mut truncated = sample_pcapng();
+        truncated.extend_from_slice(&ENHANCED_PACKET_BLOCK.to_le_bytes());
+        truncated.extend_from_slice(&0xffff_fff0_u32.to_le_bytes());
+        truncated.extend_from_slice(&[0; 28]);
+        let packets: Vec<_> = PcapNgReader::<_, Packet>::new(Cursor::new(truncated)).unwrap().collect();
+        assert_eq!(packets.len(), 3);
+        assert!(packets[2].is_err());
+    }
+}
@@ -0,0 +1,240 @@
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+
+use crate::{
+    Endianness,
+    myerrors::*,
+    pcap::{Packet, PacketHeader, PacketWriter, PcapHeader, SomePacketHeader, VppPacket, VppPacketHeader},
+    pcapng::myblocks::*
+};
+
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    io::Write
+};
+
+/// `if_tsresol` value written by the writer: nanoseconds, to keep `ts_nsec` intact
+const NANOSECOND_TSRESOL: u8 = 9;
+
+
+/// This struct wraps another writer and uses it to write a PcapNg formated stream.
+///
+/// An Interface Description Block is written the first time a packet with a new
+/// interface is met. For `VppPacket`, there is one interface per distinct
+/// `VppPacketHeader::interface_index`, named `vpp<interface_index>` so that `PcapNgReader`
+/// restores the interface index whatever the order of the interfaces.
+/// `Packet` doesn't record an interface and is always written on the same one.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use std::fs::File;
+/// use pcap_assistant::pcap::{PacketWriter, PcapReader, VppPacket};
+/// use pcap_assistant::pcapng::PcapNgWriter;
+///
+/// let file_in = File::open("vpp.pcap").expect("Error opening file");
+/// let pcap_reader = PcapReader::<_, VppPacket>::new(file_in).unwrap();
+///
+/// let file_out = File::create("out.pcapng").expect("Error creating file out");
+/// let mut pcapng_writer = PcapNgWriter::with_header(pcap_reader.header, file_out).unwrap();
+///
+/// for packet in pcap_reader {
+///     pcapng_writer.write_packet(packet.unwrap()).unwrap();
+/// }
+/// ```
+#[derive(Debug)]
+pub struct PcapNgWriter<W: Write> {
+
+    /// Header giving the endianness, the datalink and the snaplen of the interfaces
+    pub header: PcapHeader,
+
+    /// Interface id in the section of every interface index already written
+    interface_ids: HashMap<Option<u32>, u32>,
+
+    writer: W
+}
+
+impl<'a, W: Write> PacketWriter<Packet<'a>> for PcapNgWriter<W> {
+
+    /// Writes some raw data as an Enhanced Packet Block.
+    fn write(&mut self, ts_sec: u32, ts_nsec: u32, data: &[u8], orig_len: u32) -> ResultParsing<()> {
+
+        let packet = Packet {
+            header: PacketHeader::new(ts_sec, ts_nsec, data.len() as u32, orig_len),
+            data: Cow::Borrowed(data)
+        };
+
+        Self::write_packet(self, packet)
+    }
+
+    /// Writes a `Packet` as an Enhanced Packet Block.
+    fn write_packet(&mut self, packet: Packet) -> ResultParsing<()> {
+        self.write_enhanced_packet(&packet.header, &packet.data, packet.header.orig_len)
+    }
+}
+
+impl<'a, W: Write> PacketWriter<VppPacket<'a>> for PcapNgWriter<W> {
+
+    /// Writes some raw data as an Enhanced Packet Block on the interface 0.
+    fn write(&mut self, ts_sec: u32, ts_nsec: u32, data: &[u8], orig_len: u32) -> ResultParsing<()> {
+
+        let packet = VppPacket {
+            header: VppPacketHeader::new(ts_sec, ts_nsec, data.len() as u32, orig_len),
+            data: Cow::Borrowed(data)
+        };
+
+        Self::write_packet(self, packet)
+    }
+
+    /// Writes a `VppPacket` as an Enhanced Packet Block on the interface of its `interface_index`.
+    fn write_packet(&mut self, packet: VppPacket) -> ResultParsing<()> {
+        self.write_enhanced_packet(&packet.header, &packet.data, packet.header.orig_len)
+    }
+}
+
+impl<W: Write> PcapNgWriter<W> {
+
+    /// Creates a new `PcapNgWriter` from an existing writer with the default `PcapHeader`
+    /// in the native endianness of the CPU.
+    ///
+    /// Automatically writes the Section Header Block.
+    ///
+    /// # Errors
+    ///
+    /// Return an error if the writer can't be written to.
+    pub fn new(writer: W) -> ResultParsing<PcapNgWriter<W>> {
+
+        let mut header = PcapHeader::default();
+        header.set_endianness(Endianness::new::<byteorder::NativeEndian>());
+
+        PcapNgWriter::with_header(header, writer)
+    }
+
+    /// Create a new `PcapNgWriter` from an existing writer.
+    ///
+    /// The endianness of the section comes from the magic number of `header`,
+    /// the datalink and snaplen are used for every Interface Description Block.
+    /// The timestamp resolution is always nanoseconds.
+    ///
+    /// Automatically writes the Section Header Block.
+    ///
+    /// # Errors
+    ///
+    /// Return an error if the writer can't be written to.
+    pub fn with_header(header: PcapHeader, mut writer: W) -> ResultParsing<PcapNgWriter<W>> {
+
+        let section = SectionHeaderBlock {
+            endianness: header.endianness(),
+            ..Default::default()
+        };
+        section.write_to(&mut writer)?;
+
+        Ok(
+            PcapNgWriter {
+                header,
+                interface_ids: HashMap::new(),
+                writer
+            }
+        )
+    }
+
+    /// Consumes the `PcapNgWriter`, returning the wrapped writer.
+    pub fn into_writer(self) -> W {
+        self.writer
+    }
+
+    /// Gets a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Gets a mutable reference to the underlying writer.
+    ///
+    /// It is inadvisable to directly write to the underlying writer.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    fn write_enhanced_packet<H: SomePacketHeader>(&mut self, header: &H, data: &[u8], orig_len: u32) -> ResultParsing<()> {
+
+        match self.header.endianness() {
+            Endianness::Big => self.write_enhanced_packet_in::<_, BigEndian>(header, data, orig_len),
+            Endianness::Little => self.write_enhanced_packet_in::<_, LittleEndian>(header, data, orig_len)
+        }
+    }
+
+    fn write_enhanced_packet_in<H: SomePacketHeader, B: ByteOrder>(&mut self, header: &H, data: &[u8], orig_len: u32) -> ResultParsing<()> {
+
+        let interface_id = self.interface_id::<B>(header.interface_index())?;
+        let timestamp = header.timestamp().as_nanos() as u64;
+
+        let block = EnhancedPacketBlock {
+            interface_id,
+            timestamp,
+            original_len: orig_len,
+            data: data.to_vec(),
+            options: Vec::new()
+        };
+
+        block.write_to::<_, B>(&mut self.writer)
+    }
+
+    // Return the id of the interface of `interface_index`, writing its description first if needed
+    fn interface_id<B: ByteOrder>(&mut self, interface_index: Option<u32>) -> ResultParsing<u32> {
+
+        if let Some(id) = self.interface_ids.get(&interface_index) {
+            return Ok(*id);
+        }
+
+        let mut options = vec![BlockOption { code: IF_TSRESOL, value: vec![NANOSECOND_TSRESOL] }];
+        if let Some(index) = interface_index {
+            options.push(BlockOption { code: IF_NAME, value: format!("{}{}", VPP_INTERFACE_PREFIX, index).into_bytes() });
+        }
+
+        let idb = InterfaceDescriptionBlock {
+            linktype: self.header.datalink,
+            snaplen: self.header.snaplen,
+            options
+        };
+        idb.write_to::<_, B>(&mut self.writer)?;
+
+        let id = self.interface_ids.len() as u32;
+        self.interface_ids.insert(interface_index, id);
+
+        Ok(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pcap::*;
+    use crate::pcapng::*;
+    use std::borrow::Cow;
+    use std::io::Cursor;
+
+    #[test]
+    fn pcapng_writer_test() {
+        let mut writer = PcapNgWriter::new(Vec::new()).unwrap();
+
+        for (ts_nsec, interface_index) in [(1, 7), (2, 3), (3, 7)] {
+            let packet = VppPacket {
+                header: VppPacketHeader { ts_sec: 10, ts_nsec, incl_len: 2, orig_len: 4, interface_index },
+                data: Cow::Owned(vec![0xAB, ts_nsec as u8])
+            };
+            writer.write_packet(packet).unwrap();
+        }
+
+        let mut reader = PcapNgReader::<_, VppPacket>::new(Cursor::new(writer.into_writer())).unwrap();
+        let packets: Vec<_> = reader.by_ref().map(|packet| packet.unwrap()).collect();
+
+        let names: Vec<_> = reader.interfaces.iter().map(|idb| idb.name().unwrap()).collect();
+        assert_eq!(names, vec!["vpp7", "vpp3"]);
+
+        let indexes: Vec<_> = packets.iter().map(|packet| packet.header.interface_index).collect();
+        assert_eq!(indexes, vec![7, 3, 7]);
+
+        assert_eq!((packets[2].header.ts_sec, packets[2].header.ts_nsec), (10, 3));
+        assert_eq!(packets[2].header.orig_len, 4);
+        assert_eq!(packets[2].data.as_ref(), &[0xAB, 3]);
+    }
+}
@@ -4,6 +4,8 @@ use std::io::{Read, Result as IoResult, Seek, SeekFrom};
 pub(crate) struct PeekReader<R: Read> {
     pub inner: R,
     pub peeked: Option<u8>,
+    /// Number of bytes consumed from the start of the stream
+    pub position: u64,
 }
 
 impl<R: Read> Read for PeekReader<R> {
@@ -20,10 +22,16 @@ impl<R: Read> Read for PeekReader<R> {
             self.peeked = None;
 
             //Read the input and add one to the number of byte read
-            self.inner.read(&mut buf[1..]).map(|x| x+1)
+            let nb_read = self.inner.read(&mut buf[1..]).map(|x| x+1)?;
+            self.position += nb_read as u64;
+
+            Ok(nb_read)
         }
         else {
-            self.inner.read(buf)
+            let nb_read = self.inner.read(buf)?;
+            self.position += nb_read as u64;
+
+            Ok(nb_read)
         }
     }
 }
@@ -34,7 +42,9 @@ impl<R: Read + Seek> Seek for PeekReader<R> {
             self.inner.seek(SeekFrom::Current(-1))?;
             self.peeked = None;
         }
-        self.inner.seek(pos)
+        self.position = self.inner.seek(pos)?;
+
+        Ok(self.position)
     }
 }
 
@@ -43,7 +53,8 @@ impl<R: Read> PeekReader<R> {
     pub fn new(inner: R) -> PeekReader<R> {
         PeekReader {
             inner,
-            peeked: None
+            peeked: None,
+            position: 0
         }
     }
 
@@ -61,6 +72,8 @@ impl<R: Read> PeekReader<R> {
             match nb_read {
                 0 => true,
                 1 => {
+                    // The peeked byte is not consumed yet
+                    self.position -= 1;
                     self.peeked = Some(buf[0]);
                     false
                 },
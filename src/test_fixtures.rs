@@ -0,0 +1,24 @@
+use crate::pcap::*;
+use std::fs;
+
+
+/// Capture of `packets` with the given header, as the bytes of a pcap file.
+pub(crate) fn capture<P>(header: PcapHeader, packets: impl IntoIterator<Item = P>) -> Vec<u8>
+    where PcapWriter<Vec<u8>>: PacketWriter<P>
+{
+    let mut writer = PcapWriter::with_header(header, Vec::new()).unwrap();
+    for packet in packets {
+        writer.write_packet(packet).unwrap();
+    }
+    writer.into_writer()
+}
+
+/// Packets with the given data, whose timestamp is their index in seconds.
+pub(crate) fn packets(data: &[&[u8]]) -> Vec<Packet<'static>> {
+    data.iter().enumerate().map(|(index, data)| Packet::new_owned(index as u32, 0, data.to_vec(), data.len() as u32)).collect()
+}
+
+/// Write the capture of `packets(data)` at `path`, with the default header.
+pub(crate) fn write_capture(path: &str, data: &[&[u8]]) {
+    fs::write(path, capture(PcapHeader::default(), packets(data))).unwrap();
+}